@@ -7,7 +7,8 @@ use std::cell::Cell;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::panel_buttons::workspace_button::hyprland_service::HyprlandService;
+use crate::config::PanelLayoutConfig;
+use crate::panel_buttons::workspace_button::workspace_service::WorkspaceService;
 use crate::system_panel::SystemPanel;
 
 struct PanelEntry {
@@ -39,6 +40,8 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
     app.connect_activate(move |app| {
       let display = gdk::Display::default().expect("Could not get default display");
 
+      WorkspaceService::set_orphan_policy(PanelLayoutConfig::load_from_file().workspaces.orphan_policy);
+
       // Initial panel creation
       sync_panels(app, &panels);
 
@@ -127,15 +130,13 @@ fn sync_panels(app: &adw::Application, panels: &Rc<RefCell<Vec<PanelEntry>>>) {
     }
   });
 
-  // If monitors were removed and at least one remains, move any workspaces
-  // still assigned to the now-gone monitors to the first remaining monitor.
-  // Pass the GDK connector list directly — Hyprland's IPC monitor list can
+  // If monitors were removed, move any workspaces still assigned to the
+  // now-gone monitors according to the configured orphan policy.
+  // Pass the GDK connector list directly — the compositor's IPC monitor list can
   // lag behind GDK when multiple monitors disconnect simultaneously.
   if any_removed {
-    if let Some((fallback, _)) = current.first() {
-      let active_connectors: Vec<String> = current.iter().map(|(c, _)| c.clone()).collect();
-      HyprlandService::move_orphaned_workspaces_to(fallback, &active_connectors);
-    }
+    let active_connectors: Vec<String> = current.iter().map(|(c, _)| c.clone()).collect();
+    WorkspaceService::rehome_orphaned_workspaces(&active_connectors);
   }
 
   // Create panels for monitors that don't have one yet
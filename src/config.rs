@@ -1,5 +1,7 @@
 use gtk4_layer_shell::Layer;
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
 
 #[derive(Clone)]
 pub struct WaltoPanelConfig {
@@ -43,27 +45,35 @@ pub struct PanelLayoutConfig {
   pub center: Vec<PanelButtonConfig>,
   #[serde(default)]
   pub right: Vec<PanelButtonConfig>,
+  #[serde(default)]
+  pub workspaces: WorkspacesConfig,
 }
 
 impl PanelLayoutConfig {
   pub fn default_layout() -> Self {
-    Self {
-      left: vec![PanelButtonConfig::Launch {
-        icon: "view-app-grid-symbolic".to_string(),
-        command: "pkill rofi || /home/billy/.config/waltoland/scripts/rofi-alphabetical-apps.sh"
-          .to_string(),
+    serde_json::from_value(Self::default_layout_json()).expect("default layout should parse")
+  }
+
+  /// The layout used without a config file, as it would be written in one
+  fn default_layout_json() -> serde_json::Value {
+    serde_json::json!({
+      "left": [{
+        "type": "launch",
+        "icon": "view-app-grid-symbolic",
+        "command": "pkill rofi || /home/billy/.config/waltoland/scripts/rofi-alphabetical-apps.sh",
       }],
-      center: vec![],
-      right: vec![PanelButtonConfig::Clock],
-    }
+      "right": [{ "type": "clock" }],
+    })
   }
 
-  pub fn load_from_file() -> Self {
-    let config_path = std::env::var("HOME").ok().map(|home| {
-      std::path::PathBuf::from(home).join(".config/waltopanel/config.json")
-    });
+  fn config_path() -> Option<PathBuf> {
+    std::env::var("HOME").ok().map(|home| {
+      PathBuf::from(home).join(".config/waltopanel/config.json")
+    })
+  }
 
-    if let Some(path) = config_path {
+  pub fn load_from_file() -> Self {
+    if let Some(path) = Self::config_path() {
       if path.exists() {
         match std::fs::read_to_string(&path) {
           Ok(content) => match serde_json::from_str::<PanelLayoutConfig>(&content) {
@@ -77,16 +87,56 @@ impl PanelLayoutConfig {
 
     Self::default_layout()
   }
+
+  /// Apply `update` to the JSON of every button of `button_type` in config.json, e.g. to
+  /// remember a setting changed from the panel. A missing config.json is created from the
+  /// default layout. Other settings and buttons are left as they are.
+  pub fn update_button_config(button_type: &str, update: impl Fn(&mut serde_json::Value)) -> Result<(), String> {
+    let path = Self::config_path().ok_or("HOME is not set")?;
+
+    let original = match std::fs::read_to_string(&path) {
+      Ok(content) => serde_json::from_str(&content)
+        .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default_layout_json(),
+      Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+    };
+
+    let mut json = original.clone();
+    let mut found = false;
+    for section in ["left", "center", "right"] {
+      let Some(buttons) = json.get_mut(section).and_then(|v| v.as_array_mut()) else { continue };
+      for button in buttons.iter_mut().filter(|b| b.get("type").and_then(|t| t.as_str()) == Some(button_type)) {
+        update(button);
+        found = true;
+      }
+    }
+
+    if !found {
+      return Err(format!("{} has no {} button", path.display(), button_type));
+    }
+    if json == original && path.exists() {
+      return Ok(());
+    }
+
+    if let Some(dir) = path.parent() {
+      std::fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+    }
+    let content = serde_json::to_string_pretty(&json).map_err(|e| e.to_string())?;
+    std::fs::write(&path, content).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+  }
 }
 
 #[derive(Clone, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum PanelButtonConfig {
   Launch { icon: String, command: String },
-  Clock,
-  Weather { location: String },
-  Workspace,
-  Network,
+  Clock(ClockButtonConfig),
+  Weather(WeatherButtonConfig),
+  Workspace(WorkspaceButtonConfig),
+  Network {
+    #[serde(default)]
+    hotspot: Option<HotspotConfig>,
+  },
   Brightness,
   Microphone,
   Sound,
@@ -94,3 +144,231 @@ pub enum PanelButtonConfig {
   System,
   SystemMetrics,
 }
+
+#[derive(Clone, Deserialize)]
+pub struct HotspotConfig {
+  pub ssid: String,
+  pub passphrase: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct ClockButtonConfig {
+  /// strftime-style format for the button text, e.g. `"%H:%M"`. Defaults to
+  /// `"%b %-d, %Y %-I:%M %p"`.
+  #[serde(default)]
+  pub format: Option<String>,
+  /// Calendars whose events are listed in the dropdown
+  #[serde(default)]
+  pub calendars: Vec<CalendarSourceConfig>,
+  /// Minutes before an event starts to send a reminder notification; 0 turns reminders off.
+  /// Defaults to 10.
+  #[serde(default)]
+  pub reminder_minutes: Option<u32>,
+  /// Extra IANA time zones, e.g. `["Europe/Berlin", "Asia/Tokyo"]`, listed in the tooltip
+  /// and dropdown
+  #[serde(default)]
+  pub zones: Vec<String>,
+  #[serde(default)]
+  pub zone_display: ZoneDisplayMode,
+}
+
+/// Which time the clock button itself shows when extra zones are configured
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneDisplayMode {
+  /// Always local time
+  #[default]
+  Local,
+  /// Scrolling over the clock steps through local time and each zone
+  Scroll,
+  /// Local time and each zone take turns every few seconds
+  Cycle,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum CalendarSourceConfig {
+  /// An `.ics` file, or a directory of them such as one kept in sync by vdirsyncer
+  Path { path: String },
+  /// A CalDAV calendar collection
+  CalDav {
+    url: String,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+  },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WeatherButtonConfig {
+  /// Free-form place name, e.g. `"Springfield, IL"` or a zip code
+  pub location: String,
+  #[serde(default)]
+  pub temperature_unit: TemperatureUnit,
+  /// Defaults to mph with Fahrenheit and km/h with Celsius
+  #[serde(default)]
+  pub wind_speed_unit: Option<WindSpeedUnit>,
+  #[serde(default)]
+  pub provider: WeatherProviderKind,
+  #[serde(default)]
+  pub geocoder: GeocoderKind,
+}
+
+impl WeatherButtonConfig {
+  pub fn wind_speed_unit(&self) -> WindSpeedUnit {
+    self.wind_speed_unit.unwrap_or(match self.temperature_unit {
+      TemperatureUnit::Fahrenheit => WindSpeedUnit::Mph,
+      TemperatureUnit::Celsius => WindSpeedUnit::Kmh,
+    })
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperatureUnit {
+  #[default]
+  #[serde(alias = "f")]
+  Fahrenheit,
+  #[serde(alias = "c")]
+  Celsius,
+}
+
+impl TemperatureUnit {
+  /// The letter shown after the degree sign
+  pub fn symbol(&self) -> &'static str {
+    match self {
+      Self::Fahrenheit => "F",
+      Self::Celsius => "C",
+    }
+  }
+
+  pub fn convert_celsius(&self, celsius: f64) -> f64 {
+    match self {
+      Self::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+      Self::Celsius => celsius,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WindSpeedUnit {
+  #[serde(alias = "km/h")]
+  Kmh,
+  Mph,
+  #[serde(alias = "m/s")]
+  Ms,
+}
+
+impl WindSpeedUnit {
+  pub fn label(&self) -> &'static str {
+    match self {
+      Self::Kmh => "km/h",
+      Self::Mph => "mph",
+      Self::Ms => "m/s",
+    }
+  }
+
+  pub fn convert_meters_per_second(&self, speed: f64) -> f64 {
+    match self {
+      Self::Kmh => speed * 3.6,
+      Self::Mph => speed * 2.236_936,
+      Self::Ms => speed,
+    }
+  }
+}
+
+/// Where forecasts come from
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WeatherProviderKind {
+  /// open-meteo.com, worldwide
+  #[default]
+  OpenMeteo,
+  /// The Norwegian Meteorological Institute's api.met.no, worldwide
+  MetNorway,
+  /// The US National Weather Service's api.weather.gov, US locations only
+  Nws,
+}
+
+/// How the configured location is turned into coordinates
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GeocoderKind {
+  /// OpenStreetMap's Nominatim, which also understands addresses and postal codes
+  #[default]
+  Nominatim,
+  /// Open-Meteo's place name search
+  OpenMeteo,
+}
+
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct WorkspaceButtonConfig {
+  #[serde(default)]
+  pub windows: WindowDisplayMode,
+  /// Button labels by workspace name or number, e.g. `{"1": "", "web": "firefox"}`.
+  /// Values naming an icon in the icon theme are shown as that icon, anything else as text.
+  #[serde(default)]
+  pub labels: HashMap<String, String>,
+}
+
+/// How the workspace bar shows the windows on its monitor
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowDisplayMode {
+  /// One app icon per window
+  #[default]
+  Icons,
+  /// One app icon per window, followed by its truncated title
+  IconsAndTitles,
+  /// One button per app, with a count and a dropdown of its windows when it has several
+  Grouped,
+}
+
+#[derive(Clone, Deserialize, Default)]
+pub struct WorkspacesConfig {
+  #[serde(default)]
+  pub orphan_policy: OrphanPolicy,
+}
+
+/// Where workspaces go when their monitor is unplugged or powered off (e.g. by a KVM switch)
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrphanPolicy {
+  /// The first connected monitor matching the list, else the first connected monitor.
+  /// Entries match a connector exactly or by prefix, so "eDP" matches "eDP-1".
+  PreferredConnectors { connectors: Vec<String> },
+  /// The most recently focused monitor that's still connected
+  LastFocused,
+  /// Leave workspaces where the compositor puts them
+  None,
+}
+
+impl Default for OrphanPolicy {
+  fn default() -> Self {
+    Self::PreferredConnectors { connectors: vec!["eDP".to_string()] }
+  }
+}
+
+impl OrphanPolicy {
+  /// Pick a target among `candidates`, given monitors in most-recently-focused order.
+  /// Returns None when the policy says not to move anything.
+  pub fn target(&self, candidates: &[String], focus_history: &[String]) -> Option<String> {
+    match self {
+      Self::PreferredConnectors { connectors } => connectors
+        .iter()
+        .find_map(|preferred| {
+          candidates.iter().find(|c| *c == preferred || c.starts_with(&format!("{}-", preferred)))
+        })
+        .or_else(|| candidates.first())
+        .cloned(),
+      Self::LastFocused => focus_history
+        .iter()
+        .find(|monitor| candidates.contains(monitor))
+        .or_else(|| candidates.first())
+        .cloned(),
+      Self::None => None,
+    }
+  }
+}
@@ -0,0 +1,261 @@
+use chrono::{Duration, Local};
+use gtk::{gio, glib, prelude::*};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::config::{CalendarSourceConfig, ClockButtonConfig};
+use super::icalendar::{self, CalendarEvent};
+
+const REFRESH_INTERVAL_SECS: u32 = 300;
+const REMINDER_CHECK_SECS: u32 = 30;
+const DEFAULT_REMINDER_MINUTES: u32 = 10;
+
+/// Asks a CalDAV collection for every event in it, with recurring ones left unexpanded
+const CALENDAR_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><c:calendar-data/></d:prop>
+  <c:filter>
+    <c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT"/></c:comp-filter>
+  </c:filter>
+</c:calendar-query>"#;
+
+thread_local! {
+  static SERVICE: RefCell<Option<CalendarService>> = const { RefCell::new(None) };
+}
+
+type Subscriber = Box<dyn Fn(&Rc<Vec<CalendarEvent>>)>;
+
+/// Loads events from the configured calendars, reloads them periodically and sends a
+/// notification shortly before each event starts
+#[derive(Clone)]
+pub struct CalendarService {
+  events: Rc<RefCell<Rc<Vec<CalendarEvent>>>>,
+  subscribers: Rc<RefCell<Vec<Subscriber>>>,
+  /// (summary, start timestamp) of occurrences already reminded about
+  reminded: Rc<RefCell<HashSet<(String, i64)>>>,
+}
+
+impl CalendarService {
+  /// The service shared by every clock, so that events are only loaded and reminders only
+  /// sent once however many panels there are. The first clock's configuration is used.
+  /// Returns None when no calendars are configured.
+  pub fn shared(config: &ClockButtonConfig) -> Option<Self> {
+    if config.calendars.is_empty() {
+      return None;
+    }
+
+    SERVICE.with(|service| {
+      let mut service = service.borrow_mut();
+      Some(service.get_or_insert_with(|| Self::start(config)).clone())
+    })
+  }
+
+  /// Call `f` with the current events and again every time they're reloaded
+  pub fn subscribe(&self, f: impl Fn(&Rc<Vec<CalendarEvent>>) + 'static) {
+    f(&self.events.borrow());
+    self.subscribers.borrow_mut().push(Box::new(f));
+  }
+
+  fn start(config: &ClockButtonConfig) -> Self {
+    let service = Self {
+      events: Rc::new(RefCell::new(Rc::new(Vec::new()))),
+      subscribers: Rc::new(RefCell::new(Vec::new())),
+      reminded: Rc::new(RefCell::new(HashSet::new())),
+    };
+
+    let sources = config.calendars.clone();
+    service.reload(sources.clone());
+
+    let service_clone = service.clone();
+    glib::timeout_add_seconds_local(REFRESH_INTERVAL_SECS, move || {
+      service_clone.reload(sources.clone());
+      glib::ControlFlow::Continue
+    });
+
+    let reminder_minutes = config.reminder_minutes.unwrap_or(DEFAULT_REMINDER_MINUTES);
+    if reminder_minutes > 0 {
+      let service_clone = service.clone();
+      glib::timeout_add_seconds_local(REMINDER_CHECK_SECS, move || {
+        service_clone.send_reminders(reminder_minutes);
+        glib::ControlFlow::Continue
+      });
+    }
+
+    service
+  }
+
+  /// Read the calendars off the main thread, then hand the events to subscribers
+  fn reload(&self, sources: Vec<CalendarSourceConfig>) {
+    let service = self.clone();
+    glib::MainContext::default().spawn_local(async move {
+      let Ok(events) = gio::spawn_blocking(move || load_events(&sources)).await else {
+        return;
+      };
+
+      let events = Rc::new(events);
+      *service.events.borrow_mut() = events.clone();
+      for subscriber in service.subscribers.borrow().iter() {
+        subscriber(&events);
+      }
+    });
+  }
+
+  fn send_reminders(&self, minutes: u32) {
+    let now = Local::now();
+    let events = self.events.borrow().clone();
+
+    for occurrence in icalendar::occurrences(&events, now, now + Duration::minutes(minutes as i64)) {
+      if occurrence.all_day || occurrence.start < now {
+        continue;
+      }
+      if !self.reminded.borrow_mut().insert((occurrence.summary.clone(), occurrence.start.timestamp())) {
+        continue;
+      }
+
+      let mut body = format!("Starts at {}", occurrence.start.format("%-I:%M %p"));
+      if let Some(location) = &occurrence.location {
+        body.push_str(&format!("\n{}", location));
+      }
+      send_notification(&occurrence.summary, &body);
+    }
+  }
+}
+
+fn load_events(sources: &[CalendarSourceConfig]) -> Vec<CalendarEvent> {
+  let mut events = Vec::new();
+
+  for source in sources {
+    match source {
+      CalendarSourceConfig::Path { path } => {
+        let mut files = Vec::new();
+        collect_ics_files(&expand_home(path), &mut files);
+
+        for file in files {
+          match std::fs::read_to_string(&file) {
+            Ok(contents) => events.extend(icalendar::parse_calendar(&contents)),
+            Err(e) => eprintln!("waltopanel: failed to read calendar {}: {}", file.display(), e),
+          }
+        }
+      }
+      CalendarSourceConfig::CalDav { url, username, password } => {
+        match fetch_caldav(url, username.as_deref(), password.as_deref()) {
+          Ok(calendars) => {
+            for calendar in calendars {
+              events.extend(icalendar::parse_calendar(&calendar));
+            }
+          }
+          Err(e) => eprintln!("waltopanel: {}", e),
+        }
+      }
+    }
+  }
+
+  events
+}
+
+fn expand_home(path: &str) -> PathBuf {
+  match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+    (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+    _ => PathBuf::from(path),
+  }
+}
+
+/// `path` itself if it's a file, or every `.ics` file below it if it's a directory
+fn collect_ics_files(path: &Path, files: &mut Vec<PathBuf>) {
+  if !path.is_dir() {
+    files.push(path.to_path_buf());
+    return;
+  }
+
+  let Ok(entries) = std::fs::read_dir(path) else { return };
+  for entry in entries.flatten() {
+    let path = entry.path();
+    if path.is_dir() {
+      collect_ics_files(&path, files);
+    } else if path.extension().is_some_and(|ext| ext == "ics") {
+      files.push(path);
+    }
+  }
+}
+
+/// Fetch the iCalendar data of every event in a CalDAV collection
+fn fetch_caldav(url: &str, username: Option<&str>, password: Option<&str>) -> Result<Vec<String>, String> {
+  let method = reqwest::Method::from_bytes(b"REPORT").map_err(|e| e.to_string())?;
+  let mut request = reqwest::blocking::Client::new()
+    .request(method, url)
+    .header("Depth", "1")
+    .header("Content-Type", "application/xml; charset=utf-8")
+    .body(CALENDAR_QUERY);
+
+  if let Some(username) = username {
+    request = request.basic_auth(username, password);
+  }
+
+  let response = request
+    .send()
+    .map_err(|e| format!("CalDAV request to {} failed: {}", url, e))?;
+  if !response.status().is_success() {
+    return Err(format!("CalDAV server at {} returned {}", url, response.status()));
+  }
+
+  let body = response
+    .text()
+    .map_err(|e| format!("Failed to read CalDAV response from {}: {}", url, e))?;
+  Ok(calendar_data(&body))
+}
+
+/// The contents of each `calendar-data` element in a multistatus response. Servers use
+/// different namespace prefixes for it, so only the local name is matched.
+fn calendar_data(xml: &str) -> Vec<String> {
+  let mut calendars = Vec::new();
+  let mut rest = xml;
+
+  while let Some(tag_start) = rest.find('<') {
+    rest = &rest[tag_start + 1..];
+    let Some(tag_end) = rest.find('>') else { break };
+    let tag = &rest[..tag_end];
+    rest = &rest[tag_end + 1..];
+
+    let name = tag.split_whitespace().next().unwrap_or_default();
+    if name.starts_with('/') || !name.ends_with("calendar-data") || tag.ends_with('/') {
+      continue;
+    }
+
+    let Some(close) = rest.find(&format!("</{}", name)) else { break };
+    calendars.push(unescape_xml(&rest[..close]));
+    rest = &rest[close..];
+  }
+
+  calendars
+}
+
+fn unescape_xml(text: &str) -> String {
+  let text = text.trim();
+  if let Some(cdata) = text.strip_prefix("<![CDATA[").and_then(|text| text.strip_suffix("]]>")) {
+    return cdata.to_string();
+  }
+
+  text
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&#13;", "\r")
+    .replace("&#xD;", "\r")
+    .replace("&amp;", "&")
+}
+
+/// Send a desktop notification from any thread
+fn send_notification(title: &str, body: &str) {
+  let title = title.to_string();
+  let body = body.to_string();
+  glib::MainContext::default().invoke(move || {
+    if let Some(app) = gtk::gio::Application::default() {
+      let notification = gtk::gio::Notification::new(&title);
+      notification.set_body(Some(&body));
+      app.send_notification(None, &notification);
+    }
+  });
+}
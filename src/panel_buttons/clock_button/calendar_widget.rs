@@ -0,0 +1,243 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone};
+use gtk::{prelude::*, Align, Box as GtkBox, Calendar, Label, Orientation};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::icalendar::{self, CalendarEvent, Occurrence};
+use super::world_clock::WorldZone;
+
+/// How far ahead the agenda looks for upcoming events, and how many it lists
+const UPCOMING_DAYS: i64 = 7;
+const UPCOMING_LIMIT: usize = 6;
+
+/// Dropdown for the clock: today's date in long form above a month calendar with week
+/// numbers, the time in any extra zones, and an agenda of today's and upcoming events when
+/// calendars are configured
+#[derive(Clone)]
+pub struct CalendarWidget {
+  container: GtkBox,
+  date_label: Label,
+  calendar: Calendar,
+  /// Each extra zone with the labels showing its time and offset from local time
+  world_clocks: Rc<Vec<(WorldZone, Label, Label)>>,
+  agenda: GtkBox,
+  events: Rc<RefCell<Rc<Vec<CalendarEvent>>>>,
+}
+
+impl CalendarWidget {
+  pub fn new(zones: Rc<Vec<WorldZone>>) -> Self {
+    let container = GtkBox::new(Orientation::Vertical, 8);
+    container.set_margin_top(12);
+    container.set_margin_bottom(12);
+    container.set_margin_start(12);
+    container.set_margin_end(12);
+    container.add_css_class("clock-calendar");
+
+    let date_label = Label::new(None);
+    date_label.add_css_class("heading");
+    date_label.set_halign(Align::Start);
+    container.append(&date_label);
+
+    // Calendar has its own month and year navigation
+    let calendar = Calendar::builder()
+      .show_week_numbers(true)
+      .show_heading(true)
+      .show_day_names(true)
+      .build();
+    container.append(&calendar);
+
+    let world_clocks = Self::create_world_clocks(&container, &zones);
+
+    // Shown once there are calendars to list events from
+    let agenda = GtkBox::new(Orientation::Vertical, 4);
+    agenda.add_css_class("clock-agenda");
+    agenda.set_visible(false);
+    container.append(&agenda);
+
+    let obj = Self {
+      container,
+      date_label,
+      calendar,
+      world_clocks: Rc::new(world_clocks),
+      agenda,
+      events: Rc::new(RefCell::new(Rc::new(Vec::new()))),
+    };
+
+    // Start from today each time the dropdown opens, even if the date changed since the last
+    // time or the user browsed to another month
+    let obj_clone = obj.clone();
+    obj.container.connect_map(move |_| obj_clone.show_today());
+
+    // Marks are per day number, so they're redone for each month shown
+    for property in ["month", "year"] {
+      let obj_clone = obj.clone();
+      obj.calendar.connect_notify_local(Some(property), move |_, _| obj_clone.update_marks());
+    }
+
+    obj
+  }
+
+  pub fn widget(&self) -> &GtkBox {
+    &self.container
+  }
+
+  /// Refresh the world clock times while the dropdown is open
+  pub fn update_world_clocks(&self) {
+    if !self.container.is_mapped() {
+      return;
+    }
+
+    let now = Local::now();
+    for (zone, time, offset) in self.world_clocks.iter() {
+      time.set_text(&now.with_timezone(&zone.tz).format("%a %-I:%M %p").to_string());
+      offset.set_text(&zone.offset_from_local(now));
+    }
+  }
+
+  pub fn set_events(&self, events: &Rc<Vec<CalendarEvent>>) {
+    *self.events.borrow_mut() = events.clone();
+    self.agenda.set_visible(true);
+    self.update_marks();
+    self.update_agenda();
+  }
+
+  fn show_today(&self) {
+    let now = Local::now();
+    self.date_label.set_text(&now.format("%A, %B %-d, %Y").to_string());
+
+    if let Ok(today) = gtk::glib::DateTime::now_local() {
+      self.calendar.select_day(&today);
+    }
+    self.update_marks();
+    self.update_agenda();
+    self.update_world_clocks();
+  }
+
+  fn create_world_clocks(container: &GtkBox, zones: &[WorldZone]) -> Vec<(WorldZone, Label, Label)> {
+    if zones.is_empty() {
+      return Vec::new();
+    }
+
+    let section = GtkBox::new(Orientation::Vertical, 4);
+    section.add_css_class("clock-world-clocks");
+
+    let heading = Label::new(Some("World clocks"));
+    heading.add_css_class("heading");
+    heading.set_halign(Align::Start);
+    section.append(&heading);
+
+    let rows = zones
+      .iter()
+      .map(|zone| {
+        let row = GtkBox::new(Orientation::Horizontal, 8);
+
+        let name = Label::new(Some(&zone.name));
+        name.set_hexpand(true);
+        name.set_xalign(0.0);
+        row.append(&name);
+
+        let offset = Label::new(None);
+        offset.add_css_class("dim-label");
+        row.append(&offset);
+
+        let time = Label::new(None);
+        time.set_width_chars(11);
+        time.set_xalign(1.0);
+        row.append(&time);
+
+        section.append(&row);
+        (zone.clone(), time, offset)
+      })
+      .collect();
+
+    container.append(&section);
+    rows
+  }
+
+  /// Mark the days of the shown month that have events
+  fn update_marks(&self) {
+    self.calendar.clear_marks();
+
+    let shown = self.calendar.date();
+    let Some(month_start) = NaiveDate::from_ymd_opt(shown.year(), shown.month() as u32, 1) else { return };
+    let Some(next_month) = month_start.checked_add_months(chrono::Months::new(1)) else { return };
+    let (Some(from), Some(to)) = (local_midnight(month_start), local_midnight(next_month)) else { return };
+
+    for occurrence in icalendar::occurrences(&self.events.borrow(), from, to) {
+      let first = occurrence.start.date_naive().max(month_start);
+      // An event ending at midnight doesn't touch the day it ends on
+      let last = (occurrence.end - Duration::seconds(1)).date_naive().max(first).min(next_month);
+
+      for day in first.iter_days().take_while(|day| *day <= last && *day < next_month) {
+        self.calendar.mark_day(day.day());
+      }
+    }
+  }
+
+  fn update_agenda(&self) {
+    while let Some(child) = self.agenda.first_child() {
+      self.agenda.remove(&child);
+    }
+
+    let now = Local::now();
+    let Some(today_end) = local_midnight(now.date_naive() + Duration::days(1)) else { return };
+    let events = self.events.borrow();
+
+    let today: Vec<Occurrence> = icalendar::occurrences(&events, now, today_end);
+    self.append_section("Today", &today, "No more events today", false);
+
+    let upcoming: Vec<Occurrence> = icalendar::occurrences(&events, today_end, today_end + Duration::days(UPCOMING_DAYS))
+      .into_iter()
+      // Events that started earlier are already listed under today
+      .filter(|occurrence| occurrence.start >= today_end)
+      .take(UPCOMING_LIMIT)
+      .collect();
+    self.append_section("Upcoming", &upcoming, "Nothing in the next week", true);
+  }
+
+  fn append_section(&self, title: &str, occurrences: &[Occurrence], empty_text: &str, show_day: bool) {
+    let heading = Label::new(Some(title));
+    heading.add_css_class("heading");
+    heading.set_halign(Align::Start);
+    self.agenda.append(&heading);
+
+    if occurrences.is_empty() {
+      let empty = Label::new(Some(empty_text));
+      empty.add_css_class("dim-label");
+      empty.set_halign(Align::Start);
+      self.agenda.append(&empty);
+      return;
+    }
+
+    for occurrence in occurrences {
+      let row = GtkBox::new(Orientation::Horizontal, 8);
+
+      let time_text = match (occurrence.all_day, show_day) {
+        (true, true) => occurrence.start.format("%a %-d").to_string(),
+        (true, false) => "All day".to_string(),
+        (false, true) => occurrence.start.format("%a %-d, %-I:%M %p").to_string(),
+        (false, false) => occurrence.start.format("%-I:%M %p").to_string(),
+      };
+      let time = Label::new(Some(&time_text));
+      time.add_css_class("dim-label");
+      time.set_width_chars(12);
+      time.set_xalign(0.0);
+      row.append(&time);
+
+      let summary = Label::new(Some(&occurrence.summary));
+      summary.set_ellipsize(gtk::pango::EllipsizeMode::End);
+      summary.set_max_width_chars(28);
+      summary.set_xalign(0.0);
+      if let Some(location) = &occurrence.location {
+        summary.set_tooltip_text(Some(location));
+      }
+      row.append(&summary);
+
+      self.agenda.append(&row);
+    }
+  }
+}
+
+fn local_midnight(date: NaiveDate) -> Option<DateTime<Local>> {
+  Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest()
+}
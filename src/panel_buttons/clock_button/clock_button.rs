@@ -1,41 +1,201 @@
+use chrono::format::StrftimeItems;
 use chrono::Local;
 use gtk::glib;
-use gtk::Widget;
+use gtk::glib::Propagation;
+use gtk::prelude::{BoxExt, WidgetExt};
+use gtk::{Align, Box as GtkBox, EventControllerScroll, EventControllerScrollFlags, Orientation, Stack, StackSwitcher, Widget};
 use gtk::glib::object::Cast;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 
 
-use crate::{traits::CompositeWidget, widgets::{PanelButton, PanelButtonBuilder}};
+use crate::{config::{ClockButtonConfig, ZoneDisplayMode}, traits::CompositeWidget, widgets::{PanelButton, PanelButtonBuilder}};
+use super::calendar_service::CalendarService;
+use super::calendar_widget::CalendarWidget;
+use super::system_clock::SystemClock;
+use super::tick::TickUnit;
+use super::timer_service::{format_duration, TimerService};
+use super::timers_widget::TimersWidget;
+use super::world_clock::WorldZone;
 
-#[derive(Clone, Debug)]
+const DEFAULT_FORMAT: &str = "%b %-d, %Y %-I:%M %p";
+
+/// How long each zone is shown for in `ZoneDisplayMode::Cycle`
+const CYCLE_SECS: u32 = 5;
+
+#[derive(Clone)]
 pub struct ClockButton{
   panel_button: PanelButton,
+  format: String,
+  zones: Rc<Vec<WorldZone>>,
+  /// The time the button shows: 0 for local time, otherwise `zones[shown_zone - 1]`
+  shown_zone: Rc<Cell<usize>>,
+  timer_service: TimerService,
+  calendar_widget: CalendarWidget,
+  tick_unit: TickUnit,
+  /// The next redraw, on the boundary of `tick_unit`
+  tick: Rc<RefCell<Option<glib::SourceId>>>,
 }
 
 impl ClockButton {
-  pub fn new() -> Self {
-    let time = Self::get_time(); 
+  pub fn new(config: &ClockButtonConfig) -> Self {
+    let format = Self::validated_format(config.format.as_deref());
+    let zones = Rc::new(WorldZone::parse_all(&config.zones));
+
+    // Zone offsets and the tooltip and dropdown times all need at least minutes
+    let tick_unit = match zones.is_empty() {
+      true => TickUnit::for_format(&format),
+      false => TickUnit::for_format(&format).max(TickUnit::Minute),
+    };
+
+    let calendar_widget = CalendarWidget::new(zones.clone());
+    let timer_service = TimerService::shared();
+    let timers_widget = TimersWidget::new(&timer_service);
+    let dropdown_widget = Self::create_dropdown(&calendar_widget, &timers_widget);
+
+    if let Some(calendar_service) = CalendarService::shared(config) {
+      let calendar_widget = calendar_widget.clone();
+      calendar_service.subscribe(move |events| calendar_widget.set_events(events));
+    }
+
     let panel_button = PanelButtonBuilder::new()
-      .text(&time)
+      .dropdown_widget(dropdown_widget)
       .build();
 
     let obj = Self {
       panel_button,
-    };  
+      format,
+      zones,
+      shown_zone: Rc::new(Cell::new(0)),
+      timer_service,
+      calendar_widget,
+      tick_unit,
+      tick: Rc::new(RefCell::new(None)),
+    };
 
+    // Keeps the remaining time in the text current, and clears it when the countdown ends
     let obj_clone = obj.clone();
+    obj.timer_service.subscribe(move |_| obj_clone.update());
 
-    glib::timeout_add_seconds_local(1, move || {
-      let time = Self::get_time();
-      obj_clone.panel_button.set_text(&time);
-      glib::ControlFlow::Continue
-    });
+    let zone_display = if obj.zones.is_empty() { ZoneDisplayMode::Local } else { config.zone_display };
+    if zone_display == ZoneDisplayMode::Scroll {
+      obj.connect_scroll();
+    }
+
+    if zone_display == ZoneDisplayMode::Cycle {
+      let obj_clone = obj.clone();
+      glib::timeout_add_seconds_local(CYCLE_SECS, move || {
+        obj_clone.step_zone(1);
+        obj_clone.update();
+        glib::ControlFlow::Continue
+      });
+    }
 
+    // Timeouts run on the monotonic clock, which stops during suspend and doesn't follow
+    // changes to the time or zone, so start over from the wall clock after any of those
+    let obj_clone = obj.clone();
+    SystemClock::connect_changed(move || obj_clone.on_tick());
+
+    obj.on_tick();
     obj
   }
 
-  fn get_time() -> String {
+  /// The configured format, or the default if it's missing or invalid. chrono panics when
+  /// formatting with an invalid string, so it's checked up front.
+  fn validated_format(format: Option<&str>) -> String {
+    match format {
+      Some(format) if StrftimeItems::new(format).parse().is_ok() => format.to_string(),
+      Some(format) => {
+        eprintln!("waltopanel: invalid clock format {:?}, using the default", format);
+        DEFAULT_FORMAT.to_string()
+      }
+      None => DEFAULT_FORMAT.to_string(),
+    }
+  }
+
+  /// Pages for the calendar, countdown timer, stopwatch and alarms, with a switcher on top
+  fn create_dropdown(calendar_widget: &CalendarWidget, timers_widget: &TimersWidget) -> Widget {
+    let stack = Stack::builder().vhomogeneous(false).build();
+    stack.add_titled(calendar_widget.widget(), Some("calendar"), "Calendar");
+    stack.add_titled(timers_widget.timer_page(), Some("timer"), "Timer");
+    stack.add_titled(timers_widget.stopwatch_page(), Some("stopwatch"), "Stopwatch");
+    stack.add_titled(timers_widget.alarms_page(), Some("alarms"), "Alarms");
+
+    let switcher = StackSwitcher::builder()
+      .stack(&stack)
+      .halign(Align::Center)
+      .margin_top(12)
+      .build();
+
+    let container = GtkBox::new(Orientation::Vertical, 0);
+    container.append(&switcher);
+    container.append(&stack);
+    container.upcast()
+  }
+
+  fn connect_scroll(&self) {
+    // Discrete so a touchpad moves one zone per scroll step rather than one per event
+    let scroll = EventControllerScroll::new(EventControllerScrollFlags::VERTICAL | EventControllerScrollFlags::DISCRETE);
+
+    let obj = self.clone();
+    scroll.connect_scroll(move |_, _, dy| {
+      match dy {
+        dy if dy > 0.0 => obj.step_zone(1),
+        dy if dy < 0.0 => obj.step_zone(-1),
+        _ => return Propagation::Proceed,
+      }
+      obj.update();
+      Propagation::Stop
+    });
+
+    self.panel_button.add_controller(scroll);
+  }
+
+  /// Move to the next or previous zone, with local time in the cycle too
+  fn step_zone(&self, step: isize) {
+    let count = self.zones.len() as isize + 1;
+    let next = (self.shown_zone.get() as isize + step).rem_euclid(count);
+    self.shown_zone.set(next as usize);
+  }
+
+  /// Redraw, then wait for the next second, minute or day boundary the format needs
+  fn on_tick(&self) {
+    if let Some(source) = self.tick.borrow_mut().take() {
+      source.remove();
+    }
+
+    self.update();
+    self.calendar_widget.update_world_clocks();
+
+    let obj = self.clone();
+    let source = glib::timeout_add_local_once(self.tick_unit.until_next(Local::now()), move || {
+      obj.tick.borrow_mut().take();
+      obj.on_tick();
+    });
+    *self.tick.borrow_mut() = Some(source);
+  }
+
+  fn update(&self) {
     let now = Local::now();
-    now.format("%b %-d, %Y %-I:%M %p").to_string()
+
+    let text = match self.shown_zone.get().checked_sub(1).and_then(|index| self.zones.get(index)) {
+      Some(zone) => format!("{} {}", now.with_timezone(&zone.tz).format(&self.format), zone.name),
+      None => now.format(&self.format).to_string(),
+    };
+
+    let text = match self.timer_service.countdown() {
+      Some(countdown) => format!("{} · {}", text, format_duration(countdown.remaining(now.timestamp_millis()))),
+      None => text,
+    };
+    self.panel_button.set_text(&text);
+
+    if !self.zones.is_empty() {
+      let mut tooltip = format!("Local: {}", now.format("%a %-I:%M %p"));
+      for zone in self.zones.iter() {
+        tooltip.push_str(&format!("\n{}: {}", zone.name, now.with_timezone(&zone.tz).format("%a %-I:%M %p %Z")));
+      }
+      self.panel_button.set_tooltip_text(Some(&tooltip));
+    }
   }
 }
 
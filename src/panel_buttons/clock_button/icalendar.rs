@@ -0,0 +1,1004 @@
+use chrono::{DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use std::collections::{HashMap, HashSet};
+
+/// One VEVENT from an iCalendar file. Recurring events keep their rule and are expanded on
+/// demand by `occurrences`.
+#[derive(Clone, Debug)]
+pub struct CalendarEvent {
+  uid: String,
+  summary: String,
+  location: Option<String>,
+  start: EventTime,
+  /// Length of each occurrence
+  duration: Duration,
+  rule: Option<RecurrenceRule>,
+  /// Occurrences cancelled with EXDATE
+  excluded: Vec<DateTime<Local>>,
+  /// Set on an event that replaces one occurrence of a recurring event with the same UID
+  recurrence_id: Option<DateTime<Local>>,
+}
+
+/// A single occurrence of an event, in local time
+#[derive(Clone, Debug)]
+pub struct Occurrence {
+  pub summary: String,
+  pub location: Option<String>,
+  pub start: DateTime<Local>,
+  pub end: DateTime<Local>,
+  pub all_day: bool,
+}
+
+/// A DTSTART-style value: the wall-clock time as written and the zone it's written in
+#[derive(Clone, Copy, Debug)]
+struct EventTime {
+  local: NaiveDateTime,
+  zone: Zone,
+  all_day: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Zone {
+  Utc,
+  /// No zone given, or one we don't know; taken as the user's local time
+  Floating,
+  Named(Tz),
+}
+
+impl Zone {
+  fn resolve(self, local: NaiveDateTime) -> Option<DateTime<Local>> {
+    match self {
+      Zone::Utc => Some(Utc.from_utc_datetime(&local).with_timezone(&Local)),
+      Zone::Floating => earliest(&Local, local),
+      Zone::Named(tz) => earliest(&tz, local).map(|time| time.with_timezone(&Local)),
+    }
+  }
+
+  /// Convert a UTC time to wall-clock time in this zone
+  fn wall_clock(self, utc: NaiveDateTime) -> NaiveDateTime {
+    let utc = Utc.from_utc_datetime(&utc);
+    match self {
+      Zone::Utc => utc.naive_utc(),
+      Zone::Floating => utc.with_timezone(&Local).naive_local(),
+      Zone::Named(tz) => utc.with_timezone(&tz).naive_local(),
+    }
+  }
+}
+
+/// Resolve a wall-clock time, taking the earlier time when a DST change repeats it. Times a
+/// DST change skips move forward by the usual one hour gap.
+fn earliest<T: TimeZone>(tz: &T, local: NaiveDateTime) -> Option<DateTime<T>> {
+  tz.from_local_datetime(&local)
+    .earliest()
+    .or_else(|| tz.from_local_datetime(&(local + Duration::hours(1))).earliest())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Frequency {
+  Daily,
+  Weekly,
+  Monthly,
+  Yearly,
+}
+
+/// The parts of an RRULE we support. BYSETPOS and the sub-daily frequencies are rare in
+/// calendar data and are ignored.
+#[derive(Clone, Debug)]
+struct RecurrenceRule {
+  frequency: Frequency,
+  interval: u32,
+  count: Option<u32>,
+  /// Last possible start, as wall-clock time in the event's zone
+  until: Option<NaiveDateTime>,
+  /// Weekdays, with an optional ordinal such as 2 for `2TU` or -1 for `-1FR`
+  by_day: Vec<(Option<i32>, Weekday)>,
+  by_month_day: Vec<i32>,
+  by_month: Vec<u32>,
+}
+
+struct Property {
+  name: String,
+  params: HashMap<String, String>,
+  value: String,
+}
+
+/// Parse every VEVENT in an iCalendar file. Events that can't be read are skipped.
+pub fn parse_calendar(contents: &str) -> Vec<CalendarEvent> {
+  let mut events = Vec::new();
+  let mut components: Vec<String> = Vec::new();
+  let mut properties: Vec<Property> = Vec::new();
+
+  for line in unfold(contents) {
+    let Some(property) = Property::parse(&line) else { continue };
+
+    match property.name.as_str() {
+      "BEGIN" => {
+        if property.value.eq_ignore_ascii_case("VEVENT") {
+          properties.clear();
+        }
+        components.push(property.value.to_uppercase());
+      }
+      "END" => {
+        if components.pop().as_deref() == Some("VEVENT")
+          && let Some(event) = CalendarEvent::from_properties(&properties)
+        {
+          events.push(event);
+        }
+      }
+      // Skips the properties of components nested in the event, like VALARM
+      _ if components.last().map(String::as_str) == Some("VEVENT") => properties.push(property),
+      _ => {}
+    }
+  }
+
+  events
+}
+
+/// Every occurrence of `events` that overlaps `from..to`, ordered by start
+pub fn occurrences(events: &[CalendarEvent], from: DateTime<Local>, to: DateTime<Local>) -> Vec<Occurrence> {
+  let mut overridden: HashMap<&str, HashSet<DateTime<Local>>> = HashMap::new();
+  for event in events {
+    if let Some(recurrence_id) = event.recurrence_id {
+      overridden.entry(&event.uid).or_default().insert(recurrence_id);
+    }
+  }
+
+  let no_overrides = HashSet::new();
+  let mut result = Vec::new();
+  for event in events {
+    let overrides = match event.recurrence_id {
+      None => overridden.get(event.uid.as_str()).unwrap_or(&no_overrides),
+      Some(_) => &no_overrides,
+    };
+    event.collect_occurrences(from, to, overrides, &mut result);
+  }
+
+  result.sort_by_key(|occurrence| occurrence.start);
+  result
+}
+
+impl CalendarEvent {
+  fn from_properties(properties: &[Property]) -> Option<Self> {
+    let get = |name: &str| properties.iter().find(|property| property.name == name);
+
+    if get("STATUS").is_some_and(|status| status.value.eq_ignore_ascii_case("CANCELLED")) {
+      return None;
+    }
+
+    let start = get("DTSTART").and_then(Property::time)?;
+
+    let duration = if let Some(end) = get("DTEND").and_then(Property::time) {
+      if start.all_day {
+        end.local - start.local
+      } else {
+        match (start.zone.resolve(start.local), end.zone.resolve(end.local)) {
+          (Some(start), Some(end)) => end - start,
+          _ => Duration::zero(),
+        }
+      }
+    } else if let Some(duration) = get("DURATION").and_then(|duration| parse_duration(&duration.value)) {
+      duration
+    } else if start.all_day {
+      Duration::days(1)
+    } else {
+      Duration::zero()
+    };
+
+    let excluded = properties
+      .iter()
+      .filter(|property| property.name == "EXDATE")
+      .flat_map(|property| {
+        property
+          .value
+          .split(',')
+          .filter_map(|value| parse_time(&property.params, value))
+          .filter_map(|time| time.zone.resolve(time.local))
+          .collect::<Vec<_>>()
+      })
+      .collect();
+
+    let recurrence_id = get("RECURRENCE-ID")
+      .and_then(Property::time)
+      .and_then(|time| time.zone.resolve(time.local));
+
+    // A replaced occurrence is a single event even if it repeats its series' rule
+    let rule = match recurrence_id {
+      None => get("RRULE").and_then(|rule| RecurrenceRule::parse(&rule.value, &start)),
+      Some(_) => None,
+    };
+
+    Some(Self {
+      uid: get("UID").map(|uid| uid.value.clone()).unwrap_or_default(),
+      summary: get("SUMMARY").map(Property::text).unwrap_or_else(|| "Untitled event".to_string()),
+      location: get("LOCATION").map(Property::text).filter(|location| !location.is_empty()),
+      start,
+      duration: duration.max(Duration::zero()),
+      rule,
+      excluded,
+      recurrence_id,
+    })
+  }
+
+  fn collect_occurrences(
+    &self,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+    overridden: &HashSet<DateTime<Local>>,
+    result: &mut Vec<Occurrence>,
+  ) {
+    let starts = match &self.rule {
+      // A day either side covers the difference between the event's zone and ours
+      Some(rule) => rule.starts(
+        self.start.local,
+        from.date_naive() - self.duration - Duration::days(1),
+        to.date_naive() + Duration::days(1),
+      ),
+      None => vec![self.start.local],
+    };
+
+    for local in starts {
+      let Some(start) = self.start.zone.resolve(local) else { continue };
+      if self.excluded.contains(&start) || overridden.contains(&start) {
+        continue;
+      }
+
+      let end = self.start.zone.resolve(local + self.duration).unwrap_or(start);
+      if start < to && (end > from || start >= from) {
+        result.push(Occurrence {
+          summary: self.summary.clone(),
+          location: self.location.clone(),
+          start,
+          end,
+          all_day: self.start.all_day,
+        });
+      }
+    }
+  }
+}
+
+impl RecurrenceRule {
+  fn parse(value: &str, start: &EventTime) -> Option<Self> {
+    let mut frequency = None;
+    let mut rule = Self {
+      frequency: Frequency::Daily,
+      interval: 1,
+      count: None,
+      until: None,
+      by_day: Vec::new(),
+      by_month_day: Vec::new(),
+      by_month: Vec::new(),
+    };
+
+    for part in value.split(';') {
+      let Some((key, value)) = part.split_once('=') else { continue };
+      match key.to_uppercase().as_str() {
+        "FREQ" => {
+          frequency = match value.to_uppercase().as_str() {
+            "DAILY" => Some(Frequency::Daily),
+            "WEEKLY" => Some(Frequency::Weekly),
+            "MONTHLY" => Some(Frequency::Monthly),
+            "YEARLY" => Some(Frequency::Yearly),
+            _ => return None,
+          }
+        }
+        "INTERVAL" => rule.interval = value.parse::<u32>().ok()?.max(1),
+        "COUNT" => rule.count = Some(value.parse().ok()?),
+        "UNTIL" => {
+          let until = parse_time(&HashMap::new(), value)?;
+          rule.until = Some(match until {
+            // A date includes the whole day
+            EventTime { all_day: true, local, .. } => local + Duration::days(1) - Duration::seconds(1),
+            EventTime { zone: Zone::Utc, local, .. } => start.zone.wall_clock(local),
+            EventTime { local, .. } => local,
+          });
+        }
+        "BYDAY" => rule.by_day = value.split(',').filter_map(parse_weekday).collect(),
+        "BYMONTHDAY" => rule.by_month_day = value.split(',').filter_map(|day| day.parse().ok()).collect(),
+        "BYMONTH" => rule.by_month = value.split(',').filter_map(|month| month.parse().ok()).collect(),
+        _ => {}
+      }
+    }
+
+    rule.frequency = frequency?;
+    Some(rule)
+  }
+
+  /// Wall-clock starts of the occurrences from `dtstart` whose dates fall before `until_date`.
+  /// Without a COUNT, occurrences before `from_date` don't affect later ones, so whole
+  /// periods before it are skipped rather than generated.
+  fn starts(&self, dtstart: NaiveDateTime, from_date: NaiveDate, until_date: NaiveDate) -> Vec<NaiveDateTime> {
+    let start_date = dtstart.date();
+    let first_period = match self.count {
+      None => self.periods_between(start_date, from_date).saturating_sub(1),
+      Some(_) => 0,
+    };
+
+    let mut starts = Vec::new();
+    let mut count = 0;
+
+    for period in first_period.. {
+      match self.period_start(start_date, period) {
+        Some(period_start) if period_start <= until_date => {}
+        _ => break,
+      }
+
+      for date in self.period_dates(start_date, period) {
+        if date < start_date {
+          continue;
+        }
+
+        let start = date.and_time(dtstart.time());
+        if date > until_date
+          || self.until.is_some_and(|until| start > until)
+          || self.count.is_some_and(|limit| count >= limit)
+        {
+          return starts;
+        }
+
+        count += 1;
+        starts.push(start);
+      }
+    }
+
+    starts
+  }
+
+  fn periods_between(&self, start: NaiveDate, date: NaiveDate) -> u32 {
+    if date <= start {
+      return 0;
+    }
+
+    let periods = match self.frequency {
+      Frequency::Daily => (date - start).num_days(),
+      Frequency::Weekly => (week_start(date) - week_start(start)).num_weeks(),
+      Frequency::Monthly => months_between(start, date),
+      Frequency::Yearly => (date.year() - start.year()) as i64,
+    };
+    (periods / self.interval as i64).try_into().unwrap_or(u32::MAX)
+  }
+
+  fn period_start(&self, start: NaiveDate, period: u32) -> Option<NaiveDate> {
+    let step = period.checked_mul(self.interval)?;
+    match self.frequency {
+      Frequency::Daily => start.checked_add_signed(Duration::days(step as i64)),
+      Frequency::Weekly => week_start(start).checked_add_signed(Duration::weeks(step as i64)),
+      Frequency::Monthly => start.with_day(1)?.checked_add_months(Months::new(step)),
+      Frequency::Yearly => NaiveDate::from_ymd_opt(start.year().checked_add(step as i32)?, 1, 1),
+    }
+  }
+
+  /// Candidate dates in one period, before COUNT and UNTIL are applied
+  fn period_dates(&self, start: NaiveDate, period: u32) -> Vec<NaiveDate> {
+    let Some(period_start) = self.period_start(start, period) else { return Vec::new() };
+
+    let mut dates = match self.frequency {
+      Frequency::Daily => vec![period_start],
+      Frequency::Weekly => {
+        let weekdays: Vec<Weekday> = match self.by_day.is_empty() {
+          true => vec![start.weekday()],
+          false => self.by_day.iter().map(|(_, weekday)| *weekday).collect(),
+        };
+        period_start
+          .iter_days()
+          .take(7)
+          .filter(|date| weekdays.contains(&date.weekday()))
+          .collect()
+      }
+      Frequency::Monthly => self.days_in_month(period_start, start),
+      Frequency::Yearly if self.by_month.is_empty() && self.by_month_day.is_empty() && !self.by_day.is_empty() => {
+        // e.g. BYDAY=20MO, the 20th Monday of the year
+        let year_end = NaiveDate::from_ymd_opt(period_start.year(), 12, 31).unwrap_or(period_start);
+        weekdays_between(period_start, year_end, &self.by_day)
+      }
+      Frequency::Yearly => {
+        let months = match self.by_month.is_empty() {
+          true => vec![start.month()],
+          false => self.by_month.clone(),
+        };
+        months
+          .iter()
+          .filter_map(|month| NaiveDate::from_ymd_opt(period_start.year(), *month, 1))
+          .flat_map(|month_start| self.days_in_month(month_start, start))
+          .collect()
+      }
+    };
+
+    // BYMONTH, BYMONTHDAY and BYDAY narrow down frequencies they don't expand
+    if !self.by_month.is_empty() {
+      dates.retain(|date| self.by_month.contains(&date.month()));
+    }
+    if self.frequency == Frequency::Daily {
+      if !self.by_month_day.is_empty() {
+        dates.retain(|date| self.by_month_day.iter().any(|day| month_day(*date, *day) == Some(*date)));
+      }
+      if !self.by_day.is_empty() {
+        dates.retain(|date| self.by_day.iter().any(|(_, weekday)| *weekday == date.weekday()));
+      }
+    }
+
+    dates.sort();
+    dates.dedup();
+    dates
+  }
+
+  fn days_in_month(&self, month_start: NaiveDate, start: NaiveDate) -> Vec<NaiveDate> {
+    if !self.by_month_day.is_empty() {
+      let mut days: Vec<NaiveDate> = self
+        .by_month_day
+        .iter()
+        .filter_map(|day| month_day(month_start, *day))
+        .collect();
+      if !self.by_day.is_empty() {
+        days.retain(|date| self.by_day.iter().any(|(_, weekday)| *weekday == date.weekday()));
+      }
+      days
+    } else if !self.by_day.is_empty() {
+      weekdays_between(month_start, last_of_month(month_start), &self.by_day)
+    } else {
+      // Months without the start's day, like the 31st, are skipped
+      month_start.with_day(start.day()).into_iter().collect()
+    }
+  }
+}
+
+impl Property {
+  /// Parse a content line, `NAME;PARAM=value;PARAM="quoted:value":VALUE`
+  fn parse(line: &str) -> Option<Self> {
+    let mut in_quotes = false;
+    let (colon, _) = line.char_indices().find(|(_, c)| {
+      if *c == '"' {
+        in_quotes = !in_quotes;
+      }
+      *c == ':' && !in_quotes
+    })?;
+
+    let mut parts = line[..colon].split(';');
+    let name = parts.next()?.trim().to_uppercase();
+    let params = parts
+      .filter_map(|param| param.split_once('='))
+      .map(|(key, value)| (key.to_uppercase(), value.trim_matches('"').to_string()))
+      .collect();
+
+    Some(Self {
+      name,
+      params,
+      value: line[colon + 1..].to_string(),
+    })
+  }
+
+  fn time(&self) -> Option<EventTime> {
+    parse_time(&self.params, &self.value)
+  }
+
+  /// The value with TEXT escapes like `\n` and `\,` undone
+  fn text(&self) -> String {
+    let mut text = String::with_capacity(self.value.len());
+    let mut chars = self.value.chars();
+    while let Some(c) = chars.next() {
+      if c != '\\' {
+        text.push(c);
+        continue;
+      }
+      match chars.next() {
+        Some('n' | 'N') => text.push('\n'),
+        Some(escaped) => text.push(escaped),
+        None => {}
+      }
+    }
+    text
+  }
+}
+
+/// Join folded lines, which continue on the next line after a leading space or tab
+fn unfold(contents: &str) -> Vec<String> {
+  let mut lines: Vec<String> = Vec::new();
+  for line in contents.lines() {
+    match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+      (Some(continuation), Some(last)) => last.push_str(continuation),
+      _ => lines.push(line.to_string()),
+    }
+  }
+  lines
+}
+
+/// Parse a DATE or DATE-TIME value, using its TZID parameter if it has one
+fn parse_time(params: &HashMap<String, String>, value: &str) -> Option<EventTime> {
+  let value = value.trim();
+
+  if params.get("VALUE").is_some_and(|kind| kind == "DATE") || value.len() == 8 {
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+    return Some(EventTime {
+      local: date.and_hms_opt(0, 0, 0)?,
+      zone: Zone::Floating,
+      all_day: true,
+    });
+  }
+
+  let (value, utc) = match value.strip_suffix('Z') {
+    Some(value) => (value, true),
+    None => (value, false),
+  };
+  let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+
+  let zone = match (utc, params.get("TZID").and_then(|tzid| find_zone(tzid))) {
+    (true, _) => Zone::Utc,
+    (false, Some(tz)) => Zone::Named(tz),
+    (false, None) => Zone::Floating,
+  };
+
+  Some(EventTime { local, zone, all_day: false })
+}
+
+/// The IANA zone for a TZID. Some producers put a path in front of the name, like
+/// `/mozilla.org/20050126_1/Europe/Berlin`, so shorter tails of the path are tried as well.
+/// Names that aren't IANA zones, such as Windows ones, fall back to local time.
+fn find_zone(tzid: &str) -> Option<Tz> {
+  let parts: Vec<&str> = tzid.split('/').collect();
+  (0..parts.len()).find_map(|skip| parts[skip..].join("/").parse::<Tz>().ok())
+}
+
+/// Parse a DURATION value such as `PT1H30M`, `P1D` or `-P1W`
+fn parse_duration(value: &str) -> Option<Duration> {
+  let value = value.trim();
+  let (negative, value) = match value.strip_prefix('-') {
+    Some(value) => (true, value),
+    None => (false, value.strip_prefix('+').unwrap_or(value)),
+  };
+
+  let mut total = Duration::zero();
+  let mut number = String::new();
+  for c in value.strip_prefix('P')?.chars() {
+    match c {
+      '0'..='9' => number.push(c),
+      'T' => {}
+      'W' | 'D' | 'H' | 'M' | 'S' => {
+        let amount: i64 = number.parse().ok()?;
+        number.clear();
+        total += match c {
+          'W' => Duration::weeks(amount),
+          'D' => Duration::days(amount),
+          'H' => Duration::hours(amount),
+          'M' => Duration::minutes(amount),
+          _ => Duration::seconds(amount),
+        };
+      }
+      _ => return None,
+    }
+  }
+
+  Some(if negative { -total } else { total })
+}
+
+/// Parse a BYDAY entry such as `MO`, `2TU` or `-1FR`
+fn parse_weekday(value: &str) -> Option<(Option<i32>, Weekday)> {
+  let value = value.trim();
+  // Byte offsets, so anything non-ASCII fails the lookup rather than splitting a character
+  let split = value.len().checked_sub(2)?;
+  let weekday = match value.get(split..)? {
+    "MO" => Weekday::Mon,
+    "TU" => Weekday::Tue,
+    "WE" => Weekday::Wed,
+    "TH" => Weekday::Thu,
+    "FR" => Weekday::Fri,
+    "SA" => Weekday::Sat,
+    "SU" => Weekday::Sun,
+    _ => return None,
+  };
+
+  let ordinal = match value.get(..split)? {
+    "" => None,
+    ordinal => Some(ordinal.trim_start_matches('+').parse().ok()?),
+  };
+  Some((ordinal, weekday))
+}
+
+/// Dates from `first` to `last` matching BYDAY entries: every such weekday, or with an
+/// ordinal the nth one from the start (or from the end when negative)
+fn weekdays_between(first: NaiveDate, last: NaiveDate, by_day: &[(Option<i32>, Weekday)]) -> Vec<NaiveDate> {
+  let mut dates = Vec::new();
+
+  for (ordinal, weekday) in by_day {
+    let matching: Vec<NaiveDate> = first
+      .iter_days()
+      .take_while(|date| *date <= last)
+      .filter(|date| date.weekday() == *weekday)
+      .collect();
+
+    match ordinal {
+      None => dates.extend(matching),
+      Some(n) if *n > 0 => dates.extend(matching.get(*n as usize - 1)),
+      Some(n) => dates.extend(
+        matching
+          .len()
+          .checked_sub(n.unsigned_abs() as usize)
+          .and_then(|index| matching.get(index)),
+      ),
+    }
+  }
+
+  dates
+}
+
+/// Day `day` of the month containing `date`, counting from the end when negative
+fn month_day(date: NaiveDate, day: i32) -> Option<NaiveDate> {
+  let days_in_month = last_of_month(date).day() as i32;
+  let day = if day < 0 { days_in_month + day + 1 } else { day };
+  if day < 1 || day > days_in_month {
+    return None;
+  }
+  date.with_day(day as u32)
+}
+
+fn last_of_month(date: NaiveDate) -> NaiveDate {
+  date.with_day(1)
+    .and_then(|first| first.checked_add_months(Months::new(1)))
+    .and_then(|next| next.pred_opt())
+    .unwrap_or(date)
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+  date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn months_between(start: NaiveDate, date: NaiveDate) -> i64 {
+  (date.year() - start.year()) as i64 * 12 + date.month() as i64 - start.month() as i64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+    Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap().with_timezone(&Local)
+  }
+
+  fn local(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Local> {
+    Local.with_ymd_and_hms(year, month, day, hour, 0, 0).earliest().unwrap()
+  }
+
+  /// A calendar holding one VEVENT per entry, each given as its property lines
+  fn calendar(events: &[&str]) -> Vec<CalendarEvent> {
+    let mut contents = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n");
+    for event in events {
+      contents.push_str("BEGIN:VEVENT\r\n");
+      for line in event.lines() {
+        contents.push_str(line);
+        contents.push_str("\r\n");
+      }
+      contents.push_str("END:VEVENT\r\n");
+    }
+    contents.push_str("END:VCALENDAR\r\n");
+    parse_calendar(&contents)
+  }
+
+  fn starts(events: &[&str], from: DateTime<Local>, to: DateTime<Local>) -> Vec<DateTime<Local>> {
+    occurrences(&calendar(events), from, to).into_iter().map(|occurrence| occurrence.start).collect()
+  }
+
+  /// A recurring 09:00 UTC event starting on `dtstart`, within a window wide enough to see all of it
+  fn rule_starts(dtstart: &str, rule: &str) -> Vec<DateTime<Local>> {
+    let event = format!("UID:rule\nSUMMARY:Rule\nDTSTART:{}T090000Z\nDURATION:PT1H\nRRULE:{}", dtstart, rule);
+    starts(&[&event], utc(2024, 1, 1, 0, 0), utc(2030, 1, 1, 0, 0))
+  }
+
+  fn at_nine(dates: &[(i32, u32, u32)]) -> Vec<DateTime<Local>> {
+    dates.iter().map(|(year, month, day)| utc(*year, *month, *day, 9, 0)).collect()
+  }
+
+  #[test]
+  fn daily_with_interval_and_count() {
+    assert_eq!(
+      rule_starts("20250101", "FREQ=DAILY;INTERVAL=2;COUNT=4"),
+      at_nine(&[(2025, 1, 1), (2025, 1, 3), (2025, 1, 5), (2025, 1, 7)]),
+    );
+  }
+
+  #[test]
+  fn daily_until_utc_includes_the_last_start() {
+    assert_eq!(
+      rule_starts("20250101", "FREQ=DAILY;UNTIL=20250103T090000Z"),
+      at_nine(&[(2025, 1, 1), (2025, 1, 2), (2025, 1, 3)]),
+    );
+  }
+
+  #[test]
+  fn daily_until_date_includes_the_whole_day() {
+    assert_eq!(
+      rule_starts("20250101", "FREQ=DAILY;INTERVAL=2;UNTIL=20250105"),
+      at_nine(&[(2025, 1, 1), (2025, 1, 3), (2025, 1, 5)]),
+    );
+  }
+
+  #[test]
+  fn weekly_with_interval_byday_and_count() {
+    // 2025-01-06 is a Monday
+    assert_eq!(
+      rule_starts("20250106", "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;COUNT=5"),
+      at_nine(&[(2025, 1, 6), (2025, 1, 8), (2025, 1, 20), (2025, 1, 22), (2025, 2, 3)]),
+    );
+  }
+
+  #[test]
+  fn weekly_until() {
+    assert_eq!(
+      rule_starts("20250106", "FREQ=WEEKLY;UNTIL=20250120T090000Z"),
+      at_nine(&[(2025, 1, 6), (2025, 1, 13), (2025, 1, 20)]),
+    );
+    assert_eq!(
+      rule_starts("20250106", "FREQ=WEEKLY;UNTIL=20250119"),
+      at_nine(&[(2025, 1, 6), (2025, 1, 13)]),
+    );
+  }
+
+  #[test]
+  fn monthly_skips_months_without_the_day() {
+    assert_eq!(
+      rule_starts("20250131", "FREQ=MONTHLY;COUNT=3"),
+      at_nine(&[(2025, 1, 31), (2025, 3, 31), (2025, 5, 31)]),
+    );
+  }
+
+  #[test]
+  fn monthly_with_interval_and_until() {
+    assert_eq!(
+      rule_starts("20250115", "FREQ=MONTHLY;INTERVAL=3;UNTIL=20251231"),
+      at_nine(&[(2025, 1, 15), (2025, 4, 15), (2025, 7, 15), (2025, 10, 15)]),
+    );
+    assert_eq!(
+      rule_starts("20250115", "FREQ=MONTHLY;INTERVAL=2;UNTIL=20250515T090000Z"),
+      at_nine(&[(2025, 1, 15), (2025, 3, 15), (2025, 5, 15)]),
+    );
+  }
+
+  #[test]
+  fn monthly_byday_with_ordinals() {
+    // Second Tuesday
+    assert_eq!(
+      rule_starts("20250114", "FREQ=MONTHLY;BYDAY=2TU;COUNT=3"),
+      at_nine(&[(2025, 1, 14), (2025, 2, 11), (2025, 3, 11)]),
+    );
+    // Last Friday
+    assert_eq!(
+      rule_starts("20250131", "FREQ=MONTHLY;BYDAY=-1FR;COUNT=3"),
+      at_nine(&[(2025, 1, 31), (2025, 2, 28), (2025, 3, 28)]),
+    );
+  }
+
+  #[test]
+  fn monthly_bymonthday_counts_from_either_end() {
+    assert_eq!(
+      rule_starts("20250101", "FREQ=MONTHLY;BYMONTHDAY=1,-1;COUNT=4"),
+      at_nine(&[(2025, 1, 1), (2025, 1, 31), (2025, 2, 1), (2025, 2, 28)]),
+    );
+  }
+
+  #[test]
+  fn yearly_with_count_skips_years_without_the_day() {
+    assert_eq!(
+      rule_starts("20240229", "FREQ=YEARLY;COUNT=2"),
+      at_nine(&[(2024, 2, 29), (2028, 2, 29)]),
+    );
+  }
+
+  #[test]
+  fn yearly_bymonth_with_interval_and_until() {
+    assert_eq!(
+      rule_starts("20250310", "FREQ=YEARLY;INTERVAL=2;BYMONTH=3,9;UNTIL=20271231"),
+      at_nine(&[(2025, 3, 10), (2025, 9, 10), (2027, 3, 10), (2027, 9, 10)]),
+    );
+    assert_eq!(
+      rule_starts("20250310", "FREQ=YEARLY;UNTIL=20260310T090000Z"),
+      at_nine(&[(2025, 3, 10), (2026, 3, 10)]),
+    );
+  }
+
+  #[test]
+  fn yearly_bymonth_byday_with_ordinal() {
+    // US Thanksgiving, the fourth Thursday of November
+    assert_eq!(
+      rule_starts("20251127", "FREQ=YEARLY;BYMONTH=11;BYDAY=4TH;COUNT=2"),
+      at_nine(&[(2025, 11, 27), (2026, 11, 26)]),
+    );
+  }
+
+  #[test]
+  fn daily_bymonth_narrows_the_days() {
+    assert_eq!(
+      rule_starts("20250130", "FREQ=DAILY;BYMONTH=1,3;COUNT=3"),
+      at_nine(&[(2025, 1, 30), (2025, 1, 31), (2025, 3, 1)]),
+    );
+  }
+
+  #[test]
+  fn exdate_removes_occurrences() {
+    let event = "UID:a\nDTSTART:20250101T090000Z\nRRULE:FREQ=DAILY;COUNT=4\n\
+                 EXDATE:20250102T090000Z,20250104T090000Z";
+    assert_eq!(
+      starts(&[event], utc(2025, 1, 1, 0, 0), utc(2025, 2, 1, 0, 0)),
+      at_nine(&[(2025, 1, 1), (2025, 1, 3)]),
+    );
+  }
+
+  #[test]
+  fn exdate_with_tzid() {
+    let event = "UID:a\nDTSTART;TZID=Europe/Berlin:20250101T100000\nRRULE:FREQ=DAILY;COUNT=3\n\
+                 EXDATE;TZID=Europe/Berlin:20250102T100000";
+    assert_eq!(
+      starts(&[event], utc(2025, 1, 1, 0, 0), utc(2025, 2, 1, 0, 0)),
+      at_nine(&[(2025, 1, 1), (2025, 1, 3)]),
+    );
+  }
+
+  #[test]
+  fn recurrence_id_replaces_one_occurrence() {
+    let series = "UID:a\nSUMMARY:Standup\nDTSTART:20250101T090000Z\nDURATION:PT15M\nRRULE:FREQ=DAILY;COUNT=3";
+    let moved = "UID:a\nSUMMARY:Standup (moved)\nRECURRENCE-ID:20250102T090000Z\n\
+                 DTSTART:20250102T150000Z\nDURATION:PT15M";
+    let occurrences = occurrences(&calendar(&[series, moved]), utc(2025, 1, 1, 0, 0), utc(2025, 2, 1, 0, 0));
+
+    let summary: Vec<(&str, DateTime<Local>)> =
+      occurrences.iter().map(|occurrence| (occurrence.summary.as_str(), occurrence.start)).collect();
+    assert_eq!(
+      summary,
+      vec![
+        ("Standup", utc(2025, 1, 1, 9, 0)),
+        ("Standup (moved)", utc(2025, 1, 2, 15, 0)),
+        ("Standup", utc(2025, 1, 3, 9, 0)),
+      ],
+    );
+  }
+
+  #[test]
+  fn recurrence_id_only_affects_its_own_series() {
+    let series = "UID:a\nDTSTART:20250101T090000Z\nRRULE:FREQ=DAILY;COUNT=2";
+    let other = "UID:b\nRECURRENCE-ID:20250102T090000Z\nDTSTART:20250102T150000Z";
+    assert_eq!(
+      starts(&[series, other], utc(2025, 1, 1, 0, 0), utc(2025, 2, 1, 0, 0)),
+      vec![utc(2025, 1, 1, 9, 0), utc(2025, 1, 2, 9, 0), utc(2025, 1, 2, 15, 0)],
+    );
+  }
+
+  #[test]
+  fn cancelled_events_are_skipped() {
+    assert!(calendar(&["UID:a\nSTATUS:CANCELLED\nDTSTART:20250101T090000Z"]).is_empty());
+  }
+
+  #[test]
+  fn all_day_event_spans_its_dates() {
+    let event = "UID:a\nSUMMARY:Trip\nDTSTART;VALUE=DATE:20250110\nDTEND;VALUE=DATE:20250112";
+    let occurrences = occurrences(&calendar(&[event]), local(2025, 1, 11, 12), local(2025, 1, 20, 0));
+
+    assert_eq!(occurrences.len(), 1);
+    assert!(occurrences[0].all_day);
+    assert_eq!(occurrences[0].start.naive_local(), NaiveDate::from_ymd_opt(2025, 1, 10).unwrap().into());
+    assert_eq!(occurrences[0].end.naive_local(), NaiveDate::from_ymd_opt(2025, 1, 12).unwrap().into());
+  }
+
+  #[test]
+  fn all_day_event_without_end_lasts_a_day() {
+    let event = "UID:a\nDTSTART;VALUE=DATE:20250110\nRRULE:FREQ=YEARLY;COUNT=2";
+    let occurrences = occurrences(&calendar(&[event]), local(2025, 1, 1, 0), local(2027, 1, 1, 0));
+
+    let days: Vec<(NaiveDateTime, NaiveDateTime)> = occurrences
+      .iter()
+      .map(|occurrence| (occurrence.start.naive_local(), occurrence.end.naive_local()))
+      .collect();
+    let day = |year, day| NaiveDate::from_ymd_opt(year, 1, day).unwrap().into();
+    assert_eq!(days, vec![(day(2025, 10), day(2025, 11)), (day(2026, 10), day(2026, 11))]);
+    assert!(occurrences.iter().all(|occurrence| occurrence.all_day));
+  }
+
+  #[test]
+  fn tzid_with_path_prefix() {
+    assert_eq!(find_zone("/mozilla.org/20050126_1/Europe/Berlin"), Some(chrono_tz::Europe::Berlin));
+    assert_eq!(find_zone("America/New_York"), Some(chrono_tz::America::New_York));
+    assert_eq!(find_zone("W. Europe Standard Time"), None);
+
+    let event = "UID:a\nDTSTART;TZID=/mozilla.org/20050126_1/Europe/Berlin:20250115T100000";
+    assert_eq!(starts(&[event], utc(2025, 1, 15, 0, 0), utc(2025, 1, 16, 0, 0)), vec![utc(2025, 1, 15, 9, 0)]);
+  }
+
+  #[test]
+  fn tzid_keeps_wall_clock_time_across_dst() {
+    // Berlin moves from UTC+1 to UTC+2 on 2025-03-30
+    let event = "UID:a\nDTSTART;TZID=Europe/Berlin:20250324T090000\nRRULE:FREQ=WEEKLY;COUNT=2";
+    assert_eq!(
+      starts(&[event], utc(2025, 3, 1, 0, 0), utc(2025, 4, 30, 0, 0)),
+      vec![utc(2025, 3, 24, 8, 0), utc(2025, 3, 31, 7, 0)],
+    );
+  }
+
+  #[test]
+  fn dst_gap_moves_forward_an_hour() {
+    // 02:30 doesn't exist in Berlin on 2025-03-30, so it's 03:30 CEST
+    let berlin = chrono_tz::Europe::Berlin;
+    let skipped = NaiveDate::from_ymd_opt(2025, 3, 30).unwrap().and_hms_opt(2, 30, 0).unwrap();
+    assert_eq!(
+      earliest(&berlin, skipped).unwrap().with_timezone(&Utc),
+      Utc.with_ymd_and_hms(2025, 3, 30, 1, 30, 0).unwrap(),
+    );
+
+    let event = "UID:a\nDTSTART;TZID=Europe/Berlin:20250330T023000";
+    assert_eq!(starts(&[event], utc(2025, 3, 30, 0, 0), utc(2025, 3, 31, 0, 0)), vec![utc(2025, 3, 30, 1, 30)]);
+  }
+
+  #[test]
+  fn dst_overlap_takes_the_earlier_time() {
+    // 02:30 happens twice in Berlin on 2025-10-26; the first is still CEST
+    let berlin = chrono_tz::Europe::Berlin;
+    let repeated = NaiveDate::from_ymd_opt(2025, 10, 26).unwrap().and_hms_opt(2, 30, 0).unwrap();
+    assert_eq!(
+      earliest(&berlin, repeated).unwrap().with_timezone(&Utc),
+      Utc.with_ymd_and_hms(2025, 10, 26, 0, 30, 0).unwrap(),
+    );
+
+    let event = "UID:a\nDTSTART;TZID=Europe/Berlin:20251026T023000";
+    assert_eq!(starts(&[event], utc(2025, 10, 26, 0, 0), utc(2025, 10, 27, 0, 0)), vec![utc(2025, 10, 26, 0, 30)]);
+  }
+
+  #[test]
+  fn occurrences_overlapping_window_edges() {
+    let event = ["UID:a\nDTSTART:20250101T090000Z\nDTEND:20250101T110000Z"];
+    let found = |from, to| starts(&event, from, to).len();
+
+    // Started before the window and still going
+    assert_eq!(found(utc(2025, 1, 1, 10, 0), utc(2025, 1, 1, 12, 0)), 1);
+    // Starts inside the window and ends after it
+    assert_eq!(found(utc(2025, 1, 1, 8, 0), utc(2025, 1, 1, 10, 0)), 1);
+    // Ends exactly as the window starts
+    assert_eq!(found(utc(2025, 1, 1, 11, 0), utc(2025, 1, 1, 12, 0)), 0);
+    // Starts exactly as the window ends
+    assert_eq!(found(utc(2025, 1, 1, 7, 0), utc(2025, 1, 1, 9, 0)), 0);
+  }
+
+  #[test]
+  fn zero_length_event_at_window_start() {
+    let event = ["UID:a\nDTSTART:20250101T090000Z"];
+    assert_eq!(starts(&event, utc(2025, 1, 1, 9, 0), utc(2025, 1, 1, 10, 0)), vec![utc(2025, 1, 1, 9, 0)]);
+  }
+
+  #[test]
+  fn recurring_occurrence_overlapping_window_start() {
+    // Each occurrence runs from 23:00 to 01:00 the next day
+    let event = ["UID:a\nDTSTART:20250101T230000Z\nDURATION:PT2H\nRRULE:FREQ=DAILY"];
+    assert_eq!(
+      starts(&event, utc(2025, 3, 2, 0, 30), utc(2025, 3, 2, 23, 30)),
+      vec![utc(2025, 3, 1, 23, 0), utc(2025, 3, 2, 23, 0)],
+    );
+  }
+
+  #[test]
+  fn folded_lines_and_escaped_text() {
+    let events = calendar(&["UID:a\nSUMMARY:Lunch\\, then\n  a walk\nLOCATION:Café\nDTSTART:20250101T120000Z"]);
+    let occurrences = occurrences(&events, utc(2025, 1, 1, 0, 0), utc(2025, 1, 2, 0, 0));
+    assert_eq!(occurrences[0].summary, "Lunch, then a walk");
+    assert_eq!(occurrences[0].location.as_deref(), Some("Café"));
+  }
+
+  #[test]
+  fn parse_weekday_ordinals() {
+    assert_eq!(parse_weekday("MO"), Some((None, Weekday::Mon)));
+    assert_eq!(parse_weekday("2TU"), Some((Some(2), Weekday::Tue)));
+    assert_eq!(parse_weekday("+3WE"), Some((Some(3), Weekday::Wed)));
+    assert_eq!(parse_weekday("-1FR"), Some((Some(-1), Weekday::Fri)));
+    assert_eq!(parse_weekday("XX"), None);
+    assert_eq!(parse_weekday("aTU"), None);
+    assert_eq!(parse_weekday("U"), None);
+  }
+
+  #[test]
+  fn parse_weekday_rejects_non_ascii() {
+    assert_eq!(parse_weekday("éx"), None);
+    assert_eq!(parse_weekday("xé"), None);
+    assert_eq!(parse_weekday("2é"), None);
+    assert_eq!(rule_starts("20250106", "FREQ=WEEKLY;BYDAY=éx,MO;COUNT=2").len(), 2);
+  }
+
+  #[test]
+  fn parse_durations() {
+    assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+    assert_eq!(parse_duration("P1W2D"), Some(Duration::days(9)));
+    assert_eq!(parse_duration("-PT15M"), Some(Duration::minutes(-15)));
+    assert_eq!(parse_duration("1H"), None);
+  }
+}
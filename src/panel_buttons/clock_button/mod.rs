@@ -1,2 +1,10 @@
+mod calendar_service;
+mod calendar_widget;
 mod clock_button;
+mod icalendar;
+mod system_clock;
+mod tick;
+mod timer_service;
+mod timers_widget;
+mod world_clock;
 pub use clock_button::ClockButton;
\ No newline at end of file
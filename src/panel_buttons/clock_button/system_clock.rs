@@ -0,0 +1,139 @@
+use gtk::glib;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use zbus::zvariant::OwnedValue;
+
+const TIMEDATED_SERVICE: &str = "org.freedesktop.timedate1";
+const TIMEDATED_PATH: &str = "/org/freedesktop/timedate1";
+const LOGIND_SERVICE: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+
+thread_local! {
+  static SUBSCRIBERS: RefCell<Vec<Box<dyn Fn()>>> = const { RefCell::new(Vec::new()) };
+  static WATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Tells clocks when the wall clock jumps: the system time zone changes (as announced by
+/// systemd-timedated), the time is set, or the machine resumes from suspend (as announced by
+/// logind). They can then redraw and reschedule straight away instead of on their next tick.
+pub struct SystemClock;
+
+impl SystemClock {
+  /// Call `f` on the main thread whenever the wall clock jumps
+  pub fn connect_changed(f: impl Fn() + 'static) {
+    SUBSCRIBERS.with(|subscribers| subscribers.borrow_mut().push(Box::new(f)));
+
+    if !WATCHING.replace(true) {
+      std::thread::spawn(|| {
+        if let Err(e) = watch_timedated() {
+          eprintln!("waltopanel: can't watch for time zone changes: {}", e);
+        }
+      });
+      std::thread::spawn(|| {
+        if let Err(e) = watch_sleep() {
+          eprintln!("waltopanel: can't watch for resume from suspend: {}", e);
+        }
+      });
+      std::thread::spawn(|| {
+        if let Err(e) = watch_clock_set() {
+          eprintln!("waltopanel: can't watch for clock changes: {}", e);
+        }
+      });
+    }
+  }
+
+  fn notify_subscribers() {
+    SUBSCRIBERS.with(|subscribers| {
+      for subscriber in subscribers.borrow().iter() {
+        subscriber();
+      }
+    });
+  }
+}
+
+fn watch_timedated() -> zbus::Result<()> {
+  let connection = zbus::blocking::Connection::system()?;
+  let rule = zbus::MatchRule::builder()
+    .msg_type(zbus::message::Type::Signal)
+    .sender(TIMEDATED_SERVICE)?
+    .path(TIMEDATED_PATH)?
+    .interface("org.freedesktop.DBus.Properties")?
+    .member("PropertiesChanged")?
+    .build();
+
+  for message in zbus::blocking::MessageIterator::for_match_rule(rule, &connection, None)? {
+    let Ok(message) = message else { continue };
+    let Ok((_, changed, invalidated)) = message
+      .body()
+      .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+    else {
+      continue;
+    };
+
+    // timedated invalidates Timezone rather than sending the new value
+    if changed.contains_key("Timezone") || invalidated.iter().any(|property| property == "Timezone") {
+      glib::MainContext::default().invoke(SystemClock::notify_subscribers);
+    }
+  }
+
+  Ok(())
+}
+
+fn watch_sleep() -> zbus::Result<()> {
+  let connection = zbus::blocking::Connection::system()?;
+  let rule = zbus::MatchRule::builder()
+    .msg_type(zbus::message::Type::Signal)
+    .sender(LOGIND_SERVICE)?
+    .path(LOGIND_PATH)?
+    .interface("org.freedesktop.login1.Manager")?
+    .member("PrepareForSleep")?
+    .build();
+
+  for message in zbus::blocking::MessageIterator::for_match_rule(rule, &connection, None)? {
+    let Ok(message) = message else { continue };
+
+    // Sent with true going to sleep and false on waking up
+    if let Ok(false) = message.body().deserialize::<bool>() {
+      glib::MainContext::default().invoke(SystemClock::notify_subscribers);
+    }
+  }
+
+  Ok(())
+}
+
+/// Wait on a realtime timerfd set far in the future. With `TFD_TIMER_CANCEL_ON_SET` the read
+/// fails with `ECANCELED` whenever the clock is set, by hand or by NTP stepping it.
+fn watch_clock_set() -> Result<(), String> {
+  let fd = unsafe { libc::timerfd_create(libc::CLOCK_REALTIME, libc::TFD_CLOEXEC) };
+  if fd < 0 {
+    return Err(std::io::Error::last_os_error().to_string());
+  }
+
+  let spec = libc::itimerspec {
+    it_interval: libc::timespec { tv_sec: 0, tv_nsec: 0 },
+    it_value: libc::timespec { tv_sec: libc::time_t::MAX, tv_nsec: 0 },
+  };
+
+  loop {
+    let flags = libc::TFD_TIMER_ABSTIME | libc::TFD_TIMER_CANCEL_ON_SET;
+    if unsafe { libc::timerfd_settime(fd, flags, &spec, std::ptr::null_mut()) } < 0 {
+      let error = std::io::Error::last_os_error();
+      unsafe { libc::close(fd) };
+      return Err(error.to_string());
+    }
+
+    let mut expirations = 0u64;
+    let read = unsafe { libc::read(fd, &mut expirations as *mut u64 as *mut libc::c_void, size_of::<u64>()) };
+    if read < 0 {
+      let error = std::io::Error::last_os_error();
+      match error.raw_os_error() {
+        Some(libc::ECANCELED) => glib::MainContext::default().invoke(SystemClock::notify_subscribers),
+        Some(libc::EINTR) => {}
+        _ => {
+          unsafe { libc::close(fd) };
+          return Err(error.to_string());
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,70 @@
+use chrono::format::{Fixed, Item, Numeric, StrftimeItems};
+use chrono::{DateTime, Duration, Local, TimeZone, Timelike};
+
+/// A little past the boundary, so the clock never wakes just before it and shows the old time
+const TICK_SLACK_MS: i64 = 20;
+
+/// The smallest wall-clock unit a format shows, and so how often the clock has to redraw
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TickUnit {
+  Day,
+  Minute,
+  Second,
+}
+
+impl TickUnit {
+  /// The unit `format` needs. Anything unrecognised counts as seconds, so it's never stale.
+  pub fn for_format(format: &str) -> Self {
+    let Ok(items) = StrftimeItems::new(format).parse() else {
+      return Self::Second;
+    };
+
+    items.iter().map(Self::for_item).max().unwrap_or(Self::Day)
+  }
+
+  fn for_item(item: &Item) -> Self {
+    match item {
+      Item::Literal(_) | Item::OwnedLiteral(_) | Item::Space(_) | Item::OwnedSpace(_) => Self::Day,
+      Item::Numeric(Numeric::Second | Numeric::Nanosecond | Numeric::Timestamp, _) => Self::Second,
+      // Zone names and offsets change with DST, which happens on the hour
+      Item::Numeric(Numeric::Hour | Numeric::Hour12 | Numeric::Minute, _)
+      | Item::Fixed(
+        Fixed::LowerAmPm
+        | Fixed::UpperAmPm
+        | Fixed::TimezoneName
+        | Fixed::TimezoneOffset
+        | Fixed::TimezoneOffsetZ
+        | Fixed::TimezoneOffsetColon
+        | Fixed::TimezoneOffsetColonZ
+        | Fixed::TimezoneOffsetDoubleColon
+        | Fixed::TimezoneOffsetTripleColon,
+      ) => Self::Minute,
+      Item::Numeric(_, _)
+      | Item::Fixed(Fixed::ShortMonthName | Fixed::LongMonthName | Fixed::ShortWeekdayName | Fixed::LongWeekdayName) => {
+        Self::Day
+      }
+      _ => Self::Second,
+    }
+  }
+
+  /// How long from `now` until the next boundary of this unit
+  pub fn until_next(self, now: DateTime<Local>) -> std::time::Duration {
+    let into_second = now.timestamp_subsec_millis() as i64;
+    let wait = match self {
+      Self::Second => Duration::milliseconds(1000 - into_second),
+      Self::Minute => Duration::milliseconds(60_000 - now.second() as i64 * 1000 - into_second),
+      Self::Day => {
+        // Midnight can fall in a DST gap, in which case the hour after will do
+        let midnight = now.date_naive().succ_opt().and_then(|day| day.and_hms_opt(0, 0, 0));
+        match midnight.and_then(|midnight| Local.from_local_datetime(&midnight).earliest()) {
+          Some(midnight) => midnight - now,
+          None => Duration::hours(1),
+        }
+      }
+    };
+
+    (wait + Duration::milliseconds(TICK_SLACK_MS))
+      .to_std()
+      .unwrap_or(std::time::Duration::from_secs(1))
+  }
+}
@@ -0,0 +1,409 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, TimeZone};
+use gtk::{glib, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::panel_buttons::SoundButton;
+use super::system_clock::SystemClock;
+
+/// Alarms missed by up to this long, e.g. while suspended, still go off
+const MISSED_ALARM_GRACE_MINUTES: i64 = 10;
+/// Longest wait between alarm checks. Timeouts stop counting during suspend, so this bounds
+/// how late an alarm can be after a resume logind didn't announce.
+const ALARM_CHECK_SECS: i64 = 60;
+const FINISHED_SOUND: &str = "alarm-clock-elapsed";
+
+thread_local! {
+  static SERVICE: RefCell<Option<TimerService>> = const { RefCell::new(None) };
+}
+
+/// A countdown. Times are Unix milliseconds so a running timer carries on across restarts.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Countdown {
+  /// Length it was started with
+  pub total_secs: u64,
+  /// When it finishes, or None while paused
+  ends_at: Option<i64>,
+  /// Time left when it was paused
+  remaining_ms: i64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Stopwatch {
+  /// Time counted by earlier runs
+  elapsed_ms: i64,
+  /// When the current run started, if it's running
+  started_at: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Alarm {
+  pub hour: u32,
+  pub minute: u32,
+  /// Days it repeats on, 0 for Monday to 6 for Sunday. An alarm with no days goes off once.
+  pub days: Vec<u8>,
+  pub label: String,
+  pub enabled: bool,
+  /// When it last went off, or was set, so it doesn't go off twice for the same time
+  last_fired: i64,
+}
+
+/// Everything the timers keep, persisted in `$XDG_STATE_HOME/waltopanel/clock_timers.json`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TimerState {
+  #[serde(default)]
+  pub countdown: Option<Countdown>,
+  #[serde(default)]
+  pub stopwatch: Stopwatch,
+  #[serde(default)]
+  pub alarms: Vec<Alarm>,
+}
+
+type Subscriber = Box<dyn Fn(&TimerState)>;
+
+/// The countdown timer, stopwatch and alarms shared by every clock. Sends a notification and
+/// plays a sound when a countdown finishes or an alarm goes off.
+#[derive(Clone)]
+pub struct TimerService {
+  state: Rc<RefCell<TimerState>>,
+  subscribers: Rc<RefCell<Vec<Subscriber>>>,
+  /// Once-a-second tick, only while the countdown or stopwatch is running
+  tick: Rc<RefCell<Option<glib::SourceId>>>,
+  alarm_check: Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl Countdown {
+  pub fn remaining(&self, now: i64) -> Duration {
+    let remaining_ms = match self.ends_at {
+      Some(ends_at) => ends_at - now,
+      None => self.remaining_ms,
+    };
+    Duration::milliseconds(remaining_ms.max(0))
+  }
+
+  pub fn is_running(&self) -> bool {
+    self.ends_at.is_some()
+  }
+}
+
+impl Stopwatch {
+  pub fn elapsed(&self, now: i64) -> Duration {
+    let running_ms = self.started_at.map_or(0, |started_at| now - started_at);
+    Duration::milliseconds(self.elapsed_ms + running_ms.max(0))
+  }
+
+  pub fn is_running(&self) -> bool {
+    self.started_at.is_some()
+  }
+}
+
+impl Alarm {
+  pub fn time_text(&self) -> String {
+    format!("{:02}:{:02}", self.hour, self.minute)
+  }
+
+  /// The latest time at or before `now` the alarm was due, looking back up to a week
+  fn previous_due(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let time = NaiveTime::from_hms_opt(self.hour, self.minute, 0)?;
+    (0..=7)
+      .map(|days_back| now.date_naive() - Duration::days(days_back))
+      .filter(|date| self.days.is_empty() || self.days.contains(&(date.weekday().num_days_from_monday() as u8)))
+      .filter_map(|date| Local.from_local_datetime(&date.and_time(time)).earliest())
+      .find(|due| *due <= now)
+  }
+
+  /// The next time after `now` the alarm is due
+  fn next_due(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let time = NaiveTime::from_hms_opt(self.hour, self.minute, 0)?;
+    (0..=7)
+      .map(|days_ahead| now.date_naive() + Duration::days(days_ahead))
+      .filter(|date| self.days.is_empty() || self.days.contains(&(date.weekday().num_days_from_monday() as u8)))
+      .filter_map(|date| Local.from_local_datetime(&date.and_time(time)).earliest())
+      .find(|due| *due > now)
+  }
+}
+
+impl TimerService {
+  pub fn shared() -> Self {
+    SERVICE.with(|service| service.borrow_mut().get_or_insert_with(Self::start).clone())
+  }
+
+  /// Call `f` with the current state and again whenever it changes, including every second
+  /// while the countdown or stopwatch is running
+  pub fn subscribe(&self, f: impl Fn(&TimerState) + 'static) {
+    f(&self.state.borrow());
+    self.subscribers.borrow_mut().push(Box::new(f));
+  }
+
+  pub fn countdown(&self) -> Option<Countdown> {
+    self.state.borrow().countdown.clone()
+  }
+
+  pub fn start_countdown(&self, secs: u64) {
+    self.change(|state, now| {
+      state.countdown = Some(Countdown {
+        total_secs: secs,
+        ends_at: Some(now + secs as i64 * 1000),
+        remaining_ms: secs as i64 * 1000,
+      });
+    });
+  }
+
+  /// Pause the countdown if it's running, or carry on if it's paused
+  pub fn toggle_countdown(&self) {
+    self.change(|state, now| {
+      let Some(countdown) = state.countdown.as_mut() else { return };
+      match countdown.ends_at.take() {
+        Some(ends_at) => countdown.remaining_ms = (ends_at - now).max(0),
+        None => countdown.ends_at = Some(now + countdown.remaining_ms),
+      }
+    });
+  }
+
+  pub fn cancel_countdown(&self) {
+    self.change(|state, _| state.countdown = None);
+  }
+
+  pub fn toggle_stopwatch(&self) {
+    self.change(|state, now| {
+      let stopwatch = &mut state.stopwatch;
+      match stopwatch.started_at.take() {
+        Some(started_at) => stopwatch.elapsed_ms += now - started_at,
+        None => stopwatch.started_at = Some(now),
+      }
+    });
+  }
+
+  pub fn reset_stopwatch(&self) {
+    self.change(|state, _| state.stopwatch = Stopwatch::default());
+  }
+
+  pub fn add_alarm(&self, hour: u32, minute: u32, days: Vec<u8>, label: String) {
+    self.change(|state, now| {
+      state.alarms.push(Alarm {
+        hour,
+        minute,
+        days,
+        label,
+        enabled: true,
+        last_fired: now,
+      });
+    });
+  }
+
+  pub fn set_alarm_enabled(&self, index: usize, enabled: bool) {
+    self.change(|state, now| {
+      if let Some(alarm) = state.alarms.get_mut(index) {
+        alarm.enabled = enabled;
+        // Don't go off straight away for a time that passed while it was off
+        alarm.last_fired = now;
+      }
+    });
+  }
+
+  pub fn remove_alarm(&self, index: usize) {
+    self.change(|state, _| {
+      if index < state.alarms.len() {
+        state.alarms.remove(index);
+      }
+    });
+  }
+
+  fn start() -> Self {
+    let service = Self {
+      state: Rc::new(RefCell::new(Self::load())),
+      subscribers: Rc::new(RefCell::new(Vec::new())),
+      tick: Rc::new(RefCell::new(None)),
+      alarm_check: Rc::new(RefCell::new(None)),
+    };
+
+    // A countdown that ran out while the panel was closed finishes on the first tick
+    service.update_tick();
+    service.check_alarms();
+
+    // The alarm check waits on the monotonic clock, so after a suspend or a change to the time
+    // it would be late; check again straight away, and show countdowns that ran on meanwhile
+    let service_clone = service.clone();
+    SystemClock::connect_changed(move || {
+      service_clone.check_alarms();
+      service_clone.notify_subscribers();
+    });
+
+    service
+  }
+
+  /// Apply a change, then save it and tell subscribers
+  fn change(&self, f: impl FnOnce(&mut TimerState, i64)) {
+    f(&mut self.state.borrow_mut(), Local::now().timestamp_millis());
+    self.save();
+    self.update_tick();
+    self.check_alarms();
+    self.notify_subscribers();
+  }
+
+  fn notify_subscribers(&self) {
+    let state = self.state.borrow().clone();
+    for subscriber in self.subscribers.borrow().iter() {
+      subscriber(&state);
+    }
+  }
+
+  /// Tick once a second while anything is counting, and not otherwise
+  fn update_tick(&self) {
+    let needs_tick = {
+      let state = self.state.borrow();
+      state.countdown.as_ref().is_some_and(Countdown::is_running) || state.stopwatch.is_running()
+    };
+
+    let mut tick = self.tick.borrow_mut();
+    match (needs_tick, tick.is_some()) {
+      (true, false) => {
+        let service = self.clone();
+        *tick = Some(glib::timeout_add_seconds_local(1, move || service.on_tick()));
+      }
+      (false, true) => {
+        if let Some(source) = tick.take() {
+          source.remove();
+        }
+      }
+      _ => {}
+    }
+  }
+
+  fn on_tick(&self) -> glib::ControlFlow {
+    let now = Local::now().timestamp_millis();
+
+    let finished = {
+      let mut state = self.state.borrow_mut();
+      match &state.countdown {
+        Some(countdown) if countdown.is_running() && countdown.remaining(now).is_zero() => state.countdown.take(),
+        _ => None,
+      }
+    };
+
+    if let Some(countdown) = finished {
+      self.save();
+      finish("Timer finished", &format!("Your {} timer is done", format_duration(Duration::seconds(countdown.total_secs as i64))));
+    }
+
+    self.notify_subscribers();
+
+    let state = self.state.borrow();
+    if state.countdown.as_ref().is_some_and(Countdown::is_running) || state.stopwatch.is_running() {
+      glib::ControlFlow::Continue
+    } else {
+      self.tick.borrow_mut().take();
+      glib::ControlFlow::Break
+    }
+  }
+
+  /// Set off any alarm that's due, then wait for the next one
+  fn check_alarms(&self) {
+    if let Some(source) = self.alarm_check.borrow_mut().take() {
+      source.remove();
+    }
+
+    let now = Local::now();
+    let mut fired = Vec::new();
+    {
+      let mut state = self.state.borrow_mut();
+      for alarm in state.alarms.iter_mut().filter(|alarm| alarm.enabled) {
+        let Some(due) = alarm.previous_due(now) else { continue };
+        if due.timestamp_millis() <= alarm.last_fired || now - due > Duration::minutes(MISSED_ALARM_GRACE_MINUTES) {
+          continue;
+        }
+
+        alarm.last_fired = now.timestamp_millis();
+        if alarm.days.is_empty() {
+          alarm.enabled = false;
+        }
+        fired.push(alarm.clone());
+      }
+    }
+
+    if !fired.is_empty() {
+      self.save();
+      self.notify_subscribers();
+    }
+    for alarm in fired {
+      let title = if alarm.label.is_empty() { "Alarm".to_string() } else { alarm.label.clone() };
+      finish(&title, &format!("It's {}", alarm.time_text()));
+    }
+
+    let next_due = self
+      .state
+      .borrow()
+      .alarms
+      .iter()
+      .filter(|alarm| alarm.enabled)
+      .filter_map(|alarm| alarm.next_due(now))
+      .min();
+    let Some(next_due) = next_due else { return };
+
+    let wait = (next_due - now).num_seconds().clamp(1, ALARM_CHECK_SECS);
+    let service = self.clone();
+    let source = glib::timeout_add_seconds_local_once(wait as u32, move || {
+      service.alarm_check.borrow_mut().take();
+      service.check_alarms();
+    });
+    *self.alarm_check.borrow_mut() = Some(source);
+  }
+
+  fn path() -> Option<PathBuf> {
+    let state_dir = env::var_os("XDG_STATE_HOME")
+      .map(PathBuf::from)
+      .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+
+    Some(state_dir.join("waltopanel").join("clock_timers.json"))
+  }
+
+  fn load() -> TimerState {
+    Self::path()
+      .and_then(|path| fs::read_to_string(path).ok())
+      .and_then(|content| serde_json::from_str(&content).ok())
+      .unwrap_or_default()
+  }
+
+  fn save(&self) {
+    let Some(path) = Self::path() else { return };
+
+    if let Some(dir) = path.parent()
+      && let Err(e) = fs::create_dir_all(dir)
+    {
+      eprintln!("[TimerService] Failed to create {}: {}", dir.display(), e);
+      return;
+    }
+
+    match serde_json::to_string_pretty(&*self.state.borrow()) {
+      Ok(content) => {
+        if let Err(e) = fs::write(&path, content) {
+          eprintln!("[TimerService] Failed to write {}: {}", path.display(), e);
+        }
+      }
+      Err(e) => eprintln!("[TimerService] Failed to serialize timers: {}", e),
+    }
+  }
+}
+
+/// `1:05:09` or `5:09`
+pub fn format_duration(duration: Duration) -> String {
+  let secs = duration.num_seconds().max(0);
+  let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+  match hours {
+    0 => format!("{}:{:02}", minutes, secs),
+    _ => format!("{}:{:02}:{:02}", hours, minutes, secs),
+  }
+}
+
+fn finish(title: &str, body: &str) {
+  if let Some(app) = gtk::gio::Application::default() {
+    let notification = gtk::gio::Notification::new(title);
+    notification.set_body(Some(body));
+    app.send_notification(None, &notification);
+  }
+  SoundButton::play_sound(FINISHED_SOUND);
+}
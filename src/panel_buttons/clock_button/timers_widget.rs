@@ -0,0 +1,291 @@
+use chrono::{Duration, Local};
+use gtk::{
+  prelude::*, Align, Box as GtkBox, Button, Entry, Label, ListBox, Orientation, SelectionMode, SpinButton,
+  Switch, ToggleButton,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::timer_service::{format_duration, Alarm, TimerService, TimerState};
+
+const TIMER_PRESET_MINUTES: [u32; 6] = [1, 3, 5, 10, 25, 60];
+const WEEKDAY_LETTERS: [&str; 7] = ["M", "T", "W", "T", "F", "S", "S"];
+const WEEKDAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// The clock dropdown's countdown timer, stopwatch and alarm pages, showing the state of the
+/// shared `TimerService`
+#[derive(Clone)]
+pub struct TimersWidget {
+  timer_page: GtkBox,
+  stopwatch_page: GtkBox,
+  alarms_page: GtkBox,
+  countdown_label: Label,
+  countdown_toggle: Button,
+  countdown_cancel: Button,
+  stopwatch_label: Label,
+  stopwatch_toggle: Button,
+  alarm_list: ListBox,
+  /// The alarms the list was last built from, so it's only rebuilt when they change
+  shown_alarms: Rc<RefCell<Option<Vec<Alarm>>>>,
+}
+
+impl TimersWidget {
+  pub fn new(service: &TimerService) -> Self {
+    let (timer_page, countdown_label, countdown_toggle, countdown_cancel) = Self::create_timer_page(service);
+    let (stopwatch_page, stopwatch_label, stopwatch_toggle) = Self::create_stopwatch_page(service);
+    let (alarms_page, alarm_list) = Self::create_alarms_page(service);
+
+    let obj = Self {
+      timer_page,
+      stopwatch_page,
+      alarms_page,
+      countdown_label,
+      countdown_toggle,
+      countdown_cancel,
+      stopwatch_label,
+      stopwatch_toggle,
+      alarm_list,
+      shown_alarms: Rc::new(RefCell::new(None)),
+    };
+
+    let obj_clone = obj.clone();
+    let service_clone = service.clone();
+    service.subscribe(move |state| obj_clone.update(&service_clone, state));
+
+    obj
+  }
+
+  pub fn timer_page(&self) -> &GtkBox {
+    &self.timer_page
+  }
+
+  pub fn stopwatch_page(&self) -> &GtkBox {
+    &self.stopwatch_page
+  }
+
+  pub fn alarms_page(&self) -> &GtkBox {
+    &self.alarms_page
+  }
+
+  fn create_page() -> GtkBox {
+    let page = GtkBox::new(Orientation::Vertical, 8);
+    page.set_margin_top(12);
+    page.set_margin_bottom(12);
+    page.set_margin_start(12);
+    page.set_margin_end(12);
+    page
+  }
+
+  fn create_display() -> Label {
+    let label = Label::new(None);
+    label.add_css_class("title-1");
+    label.add_css_class("clock-timer-display");
+    label
+  }
+
+  fn create_timer_page(service: &TimerService) -> (GtkBox, Label, Button, Button) {
+    let page = Self::create_page();
+
+    let display = Self::create_display();
+    page.append(&display);
+
+    let presets = GtkBox::new(Orientation::Horizontal, 4);
+    presets.set_halign(Align::Center);
+    for minutes in TIMER_PRESET_MINUTES {
+      let button = Button::with_label(&format!("{} min", minutes));
+      let service = service.clone();
+      button.connect_clicked(move |_| service.start_countdown(minutes as u64 * 60));
+      presets.append(&button);
+    }
+    page.append(&presets);
+
+    // Custom length in minutes
+    let custom = GtkBox::new(Orientation::Horizontal, 8);
+    custom.set_halign(Align::Center);
+    let minutes = SpinButton::with_range(1.0, 999.0, 1.0);
+    minutes.set_value(15.0);
+    custom.append(&minutes);
+    custom.append(&Label::new(Some("minutes")));
+    let start = Button::with_label("Start");
+    start.add_css_class("suggested-action");
+    let service_clone = service.clone();
+    start.connect_clicked(move |_| service_clone.start_countdown(minutes.value_as_int() as u64 * 60));
+    custom.append(&start);
+    page.append(&custom);
+
+    let controls = GtkBox::new(Orientation::Horizontal, 8);
+    controls.set_halign(Align::Center);
+    let toggle = Button::with_label("Pause");
+    let service_clone = service.clone();
+    toggle.connect_clicked(move |_| service_clone.toggle_countdown());
+    controls.append(&toggle);
+    let cancel = Button::with_label("Cancel");
+    let service_clone = service.clone();
+    cancel.connect_clicked(move |_| service_clone.cancel_countdown());
+    controls.append(&cancel);
+    page.append(&controls);
+
+    (page, display, toggle, cancel)
+  }
+
+  fn create_stopwatch_page(service: &TimerService) -> (GtkBox, Label, Button) {
+    let page = Self::create_page();
+
+    let display = Self::create_display();
+    page.append(&display);
+
+    let controls = GtkBox::new(Orientation::Horizontal, 8);
+    controls.set_halign(Align::Center);
+    let toggle = Button::with_label("Start");
+    let service_clone = service.clone();
+    toggle.connect_clicked(move |_| service_clone.toggle_stopwatch());
+    controls.append(&toggle);
+    let reset = Button::with_label("Reset");
+    let service_clone = service.clone();
+    reset.connect_clicked(move |_| service_clone.reset_stopwatch());
+    controls.append(&reset);
+    page.append(&controls);
+
+    (page, display, toggle)
+  }
+
+  fn create_alarms_page(service: &TimerService) -> (GtkBox, ListBox) {
+    let page = Self::create_page();
+
+    let list = ListBox::new();
+    list.set_selection_mode(SelectionMode::None);
+    list.add_css_class("boxed-list");
+    page.append(&list);
+
+    // New alarm: time, repeat days and label
+    let time_row = GtkBox::new(Orientation::Horizontal, 4);
+    let hour = SpinButton::with_range(0.0, 23.0, 1.0);
+    hour.set_value(7.0);
+    let minute = SpinButton::with_range(0.0, 59.0, 1.0);
+    minute.connect_output(|spin| {
+      spin.set_text(&format!("{:02}", spin.value_as_int()));
+      gtk::glib::Propagation::Stop
+    });
+    time_row.append(&hour);
+    time_row.append(&Label::new(Some(":")));
+    time_row.append(&minute);
+
+    let label = Entry::builder().placeholder_text("Label").hexpand(true).build();
+    time_row.append(&label);
+    page.append(&time_row);
+
+    let days_row = GtkBox::new(Orientation::Horizontal, 4);
+    let day_buttons: Vec<ToggleButton> = WEEKDAY_LETTERS
+      .iter()
+      .zip(WEEKDAY_NAMES)
+      .map(|(letter, name)| {
+        let button = ToggleButton::with_label(letter);
+        button.set_tooltip_text(Some(name));
+        days_row.append(&button);
+        button
+      })
+      .collect();
+
+    let add = Button::with_label("Add alarm");
+    add.set_hexpand(true);
+    add.set_halign(Align::End);
+    let service_clone = service.clone();
+    add.connect_clicked(move |_| {
+      let days = day_buttons
+        .iter()
+        .enumerate()
+        .filter(|(_, button)| button.is_active())
+        .map(|(day, _)| day as u8)
+        .collect();
+      service_clone.add_alarm(hour.value_as_int() as u32, minute.value_as_int() as u32, days, label.text().to_string());
+      label.set_text("");
+    });
+    days_row.append(&add);
+    page.append(&days_row);
+
+    (page, list)
+  }
+
+  fn update(&self, service: &TimerService, state: &TimerState) {
+    let now = Local::now().timestamp_millis();
+
+    match &state.countdown {
+      Some(countdown) => {
+        self.countdown_label.set_text(&format_duration(countdown.remaining(now)));
+        self.countdown_toggle.set_label(if countdown.is_running() { "Pause" } else { "Resume" });
+        self.countdown_toggle.set_sensitive(true);
+        self.countdown_cancel.set_sensitive(true);
+      }
+      None => {
+        self.countdown_label.set_text(&format_duration(Duration::zero()));
+        self.countdown_toggle.set_label("Pause");
+        self.countdown_toggle.set_sensitive(false);
+        self.countdown_cancel.set_sensitive(false);
+      }
+    }
+
+    self.stopwatch_label.set_text(&format_duration(state.stopwatch.elapsed(now)));
+    self.stopwatch_toggle.set_label(if state.stopwatch.is_running() { "Stop" } else { "Start" });
+
+    if self.shown_alarms.borrow().as_ref() != Some(&state.alarms) {
+      self.rebuild_alarm_list(service, &state.alarms);
+      *self.shown_alarms.borrow_mut() = Some(state.alarms.clone());
+    }
+  }
+
+  fn rebuild_alarm_list(&self, service: &TimerService, alarms: &[Alarm]) {
+    while let Some(child) = self.alarm_list.first_child() {
+      self.alarm_list.remove(&child);
+    }
+
+    if alarms.is_empty() {
+      let empty = Label::new(Some("No alarms"));
+      empty.add_css_class("dim-label");
+      empty.set_margin_top(8);
+      empty.set_margin_bottom(8);
+      self.alarm_list.append(&empty);
+      return;
+    }
+
+    for (index, alarm) in alarms.iter().enumerate() {
+      let row = GtkBox::new(Orientation::Horizontal, 8);
+      row.set_margin_top(4);
+      row.set_margin_bottom(4);
+      row.set_margin_start(8);
+      row.set_margin_end(8);
+
+      let time = Label::new(Some(&alarm.time_text()));
+      time.add_css_class("heading");
+      row.append(&time);
+
+      let repeat = match alarm.days.len() {
+        0 => "Once".to_string(),
+        7 => "Every day".to_string(),
+        _ => alarm.days.iter().filter_map(|day| WEEKDAY_NAMES.get(*day as usize)).copied().collect::<Vec<_>>().join(" "),
+      };
+      let details = if alarm.label.is_empty() { repeat } else { format!("{} · {}", alarm.label, repeat) };
+      let details = Label::new(Some(&details));
+      details.add_css_class("dim-label");
+      details.set_ellipsize(gtk::pango::EllipsizeMode::End);
+      details.set_hexpand(true);
+      details.set_xalign(0.0);
+      row.append(&details);
+
+      let enabled = Switch::builder().active(alarm.enabled).valign(Align::Center).build();
+      let service_clone = service.clone();
+      enabled.connect_state_set(move |_, active| {
+        service_clone.set_alarm_enabled(index, active);
+        gtk::glib::Propagation::Proceed
+      });
+      row.append(&enabled);
+
+      let remove = Button::from_icon_name("user-trash-symbolic");
+      remove.set_has_frame(false);
+      let service_clone = service.clone();
+      remove.connect_clicked(move |_| service_clone.remove_alarm(index));
+      row.append(&remove);
+
+      self.alarm_list.append(&row);
+    }
+  }
+}
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Local, Offset};
+use chrono_tz::Tz;
+
+/// One of the extra time zones configured for the clock
+#[derive(Clone, Debug)]
+pub struct WorldZone {
+  /// City part of the zone name, e.g. `Buenos Aires` for `America/Argentina/Buenos_Aires`
+  pub name: String,
+  pub tz: Tz,
+}
+
+impl WorldZone {
+  /// Look up IANA zone names, skipping any chrono-tz doesn't know
+  pub fn parse_all(names: &[String]) -> Vec<Self> {
+    names
+      .iter()
+      .filter_map(|name| match name.parse::<Tz>() {
+        Ok(tz) => Some(Self {
+          name: name.rsplit('/').next().unwrap_or(name).replace('_', " "),
+          tz,
+        }),
+        Err(_) => {
+          eprintln!("waltopanel: unknown time zone {:?}", name);
+          None
+        }
+      })
+      .collect()
+  }
+
+  /// How far the zone is ahead of local time, e.g. `+6h` or `-3h30m`, or empty if it's the
+  /// same. Worked out for `now`, so it follows either side's DST changes.
+  pub fn offset_from_local(&self, now: DateTime<Local>) -> String {
+    let zone_offset = now.with_timezone(&self.tz).offset().fix().local_minus_utc();
+    let minutes = (zone_offset - now.offset().local_minus_utc()) / 60;
+    if minutes == 0 {
+      return String::new();
+    }
+
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let (hours, minutes) = (minutes.abs() / 60, minutes.abs() % 60);
+    match minutes {
+      0 => format!("{}{}h", sign, hours),
+      _ => format!("{}{}h{:02}m", sign, hours, minutes),
+    }
+  }
+}
@@ -0,0 +1,118 @@
+use std::sync::Mutex;
+use zbus::zvariant::OwnedObjectPath;
+
+const AGENT_PATH: &str = "/org/waltosoft/waltopanel/IwdAgent";
+
+/// Credentials handed to iwd when it asks the agent during a connection attempt
+pub enum AgentCredentials {
+  Passphrase(String),
+  UsernamePassword { username: String, password: String },
+}
+
+static PENDING_CREDENTIALS: Mutex<Option<AgentCredentials>> = Mutex::new(None);
+static AGENT_CONNECTION: Mutex<Option<zbus::blocking::Connection>> = Mutex::new(None);
+
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "net.connman.iwd.Agent.Error")]
+pub enum AgentError {
+  #[zbus(error)]
+  ZBus(zbus::Error),
+  Canceled(String),
+}
+
+struct Agent;
+
+#[zbus::interface(name = "net.connman.iwd.Agent")]
+impl Agent {
+  fn release(&self) {
+    // iwd is shutting down; we register again before the next connection attempt
+  }
+
+  fn request_passphrase(&self, network: OwnedObjectPath) -> Result<String, AgentError> {
+    match take_pending_credentials() {
+      Some(AgentCredentials::Passphrase(passphrase)) => Ok(passphrase),
+      _ => Err(canceled(&network)),
+    }
+  }
+
+  fn request_private_key_passphrase(&self, network: OwnedObjectPath) -> Result<String, AgentError> {
+    Err(canceled(&network))
+  }
+
+  fn request_user_name_and_password(&self, network: OwnedObjectPath) -> Result<(String, String), AgentError> {
+    match take_pending_credentials() {
+      Some(AgentCredentials::UsernamePassword { username, password }) => Ok((username, password)),
+      _ => Err(canceled(&network)),
+    }
+  }
+
+  fn request_user_password(&self, network: OwnedObjectPath, _user: String) -> Result<String, AgentError> {
+    match take_pending_credentials() {
+      Some(AgentCredentials::UsernamePassword { password, .. }) => Ok(password),
+      Some(AgentCredentials::Passphrase(passphrase)) => Ok(passphrase),
+      None => Err(canceled(&network)),
+    }
+  }
+
+  fn cancel(&self, reason: String) {
+    eprintln!("[IwdAgent] Request canceled by iwd: {}", reason);
+    take_pending_credentials();
+  }
+}
+
+fn take_pending_credentials() -> Option<AgentCredentials> {
+  PENDING_CREDENTIALS.lock().unwrap().take()
+}
+
+fn canceled(network: &OwnedObjectPath) -> AgentError {
+  AgentError::Canceled(format!("No credentials available for {}", network.as_str()))
+}
+
+/// Get the system bus connection that serves the agent, registering it with iwd.
+/// Registration is repeated on every call so an iwd restart doesn't leave us without an agent.
+fn agent_connection() -> zbus::Result<zbus::blocking::Connection> {
+  let connection = {
+    let mut connection_opt = AGENT_CONNECTION.lock().unwrap();
+    match connection_opt.as_ref() {
+      Some(connection) => connection.clone(),
+      None => {
+        let connection = zbus::blocking::connection::Builder::system()?
+          .serve_at(AGENT_PATH, Agent)?
+          .build()?;
+        *connection_opt = Some(connection.clone());
+        connection
+      }
+    }
+  };
+
+  let proxy = zbus::blocking::Proxy::new(
+    &connection,
+    "net.connman.iwd",
+    "/net/connman/iwd",
+    "net.connman.iwd.AgentManager",
+  )?;
+
+  let path = OwnedObjectPath::try_from(AGENT_PATH)?;
+  match proxy.call::<_, _, ()>("RegisterAgent", &(path,)) {
+    Ok(()) => {}
+    Err(zbus::Error::MethodError(name, _, _)) if name.as_str() == "net.connman.iwd.AlreadyExists" => {}
+    Err(e) => return Err(e),
+  }
+
+  Ok(connection)
+}
+
+/// Run `connect` on the agent's connection with `credentials` available to iwd's
+/// agent requests. Pending credentials are discarded once `connect` returns.
+pub fn with_credentials<T>(
+  credentials: Option<AgentCredentials>,
+  connect: impl FnOnce(&zbus::blocking::Connection) -> T,
+) -> zbus::Result<T> {
+  let connection = agent_connection()?;
+
+  *PENDING_CREDENTIALS.lock().unwrap() = credentials;
+  let result = connect(&connection);
+  take_pending_credentials();
+
+  Ok(result)
+}
@@ -1,4 +1,6 @@
+mod iwd_agent;
 mod network_button;
 mod network_service;
 
 pub use network_button::NetworkButton;
+pub use network_service::{ConnectionType, NetworkService};
@@ -1,8 +1,11 @@
 use gtk::{Widget, prelude::WidgetExt};
 use gtk::glib::object::Cast;
+use std::cell::RefCell;
+use std::rc::Rc;
 
+use crate::config::{HotspotConfig, PanelLayoutConfig};
 use crate::models::MenuItemModel;
-use crate::panel_buttons::network_button::network_service::WifiInfo;
+use crate::panel_buttons::network_button::network_service::{KnownNetworkInfo, WifiInfo};
 use crate::types::TypedListStore;
 use crate::traits::CompositeWidget;
 use crate::widgets::PanelButton;
@@ -15,21 +18,53 @@ pub struct NetworkButton {
 }
 
 impl NetworkButton {
-  pub fn new() -> Self {
+  pub fn new(hotspot_config: Option<HotspotConfig>) -> Self {
     let initial_metrics = NetworkService::start(3); // Update every 3 seconds
     let panel_button = PanelButton::new();
     let menu = TypedListStore::<MenuItemModel>::new();
+    let hotspot_config = Rc::new(RefCell::new(hotspot_config));
 
     panel_button.set_menu(menu.clone());
 
     panel_button.connect_menu_item_clicked(move |_, item| {
       match item.id().as_str() {
+        "enable-hotspot" => {
+          if item.toggled() {
+            NetworkService::stop_hotspot();
+          } else {
+            let (ssid, passphrase) = hotspot_config.borrow()
+              .as_ref()
+              .map(|c| (c.ssid.clone(), c.passphrase.clone()))
+              .unwrap_or_default();
+
+            let hotspot_config = hotspot_config.clone();
+            NetworkService::show_hotspot_dialog(&ssid, &passphrase, move |ssid, passphrase| {
+              let hotspot = serde_json::json!({ "ssid": ssid, "passphrase": passphrase });
+              if let Err(e) = PanelLayoutConfig::update_button_config("network", |button| button["hotspot"] = hotspot.clone()) {
+                eprintln!("waltopanel: failed to save hotspot settings: {}", e);
+              }
+              NetworkService::start_hotspot(&ssid, &passphrase);
+              *hotspot_config.borrow_mut() = Some(HotspotConfig { ssid, passphrase });
+            });
+          }
+        }
         "enable-wifi" => {
           NetworkService::toggle_wifi(! item.toggled());
         }
         "enable-networking" => {
           NetworkService::toggle_networking(! item.toggled());
         }
+        "action-hidden-network" => {
+          NetworkService::show_hidden_network_dialog();
+        }
+        id if id.starts_with("known-autoconnect-") => {
+          let name = id.trim_start_matches("known-autoconnect-");
+          NetworkService::set_autoconnect(name, ! item.toggled());
+        }
+        id if id.starts_with("known-forget-") => {
+          let name = id.trim_start_matches("known-forget-");
+          NetworkService::forget_network(name);
+        }
         id if id.starts_with("connected-wifi-") => {
           // Connected network clicked - show disconnect confirmation
           let ssid = id.trim_start_matches("connected-wifi-");
@@ -68,6 +103,8 @@ impl NetworkButton {
   fn refresh_panel_button_icon(&self, metrics: &NetworkMetrics) {
     let new_icon_name = if !metrics.is_networking_enabled {
       "network-offline-symbolic".to_string()
+    } else if metrics.hotspot.active {
+      "network-wireless-hotspot-symbolic".to_string()
     } else {
       match metrics.connection_type {
         ConnectionType::Ethernet => "network-wired-symbolic".to_string(),
@@ -119,6 +156,9 @@ impl NetworkButton {
       self.refresh_ethernet_networks(&mut menu_index, menu.clone(), metrics);
       self.refresh_connected_wifi_networks(&mut menu_index, menu.clone(), metrics);
       self.refresh_available_wifi_networks(&mut menu_index, metrics);
+      self.refresh_known_networks(&mut menu_index, metrics);
+      self.refresh_hidden_network(&mut menu_index, menu.clone(), metrics);
+      self.refresh_hotspot(&mut menu_index, menu.clone(), metrics);
       self.refresh_enable_wifi(&mut menu_index, menu.clone(), metrics);
       self.refresh_network_enabled(&mut menu_index, menu.clone(), metrics);
     }
@@ -278,6 +318,128 @@ impl NetworkButton {
     }
   }
 
+  fn refresh_known_networks(&self, parent_menu_index: &mut u32, metrics: &NetworkMetrics) {
+    let mut menu_index: u32 = 0;
+    let menu = self.menu.clone();
+
+    if metrics.is_wifi_enabled && !metrics.known_networks.is_empty() {
+      let submenu = if menu.count() > 0 && *parent_menu_index <= menu.count() - 1 {
+        let model = menu.get(*parent_menu_index).unwrap();
+        model.set_id("wifi-known-list");
+        model.set_text("Known Networks");
+        model.set_icon_name(None);
+        model.set_disabled(false);
+        model.set_allow_toggle(false);
+        model.set_toggled(false);
+        model.set_separator_after(false);
+        model.submenu()
+      }
+      else {
+        let model = MenuItemModel::new("wifi-known-list", "Known Networks");
+        model.set_disabled(false);
+        model.set_allow_toggle(false);
+        model.set_toggled(false);
+        model.set_separator_after(false);
+        menu.append(model.clone());
+        model.submenu()
+      };
+      *parent_menu_index += 1;
+
+      for known_network in metrics.known_networks.iter() {
+        let text = get_known_network_text(known_network);
+        let lock_icon = get_known_network_lock_icon(known_network);
+
+        let network_submenu = if submenu.count() > 0 && menu_index <= submenu.count() - 1 {
+          let model = submenu.get(menu_index).unwrap();
+          model.set_id(&format!("known-wifi-{}", known_network.name));
+          model.set_text(&text);
+          model.set_post_label_icon_name(lock_icon.as_deref());
+          model.set_disabled(false);
+          model.set_allow_toggle(false);
+          model.set_toggled(false);
+          model.submenu()
+        }
+        else {
+          let model = MenuItemModel::new(&format!("known-wifi-{}", known_network.name), &text);
+          model.set_post_label_icon_name(lock_icon.as_deref());
+          model.set_disabled(false);
+          model.set_allow_toggle(false);
+          model.set_toggled(false);
+          submenu.append(model.clone());
+          model.submenu()
+        };
+
+        refresh_known_network_actions(&network_submenu, known_network);
+        menu_index += 1;
+      }
+
+      // Remove any extra menu items beyond the current menu_index
+      while submenu.count() > menu_index {
+        submenu.remove(menu_index);
+      }
+    }
+  }
+
+  fn refresh_hidden_network(&self, menu_index: &mut u32, menu: TypedListStore<MenuItemModel>, metrics: &NetworkMetrics) {
+    if metrics.is_wifi_enabled {
+      if menu.count() > 0 && *menu_index <= menu.count() - 1 {
+        let model = menu.get(*menu_index).unwrap();
+        update_menu_item_model(&model,"action-hidden-network", String::from("Connect to Hidden Network…"), None, false, false, false, TypedListStore::new(), false);
+        model.set_post_label_icon_name(None);
+      }
+      else {
+        let model = MenuItemModel::new("action-hidden-network", "Connect to Hidden Network…");
+        model.set_disabled(false);
+        model.set_allow_toggle(false);
+        model.set_toggled(false);
+        model.set_separator_after(false);
+        menu.append(model)
+      }
+      *menu_index += 1;
+    }
+  }
+
+  fn refresh_hotspot(&self, menu_index: &mut u32, menu: TypedListStore<MenuItemModel>, metrics: &NetworkMetrics) {
+    if ! metrics.is_wifi_enabled {
+      return;
+    }
+
+    if menu.count() > 0 && *menu_index <= menu.count() - 1 {
+      let model = menu.get(*menu_index).unwrap();
+      update_menu_item_model(&model,"enable-hotspot", String::from("Enable Hotspot"), None, false, true, metrics.hotspot.active, TypedListStore::new(), false);
+      model.set_post_label_icon_name(None);
+    }
+    else {
+      let model = MenuItemModel::new("enable-hotspot", "Enable Hotspot");
+      model.set_disabled(false);
+      model.set_allow_toggle(true);
+      model.set_toggled(metrics.hotspot.active);
+      model.set_separator_after(false);
+      menu.append(model)
+    }
+    *menu_index += 1;
+
+    if metrics.hotspot.active {
+      let text = get_hotspot_status_text(metrics);
+
+      if menu.count() > 0 && *menu_index <= menu.count() - 1 {
+        let model = menu.get(*menu_index).unwrap();
+        update_menu_item_model(&model,"hotspot-status", text, Some(String::from("network-wireless-hotspot-symbolic")), true, false, false, TypedListStore::new(), false);
+        model.set_post_label_icon_name(None);
+      }
+      else {
+        let model = MenuItemModel::new("hotspot-status", &text);
+        model.set_icon_name(Some("network-wireless-hotspot-symbolic"));
+        model.set_disabled(true);
+        model.set_allow_toggle(false);
+        model.set_toggled(false);
+        model.set_separator_after(false);
+        menu.append(model)
+      }
+      *menu_index += 1;
+    }
+  }
+
   fn refresh_enable_wifi(&self, menu_index: &mut u32, menu: TypedListStore<MenuItemModel>, metrics: &NetworkMetrics) {
     if menu.count() > 0 && *menu_index <= menu.count() - 1 {
       let model = menu.get(*menu_index).unwrap();
@@ -335,6 +497,10 @@ impl CompositeWidget for NetworkButton {
 }
 
 fn get_tooltip_text(metrics: &NetworkMetrics) -> String {
+  if metrics.hotspot.active {
+    return get_hotspot_status_text(metrics);
+  }
+
   match metrics.connection_type {
     ConnectionType::Ethernet => {
       format!("Connected to {}", metrics.connection_name)
@@ -388,6 +554,60 @@ fn update_menu_item_model(model: &MenuItemModel, id: &str, text: String, icon_na
   }
 }
 
+fn get_hotspot_status_text(metrics: &NetworkMetrics) -> String {
+  let clients = match metrics.hotspot.clients {
+    1 => String::from("1 client"),
+    n => format!("{} clients", n),
+  };
+
+  format!("Hotspot \"{}\": {}", metrics.hotspot.ssid, clients)
+}
+
+/// Keep the "Auto-connect" / "Forget Network" actions of a known network in sync
+fn refresh_known_network_actions(submenu: &TypedListStore<MenuItemModel>, known_network: &KnownNetworkInfo) {
+  let autoconnect_id = format!("known-autoconnect-{}", known_network.name);
+  let forget_id = format!("known-forget-{}", known_network.name);
+
+  if submenu.count() == 2 {
+    let autoconnect = submenu.get(0).unwrap();
+    update_menu_item_model(&autoconnect, &autoconnect_id, String::from("Auto-connect"), None, false, true, known_network.auto_connect, TypedListStore::new(), true);
+
+    let forget = submenu.get(1).unwrap();
+    update_menu_item_model(&forget, &forget_id, String::from("Forget Network"), Some(String::from("user-trash-symbolic")), false, false, false, TypedListStore::new(), false);
+  }
+  else {
+    while submenu.count() > 0 {
+      submenu.remove(0);
+    }
+
+    let autoconnect = MenuItemModel::new(&autoconnect_id, "Auto-connect");
+    autoconnect.set_allow_toggle(true);
+    autoconnect.set_toggled(known_network.auto_connect);
+    autoconnect.set_separator_after(true);
+    submenu.append(autoconnect);
+
+    let forget = MenuItemModel::new(&forget_id, "Forget Network");
+    forget.set_icon_name(Some("user-trash-symbolic"));
+    submenu.append(forget);
+  }
+}
+
+fn get_known_network_text(known_network: &KnownNetworkInfo) -> String {
+  if known_network.hidden {
+    format!("{} (hidden)", known_network.name)
+  } else {
+    known_network.name.clone()
+  }
+}
+
+fn get_known_network_lock_icon(known_network: &KnownNetworkInfo) -> Option<String> {
+  if known_network.security != "open" {
+    Some("system-lock-screen-symbolic".to_string())
+  } else {
+    None
+  }
+}
+
 fn get_wifi_icon(network: &WifiInfo) -> String {
   // Use the same icons as the panel for visual consistency
   match network.signal {
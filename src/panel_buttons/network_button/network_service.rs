@@ -1,9 +1,71 @@
 use gtk::{glib, prelude::*};
-use std::{cell::RefCell, collections::HashMap, process::Command};
+use std::{cell::RefCell, collections::HashMap, fmt, io::Write, sync::Mutex};
 use zbus::{Connection, Result as ZbusResult};
-use zbus::zvariant::OwnedValue;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
 use futures::stream::StreamExt;
 
+use super::iwd_agent::{self, AgentCredentials};
+
+const IWD_SERVICE: &str = "net.connman.iwd";
+const NETWORKD_SERVICE: &str = "org.freedesktop.network1";
+const NETWORKD_PATH: &str = "/org/freedesktop/network1";
+
+#[derive(Debug)]
+pub enum NetworkError {
+  /// A D-Bus call or connection failed
+  Dbus(zbus::Error),
+  /// A D-Bus property could not be read or written
+  Property(zbus::fdo::Error),
+  /// The kernel rfkill interface could not be read or written
+  Rfkill(std::io::Error),
+  /// iwd has no wireless device
+  NoDevice,
+  /// iwd has no device in station mode
+  NoStation,
+  /// No scanned network has the given SSID
+  NetworkNotFound(String),
+  /// No saved network has the given name
+  KnownNetworkNotFound(String),
+  /// The device did not expose an access point after switching to AP mode
+  AccessPointUnavailable,
+  /// networkd returned a link description that could not be parsed
+  InvalidLinkDescription(String),
+}
+
+impl fmt::Display for NetworkError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      NetworkError::Dbus(e) => write!(f, "D-Bus error: {}", e),
+      NetworkError::Property(e) => write!(f, "D-Bus property error: {}", e),
+      NetworkError::Rfkill(e) => write!(f, "rfkill error: {}", e),
+      NetworkError::NoDevice => write!(f, "No iwd device found"),
+      NetworkError::NoStation => write!(f, "No iwd station found"),
+      NetworkError::NetworkNotFound(ssid) => write!(f, "Network '{}' not found", ssid),
+      NetworkError::KnownNetworkNotFound(name) => write!(f, "Known network '{}' not found", name),
+      NetworkError::AccessPointUnavailable => write!(f, "Access point mode is unavailable"),
+      NetworkError::InvalidLinkDescription(e) => write!(f, "Invalid networkd link description: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl From<zbus::Error> for NetworkError {
+  fn from(e: zbus::Error) -> Self {
+    NetworkError::Dbus(e)
+  }
+}
+
+impl From<zbus::fdo::Error> for NetworkError {
+  fn from(e: zbus::fdo::Error) -> Self {
+    NetworkError::Property(e)
+  }
+}
+
+pub type NetworkResult<T> = Result<T, NetworkError>;
+
+type ManagedObjects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
 #[derive(Debug, Clone)]
 pub enum ConnectionType {
   Wifi,
@@ -19,6 +81,21 @@ pub struct WifiInfo {
   pub connected: bool,
 }
 
+#[derive(Debug, Clone)]
+pub struct KnownNetworkInfo {
+  pub name: String,
+  pub security: String,
+  pub auto_connect: bool,
+  pub hidden: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HotspotInfo {
+  pub active: bool,
+  pub ssid: String,
+  pub clients: u32,
+}
+
 #[derive(Debug, Clone)]
 pub struct EthernetInfo {
   pub name: String,
@@ -34,7 +111,9 @@ pub struct NetworkMetrics {
   pub connection_name: String,
   pub signal_strength: u8,  // 0-100, only relevant for WiFi
   pub available_wifi_networks: Vec<WifiInfo>,
+  pub known_networks: Vec<KnownNetworkInfo>,
   pub ethernet_connections: Vec<EthernetInfo>,
+  pub hotspot: HotspotInfo,
 }
 
 type NetworkCallback = Box<dyn Fn(NetworkMetrics)>;
@@ -72,9 +151,17 @@ impl NetworkService {
 
       // Start D-Bus monitoring in background thread
       std::thread::spawn(|| {
+        let connection = match system_bus() {
+          Ok(connection) => connection,
+          Err(e) => {
+            eprintln!("D-Bus monitoring error: {}", e);
+            return;
+          }
+        };
+
         let runtime = tokio::runtime::Runtime::new().unwrap();
         runtime.block_on(async {
-          if let Err(e) = monitor_dbus_signals().await {
+          if let Err(e) = monitor_dbus_signals(connection.inner()).await {
             eprintln!("D-Bus monitoring error: {}", e);
           }
         });
@@ -86,8 +173,10 @@ impl NetworkService {
           // Trigger wifi scan before collecting network lists
           trigger_wifi_scan();
           let wifi_networks = get_wifi_connections();
+          let known_networks = get_known_networks();
           let ethernet = get_ethernet_connections();
-          Self::update_network_lists(wifi_networks, ethernet);
+          let hotspot = get_hotspot_info();
+          Self::update_network_lists(wifi_networks, known_networks, ethernet, hotspot);
         });
 
         NETWORK_SERVICE.with(|service| {
@@ -127,20 +216,14 @@ impl NetworkService {
 
   pub fn toggle_networking(enable: bool) {
     std::thread::spawn(move || {
-      // Use rfkill to enable/disable WiFi and WWAN (not Bluetooth)
-      let action = if enable { "unblock" } else { "block" };
-      let _ = Command::new("rfkill")
-        .args(&[action, "wifi"])
-        .output();
-      let _ = Command::new("rfkill")
-        .args(&[action, "wwan"])
-        .output();
+      // Block or unblock WiFi and WWAN radios (not Bluetooth)
+      if let Err(e) = set_rfkill_blocked(!enable) {
+        eprintln!("[NetworkService] Failed to change rfkill state: {}", e);
+      }
 
       // After rfkill unblock, also tell iwd to power the device on
-      if enable {
-        let _ = Command::new("iwctl")
-          .args(&["device", "wlan0", "set-property", "Powered", "on"])
-          .output();
+      if enable && let Err(e) = set_device_powered_dbus(true) {
+        eprintln!("[NetworkService] Failed to power on wifi device: {}", e);
       }
 
       Self::refresh();
@@ -149,10 +232,9 @@ impl NetworkService {
 
   pub fn toggle_wifi(enable: bool) {
     std::thread::spawn(move || {
-      let state = if enable { "on" } else { "off" };
-      let _ = Command::new("iwctl")
-        .args(&["device", "wlan0", "set-property", "Powered", state])
-        .output();
+      if let Err(e) = set_device_powered_dbus(enable) {
+        eprintln!("[NetworkService] Failed to set wifi power: {}", e);
+      }
 
       // Refresh metrics after toggle
       Self::refresh();
@@ -169,10 +251,11 @@ impl NetworkService {
         Ok(NeedsPassword::No) => {
           // Network is open or known - connect directly
           // Don't call refresh() here - D-Bus signals will update state when connection completes
-          let result = connect_to_wifi_dbus(&ssid_clone);
+          let result = connect_to_wifi_dbus(&ssid_clone, None);
           
           if let Err(e) = result {
             eprintln!("[NetworkService] connect_to_wifi_dbus error: {}", e);
+            send_notification("Wi-Fi Connection Failed", &format!("Couldn't connect to \"{}\": {}", ssid_clone, e));
             Self::refresh();
           }
         }
@@ -183,6 +266,13 @@ impl NetworkService {
             show_password_dialog(&ssid_for_dialog);
           });
         }
+        Ok(NeedsPassword::Enterprise) => {
+          // 802.1X network - ask for identity and password on main thread
+          let ssid_for_dialog = ssid_clone.clone();
+          glib::idle_add_once(move || {
+            show_enterprise_dialog(&ssid_for_dialog);
+          });
+        }
         Err(e) => {
           eprintln!("[NetworkService] Error checking network: {}", e);
           Self::refresh();
@@ -195,18 +285,8 @@ impl NetworkService {
   pub fn disconnect_wifi() {
     eprintln!("[NetworkService] disconnect_wifi called");
     std::thread::spawn(move || {
-      let result = Command::new("iwctl")
-        .args(&["station", "wlan0", "disconnect"])
-        .output();
-
-      match result {
-        Ok(output) => {
-          if ! output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            eprintln!("[NetworkService] iwctl disconnect failed: {}", stderr);
-          }
-        }
-        Err(e) => eprintln!("[NetworkService] iwctl disconnect command failed: {}", e),
+      if let Err(e) = disconnect_wifi_dbus() {
+        eprintln!("[NetworkService] Station disconnect failed: {}", e);
       }
 
       Self::refresh();
@@ -221,33 +301,130 @@ impl NetworkService {
     });
   }
 
-  /// Connect to a WiFi network with a password using iwctl
+  /// Connect to a WiFi network with a password, answering iwd's passphrase request
   pub fn connect_to_wifi_with_password(ssid: &str, password: &str) {
     let ssid = ssid.to_string();
     let password = password.to_string();
 
     std::thread::spawn(move || {
       // Don't call refresh() on success - D-Bus signals will update state when connection completes
-      let result = Command::new("iwctl")
-        .args(&["--passphrase", &password, "station", "wlan0", "connect", &ssid])
-        .output();
-
-      match result {
-        Ok(output) => {
-          if ! output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            eprintln!("[NetworkService] iwctl connect failed for {}: {}", ssid, stderr);
-            Self::refresh(); // Only refresh on failure
-          }
-        }
-        Err(e) => {
-          eprintln!("[NetworkService] iwctl command failed: {}", e);
-          Self::refresh(); // Only refresh on error
-        }
+      let credentials = AgentCredentials::Passphrase(password);
+      if let Err(e) = connect_to_wifi_dbus(&ssid, Some(credentials)) {
+        eprintln!("[NetworkService] Connect failed for {}: {}", ssid, e);
+        send_notification("Wi-Fi Connection Failed", &format!("Couldn't connect to \"{}\": {}", ssid, e));
+        Self::refresh(); // Only refresh on failure
       }
     });
   }
 
+  /// Connect to an 802.1X network, answering iwd's identity and password requests
+  pub fn connect_to_enterprise_wifi(ssid: &str, username: &str, password: &str) {
+    let ssid = ssid.to_string();
+    let credentials = AgentCredentials::UsernamePassword {
+      username: username.to_string(),
+      password: password.to_string(),
+    };
+
+    std::thread::spawn(move || {
+      if let Err(e) = connect_to_wifi_dbus(&ssid, Some(credentials)) {
+        eprintln!("[NetworkService] Enterprise connect failed for {}: {}", ssid, e);
+        send_notification(
+          "Wi-Fi Connection Failed",
+          &format!("Couldn't connect to \"{}\". The network may need to be provisioned in /var/lib/iwd first.", ssid),
+        );
+        Self::refresh();
+      }
+    });
+  }
+
+  /// Show the dialog for joining a network that doesn't broadcast its SSID
+  pub fn show_hidden_network_dialog() {
+    glib::idle_add_once(move || {
+      show_hidden_network_dialog();
+    });
+  }
+
+  /// Connect to a hidden network. An empty passphrase connects to an open network.
+  pub fn connect_to_hidden_wifi(ssid: &str, passphrase: &str) {
+    let ssid = ssid.to_string();
+    let credentials = if passphrase.is_empty() {
+      None
+    } else {
+      Some(AgentCredentials::Passphrase(passphrase.to_string()))
+    };
+
+    std::thread::spawn(move || {
+      if let Err(e) = connect_to_hidden_wifi_dbus(&ssid, credentials) {
+        eprintln!("[NetworkService] Hidden network connect failed for {}: {}", ssid, e);
+        send_notification("Wi-Fi Connection Failed", &format!("Couldn't connect to hidden network \"{}\"", ssid));
+        Self::refresh();
+      }
+    });
+  }
+
+  /// Remove a saved network from iwd
+  pub fn forget_network(name: &str) {
+    let name = name.to_string();
+
+    std::thread::spawn(move || {
+      if let Err(e) = forget_known_network_dbus(&name) {
+        eprintln!("[NetworkService] Failed to forget {}: {}", name, e);
+      }
+
+      Self::refresh();
+    });
+  }
+
+  /// Enable or disable automatic connection to a saved network
+  pub fn set_autoconnect(name: &str, enabled: bool) {
+    let name = name.to_string();
+
+    std::thread::spawn(move || {
+      if let Err(e) = set_known_network_autoconnect_dbus(&name, enabled) {
+        eprintln!("[NetworkService] Failed to set AutoConnect for {}: {}", name, e);
+      }
+
+      Self::refresh();
+    });
+  }
+
+  /// Show the hotspot settings dialog. `on_confirm` receives the SSID and passphrase.
+  pub fn show_hotspot_dialog<F>(ssid: &str, passphrase: &str, on_confirm: F)
+  where
+    F: Fn(String, String) + 'static
+  {
+    show_hotspot_dialog(ssid, passphrase, on_confirm);
+  }
+
+  /// Switch the wireless device into access point mode and start broadcasting
+  pub fn start_hotspot(ssid: &str, passphrase: &str) {
+    let ssid = ssid.to_string();
+    let passphrase = passphrase.to_string();
+
+    std::thread::spawn(move || {
+      if let Err(e) = start_hotspot_dbus(&ssid, &passphrase) {
+        eprintln!("[NetworkService] Failed to start hotspot: {}", e);
+        send_notification("Hotspot Failed", &format!("Couldn't start hotspot \"{}\": {}", ssid, e));
+
+        // Don't leave the device stranded in AP mode
+        let _ = set_device_mode_dbus("station");
+      }
+
+      Self::refresh();
+    });
+  }
+
+  /// Stop the access point and return the wireless device to station mode
+  pub fn stop_hotspot() {
+    std::thread::spawn(move || {
+      if let Err(e) = stop_hotspot_dbus() {
+        eprintln!("[NetworkService] Failed to stop hotspot: {}", e);
+      }
+
+      Self::refresh();
+    });
+  }
+
   /// Immediately refresh all metrics and notify subscribers
   fn refresh() {
     let metrics = collect_metrics();
@@ -266,14 +443,21 @@ impl NetworkService {
     });
   }
 
-  /// Update only the network lists (wifi networks + ethernet) - used by periodic polling
-  fn update_network_lists(wifi_networks: Vec<WifiInfo>, ethernet: Vec<EthernetInfo>) {
+  /// Update only the network lists (wifi networks + known networks + ethernet + hotspot) - used by periodic polling
+  fn update_network_lists(
+    wifi_networks: Vec<WifiInfo>,
+    known_networks: Vec<KnownNetworkInfo>,
+    ethernet: Vec<EthernetInfo>,
+    hotspot: HotspotInfo,
+  ) {
     glib::idle_add_once(move || {
       NETWORK_SERVICE.with(|service| {
         let mut service_opt = service.borrow_mut();
         if let Some(ref mut state) = *service_opt {
           state.metrics.available_wifi_networks = wifi_networks;
+          state.metrics.known_networks = known_networks;
           state.metrics.ethernet_connections = ethernet;
+          state.metrics.hotspot = hotspot;
 
           let metrics = state.metrics.clone();
           for subscriber in &state.subscribers {
@@ -344,19 +528,34 @@ impl NetworkService {
   }
 }
 
-fn trigger_wifi_scan() {
-  if let Ok(connection) = zbus::blocking::Connection::system() {
-    if let Ok(station_path) = find_iwd_station_path(&connection) {
-      if let Ok(proxy) = zbus::blocking::Proxy::new(
-        &connection,
-        "net.connman.iwd",
-        station_path.as_str(),
-        "net.connman.iwd.Station",
-      ) {
-        let _: Result<(), _> = proxy.call("Scan", &());
-      }
-    }
+/// The system bus connection that every blocking D-Bus call shares, opened on first use
+fn system_bus() -> NetworkResult<zbus::blocking::Connection> {
+  static SYSTEM_BUS: Mutex<Option<zbus::blocking::Connection>> = Mutex::new(None);
+
+  let mut bus = SYSTEM_BUS.lock().unwrap();
+  if let Some(connection) = bus.as_ref() {
+    return Ok(connection.clone());
   }
+
+  let connection = zbus::blocking::Connection::system()?;
+  *bus = Some(connection.clone());
+  Ok(connection)
+}
+
+fn trigger_wifi_scan() {
+  // iwd rejects scans while one is already running, which is harmless
+  let _ = trigger_wifi_scan_dbus();
+}
+
+fn trigger_wifi_scan_dbus() -> NetworkResult<()> {
+  let connection = system_bus()?;
+  let objects = get_iwd_managed_objects(&connection)?;
+  let station_path = find_iwd_object_path(&objects, "net.connman.iwd.Station")
+    .ok_or(NetworkError::NoStation)?;
+
+  let proxy = iwd_proxy(&connection, &station_path, "net.connman.iwd.Station")?;
+  proxy.call::<_, _, ()>("Scan", &())?;
+  Ok(())
 }
 
 fn collect_metrics() -> NetworkMetrics {
@@ -365,6 +564,8 @@ fn collect_metrics() -> NetworkMetrics {
   let (connection_type, connection_name, signal_strength) = get_primary_connection();
   let ethernet_connections = get_ethernet_connections();
   let available_wifi_networks = get_wifi_connections();
+  let known_networks = get_known_networks();
+  let hotspot = get_hotspot_info();
 
   NetworkMetrics {
     
@@ -374,154 +575,205 @@ fn collect_metrics() -> NetworkMetrics {
     connection_name,
     signal_strength,
     available_wifi_networks,
+    known_networks,
     ethernet_connections,
+    hotspot,
   }
 }
 
+/// Create a blocking proxy for an iwd object
+fn iwd_proxy<'a>(
+  connection: &zbus::blocking::Connection,
+  path: &'a OwnedObjectPath,
+  interface: &'a str,
+) -> NetworkResult<zbus::blocking::Proxy<'a>> {
+  Ok(zbus::blocking::Proxy::new(connection, IWD_SERVICE, path.as_str(), interface)?)
+}
+
+/// Fetch every iwd object along with its interfaces and their properties
+fn get_iwd_managed_objects(connection: &zbus::blocking::Connection) -> NetworkResult<ManagedObjects> {
+  let proxy = zbus::blocking::Proxy::new(
+    connection,
+    IWD_SERVICE,
+    "/",
+    "org.freedesktop.DBus.ObjectManager",
+  )?;
+
+  Ok(proxy.call("GetManagedObjects", &())?)
+}
+
+/// Find the first iwd object that implements `interface`
+fn find_iwd_object_path(objects: &ManagedObjects, interface: &str) -> Option<OwnedObjectPath> {
+  objects
+    .iter()
+    .find(|(_, interfaces)| interfaces.contains_key(interface))
+    .map(|(path, _)| path.clone())
+}
 
-/// Check if networking is enabled by checking rfkill state for wifi
+/// Find the scanned Network object with the given SSID
+fn find_network<'a>(
+  objects: &'a ManagedObjects,
+  ssid: &str,
+) -> NetworkResult<(&'a OwnedObjectPath, &'a HashMap<String, OwnedValue>)> {
+  objects
+    .iter()
+    .find_map(|(path, interfaces)| {
+      let props = interfaces.get("net.connman.iwd.Network")?;
+      (string_property(props, "Name").as_deref() == Some(ssid)).then_some((path, props))
+    })
+    .ok_or_else(|| NetworkError::NetworkNotFound(ssid.to_string()))
+}
+
+fn string_property(props: &HashMap<String, OwnedValue>, name: &str) -> Option<String> {
+  props.get(name).and_then(|v| TryInto::<String>::try_into(v.clone()).ok())
+}
+
+fn bool_property(props: &HashMap<String, OwnedValue>, name: &str) -> Option<bool> {
+  props.get(name).and_then(|v| TryInto::<bool>::try_into(v.clone()).ok())
+}
+
+/// Check if networking is enabled by checking the kernel rfkill state for wifi
 fn is_networking_enabled() -> bool {
-  let output = Command::new("rfkill")
-    .args(&["list", "wifi"])
-    .output();
-
-  if let Ok(output) = output {
-    if output.status.success() {
-      let stdout = String::from_utf8_lossy(&output.stdout);
-      // Check for "Soft blocked: yes" or "Hard blocked: yes"
-      // If either is blocked, networking is disabled
-      let soft_blocked = stdout.lines().any(|line| {
-        line.contains("Soft blocked:") && line.contains("yes")
-      });
-      let hard_blocked = stdout.lines().any(|line| {
-        line.contains("Hard blocked:") && line.contains("yes")
-      });
-      return !soft_blocked && !hard_blocked;
+  // Default to true if we can't determine the state
+  is_networking_enabled_rfkill().unwrap_or(true)
+}
+
+fn is_networking_enabled_rfkill() -> NetworkResult<bool> {
+  for entry in std::fs::read_dir("/sys/class/rfkill").map_err(NetworkError::Rfkill)? {
+    let path = entry.map_err(NetworkError::Rfkill)?.path();
+    let read = |name: &str| {
+      std::fs::read_to_string(path.join(name))
+        .map(|s| s.trim().to_string())
+        .map_err(NetworkError::Rfkill)
+    };
+
+    if read("type")? != "wlan" {
+      continue;
+    }
+
+    // If either soft or hard blocked, networking is disabled
+    if read("soft")? == "1" || read("hard")? == "1" {
+      return Ok(false);
     }
   }
 
-  // Default to true if we can't determine the state
-  true
+  Ok(true)
 }
 
-fn is_wifi_enabled() -> bool {
-  let output = Command::new("iwctl")
-    .args(&["device", "list"])
-    .output();
-
-  if let Ok(output) = output {
-    if output.status.success() {
-      let stdout = String::from_utf8_lossy(&output.stdout);
-      // Look for wlan0 line and check if Powered column is "on"
-      for line in stdout.lines() {
-        if line.contains("wlan0") {
-          let cleaned = strip_ansi_codes(line);
-          let parts: Vec<&str> = cleaned.split_whitespace().collect();
-          // Format: Name Address Powered Adapter Mode
-          // Index:  0    1       2       3       4
-          if parts.len() >= 3 {
-            return parts[2] == "on";
-          }
-        }
-      }
-    }
+/// Block or unblock every WiFi and WWAN radio through /dev/rfkill
+fn set_rfkill_blocked(blocked: bool) -> NetworkResult<()> {
+  const RFKILL_TYPE_WLAN: u8 = 1;
+  const RFKILL_TYPE_WWAN: u8 = 5;
+  const RFKILL_OP_CHANGE_ALL: u8 = 3;
+
+  let mut device = std::fs::OpenOptions::new().write(true).open("/dev/rfkill").map_err(NetworkError::Rfkill)?;
+
+  for rfkill_type in [RFKILL_TYPE_WLAN, RFKILL_TYPE_WWAN] {
+    // struct rfkill_event { __u32 idx; __u8 type; __u8 op; __u8 soft; __u8 hard; }
+    let mut event = [0u8; 8];
+    event[4] = rfkill_type;
+    event[5] = RFKILL_OP_CHANGE_ALL;
+    event[6] = blocked as u8;
+    device.write_all(&event).map_err(NetworkError::Rfkill)?;
   }
 
-  false
+  Ok(())
+}
+
+fn is_wifi_enabled() -> bool {
+  is_wifi_enabled_dbus().unwrap_or(false)
+}
+
+/// Check whether the iwd wireless device is powered
+fn is_wifi_enabled_dbus() -> NetworkResult<bool> {
+  let connection = system_bus()?;
+  let objects = get_iwd_managed_objects(&connection)?;
+
+  objects
+    .values()
+    .find_map(|interfaces| interfaces.get("net.connman.iwd.Device"))
+    .map(|props| bool_property(props, "Powered").unwrap_or(false))
+    .ok_or(NetworkError::NoDevice)
+}
+
+fn set_device_powered_dbus(powered: bool) -> NetworkResult<()> {
+  let connection = system_bus()?;
+  let objects = get_iwd_managed_objects(&connection)?;
+  let device_path = find_iwd_object_path(&objects, "net.connman.iwd.Device")
+    .ok_or(NetworkError::NoDevice)?;
+
+  let proxy = iwd_proxy(&connection, &device_path, "net.connman.iwd.Device")?;
+  proxy.set_property("Powered", powered)?;
+  Ok(())
 }
 
 fn get_primary_connection() -> (ConnectionType, String, u8) {
-  let primary_device = get_default_route_device();
-
-  if let Some(device) = primary_device {
-    // Check if it's wifi (wlan*)
-    if device.starts_with("wlan") {
-      // Get wifi connection info
-      let output = Command::new("iwctl")
-        .args(&["station", &device, "show"])
-        .output();
-
-      if let Ok(output) = output {
-        if output.status.success() {
-          let stdout = String::from_utf8_lossy(&output.stdout);
-
-          let mut connected_network = None;
-          let mut rssi = None;
-
-          for line in stdout.lines() {
-            let cleaned = strip_ansi_codes(line);
-            if cleaned.contains("Connected network") {
-              if let Some(network) = cleaned.split_whitespace().last() {
-                connected_network = Some(network.to_string());
-              }
-            } else if cleaned.contains("RSSI") {
-              // Parse: "RSSI                  -59 dBm"
-              let parts: Vec<&str> = cleaned.split_whitespace().collect();
-              if let Some(pos) = parts.iter().position(|&p| p == "RSSI") {
-                if pos + 1 < parts.len() {
-                  if let Ok(dbm) = parts[pos + 1].parse::<i32>() {
-                    rssi = Some(dbm);
-                  }
-                }
-              }
-            }
-          }
+  get_primary_connection_dbus().unwrap_or_else(|e| {
+    eprintln!("[NetworkService] Failed to query primary connection: {}", e);
+    (ConnectionType::Disconnected, String::from("Not connected"), 0)
+  })
+}
 
-          if let Some(network) = connected_network {
-            let signal = rssi.map(|r| dbm_to_percentage(r)).unwrap_or(0);
-            return (ConnectionType::Wifi, network, signal);
-          }
-        }
-      }
-    } else if device.starts_with("en") || device.starts_with("eth") {
-      // It's ethernet
-      return (ConnectionType::Ethernet, device, 0);
-    }
+/// Pick the primary connection from networkd's routable links, preferring ethernet over wifi
+fn get_primary_connection_dbus() -> NetworkResult<(ConnectionType, String, u8)> {
+  let links = get_networkd_links()?;
+  let routable = |link_type: &str| {
+    links.iter().find(|link| link.link_type == link_type && link.operational_state == "routable")
+  };
+
+  if let Some(link) = routable("ether") {
+    return Ok((ConnectionType::Ethernet, link.name.clone(), 0));
   }
 
-  (ConnectionType::Disconnected, String::from("Not connected"), 0)
+  if routable("wlan").is_some() {
+    return get_wlan_connection_dbus();
+  }
+
+  Ok((ConnectionType::Disconnected, String::from("Not connected"), 0))
 }
 
-/// Query wlan0 connection info directly from iwd, without checking ip route.
+/// Query the station's connection info directly from iwd, without checking networkd.
 /// Use this when we know the connection is WiFi (e.g. from iwd D-Bus signals)
-/// but the default route may not be established yet.
+/// but networkd may not have configured the link yet.
 fn get_wlan_connection() -> (ConnectionType, String, u8) {
-  let output = Command::new("iwctl")
-    .args(&["station", "wlan0", "show"])
-    .output();
-
-  if let Ok(output) = output {
-    if output.status.success() {
-      let stdout = String::from_utf8_lossy(&output.stdout);
-      let mut connected_network = None;
-      let mut rssi = None;
-
-      for line in stdout.lines() {
-        let cleaned = strip_ansi_codes(line);
-        if cleaned.contains("Connected network") {
-          if let Some(network) = cleaned.split_whitespace().last() {
-            connected_network = Some(network.to_string());
-          }
-        } else if cleaned.contains("RSSI") {
-          let parts: Vec<&str> = cleaned.split_whitespace().collect();
-          if let Some(pos) = parts.iter().position(|&p| p == "RSSI") {
-            if pos + 1 < parts.len() {
-              if let Ok(dbm) = parts[pos + 1].parse::<i32>() {
-                rssi = Some(dbm);
-              }
-            }
-          }
-        }
-      }
-
-      if let Some(network) = connected_network {
-        let signal = rssi.map(|r| dbm_to_percentage(r)).unwrap_or(0);
-        return (ConnectionType::Wifi, network, signal);
-      }
-    }
-  }
+  get_wlan_connection_dbus().unwrap_or_else(|e| {
+    eprintln!("[NetworkService] Failed to query wifi connection: {}", e);
+    (ConnectionType::Disconnected, String::from("Not connected"), 0)
+  })
+}
 
-  (ConnectionType::Disconnected, String::from("Not connected"), 0)
+fn get_wlan_connection_dbus() -> NetworkResult<(ConnectionType, String, u8)> {
+  let connection = system_bus()?;
+  let objects = get_iwd_managed_objects(&connection)?;
+  let station_path = find_iwd_object_path(&objects, "net.connman.iwd.Station")
+    .ok_or(NetworkError::NoStation)?;
+
+  // ConnectedNetwork is only present while the station is connected
+  let network_path = objects
+    .get(&station_path)
+    .and_then(|interfaces| interfaces.get("net.connman.iwd.Station"))
+    .and_then(|props| props.get("ConnectedNetwork"))
+    .and_then(|v| TryInto::<OwnedObjectPath>::try_into(v.clone()).ok());
+
+  let Some(network_path) = network_path else {
+    return Ok((ConnectionType::Disconnected, String::from("Not connected"), 0));
+  };
+
+  let name = objects
+    .get(&network_path)
+    .and_then(|interfaces| interfaces.get("net.connman.iwd.Network"))
+    .and_then(|props| string_property(props, "Name"))
+    .unwrap_or_default();
+
+  let station_proxy = iwd_proxy(&connection, &station_path, "net.connman.iwd.Station")?;
+  let ordered_networks: Vec<(OwnedObjectPath, i16)> = station_proxy.call("GetOrderedNetworks", &())?;
+  let signal = ordered_networks
+    .iter()
+    .find(|(path, _)| *path == network_path)
+    .map(|(_, signal_dbm100)| dbm_to_percentage(*signal_dbm100 as i32 / 100))
+    .unwrap_or(0);
+
+  Ok((ConnectionType::Wifi, name, signal))
 }
 
 fn dbm_to_percentage(dbm: i32) -> u8 {
@@ -539,110 +791,92 @@ fn dbm_to_percentage(dbm: i32) -> u8 {
   }
 }
 
-fn get_default_route_device() -> Option<String> {
-  let output = Command::new("ip")
-    .args(&["route", "show", "default"])
-    .output();
+struct NetworkdLink {
+  name: String,
+  link_type: String,
+  operational_state: String,
+}
 
-  if let Ok(output) = output {
-    if output.status.success() {
-      let stdout = String::from_utf8_lossy(&output.stdout);
+/// List every link networkd manages along with its type and operational state
+fn get_networkd_links() -> NetworkResult<Vec<NetworkdLink>> {
+  let connection = system_bus()?;
+  let proxy = zbus::blocking::Proxy::new(
+    &connection,
+    NETWORKD_SERVICE,
+    NETWORKD_PATH,
+    "org.freedesktop.network1.Manager",
+  )?;
 
-      // Parse: "default via 192.168.1.1 dev wlp2s0 proto dhcp metric 600"
-      for line in stdout.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
+  let links: Vec<(i32, String, OwnedObjectPath)> = proxy.call("ListLinks", &())?;
 
-        // Find "dev" keyword and get the next word (the device name)
-        for i in 0..parts.len() {
-          if parts[i] == "dev" && i + 1 < parts.len() {
-            return Some(parts[i + 1].to_string());
-          }
-        }
-      }
-    }
-  }
+  links
+    .into_iter()
+    .map(|(index, name, _path)| {
+      let description: String = proxy.call("DescribeLink", &(index,))?;
+      let json: serde_json::Value = serde_json::from_str(&description)
+        .map_err(|e| NetworkError::InvalidLinkDescription(e.to_string()))?;
+      let field = |key: &str| json.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
 
-  None
+      Ok(NetworkdLink {
+        name,
+        link_type: field("Type"),
+        operational_state: field("OperationalState"),
+      })
+    })
+    .collect()
 }
 
-
 fn get_ethernet_connections() -> Vec<EthernetInfo> {
-  let output = Command::new("networkctl")
-    .args(&["list"])
-    .output();
-
-  if let Ok(output) = output {
-    if output.status.success() {
-      let stdout = String::from_utf8_lossy(&output.stdout);
-      return stdout.lines()
-        .skip(1)  // Skip header
-        .filter_map(|line| {
-          let parts: Vec<&str> = line.split_whitespace().collect();
-
-          // Format: IDX LINK TYPE OPERATIONAL SETUP
-          if parts.len() >= 4 && parts[2] == "ether" {
-            let device = parts[1].to_string();
-            let operational = parts[3];
-
-            // Connected if operational is "routable" or "carrier"
-            let connected = operational == "routable" || operational == "carrier";
-
-            Some(EthernetInfo {
-              name: device.clone(),
-              _device: device,
-              connected,
-            })
-          } else {
-            None
-          }
-        })
-        .collect();
-    }
-  }
+  get_ethernet_connections_dbus().unwrap_or_default()
+}
 
-  Vec::new()
+fn get_ethernet_connections_dbus() -> NetworkResult<Vec<EthernetInfo>> {
+  let links = get_networkd_links()?;
+
+  Ok(links
+    .into_iter()
+    .filter(|link| link.link_type == "ether")
+    .map(|link| {
+      // Connected if operational is "routable" or "carrier"
+      let connected = link.operational_state == "routable" || link.operational_state == "carrier";
+
+      EthernetInfo {
+        name: link.name.clone(),
+        _device: link.name,
+        connected,
+      }
+    })
+    .collect())
 }
 
 fn get_wifi_connections() -> Vec<WifiInfo> {
   // Use D-Bus to query iwd for precise signal strength values
-  get_wifi_connections_dbus().unwrap_or_else(|_| {
-    get_wifi_connections_iwctl_fallback()
-  })
+  get_wifi_connections_dbus().unwrap_or_default()
 }
 
-fn get_wifi_connections_dbus() -> Result<Vec<WifiInfo>, Box<dyn std::error::Error>> {
-  // Use blocking D-Bus connection
-  let connection = zbus::blocking::Connection::system()?;
+fn get_wifi_connections_dbus() -> NetworkResult<Vec<WifiInfo>> {
+  let connection = system_bus()?;
+  let objects = get_iwd_managed_objects(&connection)?;
+  let station_path = find_iwd_object_path(&objects, "net.connman.iwd.Station")
+    .ok_or(NetworkError::NoStation)?;
 
-  // Find the station path by looking for objects that implement net.connman.iwd.Station
-  let station_path = find_iwd_station_path(&connection)?;
-
-  let proxy = zbus::blocking::Proxy::new(
-    &connection,
-    "net.connman.iwd",
-    station_path.as_str(),
-    "net.connman.iwd.Station",
-  )?;
+  let proxy = iwd_proxy(&connection, &station_path, "net.connman.iwd.Station")?;
 
   // Call GetOrderedNetworks on the station to get networks with signal strength
   // Returns array of (object_path, signal_strength_dbm_times_100)
-  let ordered_networks: Vec<(zbus::zvariant::OwnedObjectPath, i16)> =
+  let ordered_networks: Vec<(OwnedObjectPath, i16)> =
     proxy.call("GetOrderedNetworks", &())?;
 
   let mut networks = Vec::new();
 
   for (network_path, signal_dbm100) in ordered_networks {
-    // Get network properties
-    let network_proxy = zbus::blocking::Proxy::new(
-      &connection,
-      "net.connman.iwd",
-      network_path.as_str(),
-      "net.connman.iwd.Network",
-    )?;
-
-    let name: String = network_proxy.get_property("Name")?;
-    let network_type: String = network_proxy.get_property("Type")?;
-    let connected: bool = network_proxy.get_property("Connected")?;
+    // Network properties come from the same ObjectManager snapshot
+    let Some(props) = objects
+      .get(&network_path)
+      .and_then(|interfaces| interfaces.get("net.connman.iwd.Network"))
+    else {
+      continue;
+    };
 
     // Convert signal from dBm*100 to percentage
     // signal_dbm100 is like -5900 for -59 dBm
@@ -650,10 +884,10 @@ fn get_wifi_connections_dbus() -> Result<Vec<WifiInfo>, Box<dyn std::error::Erro
     let signal = dbm_to_percentage(dbm as i32);
 
     networks.push(WifiInfo {
-      ssid: name,
+      ssid: string_property(props, "Name").unwrap_or_default(),
       signal,
-      security: network_type,
-      connected,
+      security: string_property(props, "Type").unwrap_or_default(),
+      connected: bool_property(props, "Connected").unwrap_or(false),
     });
   }
 
@@ -690,78 +924,193 @@ fn get_wifi_connections_dbus() -> Result<Vec<WifiInfo>, Box<dyn std::error::Erro
   Ok(result)
 }
 
-/// Find the iwd station object path by querying the ObjectManager
-fn find_iwd_station_path(connection: &zbus::blocking::Connection) -> Result<String, Box<dyn std::error::Error>> {
+fn get_known_networks() -> Vec<KnownNetworkInfo> {
+  get_known_networks_dbus().unwrap_or_else(|e| {
+    eprintln!("[NetworkService] Failed to query known networks: {}", e);
+    Vec::new()
+  })
+}
 
-  // Query ObjectManager to get all iwd objects
-  let proxy = zbus::blocking::Proxy::new(
-    connection,
-    "net.connman.iwd",
-    "/",
-    "org.freedesktop.DBus.ObjectManager",
-  )?;
+/// List the networks iwd has saved credentials for, ordered by name
+fn get_known_networks_dbus() -> NetworkResult<Vec<KnownNetworkInfo>> {
+  let connection = system_bus()?;
+  let objects = get_iwd_managed_objects(&connection)?;
+
+  let mut known_networks: Vec<KnownNetworkInfo> = objects
+    .values()
+    .filter_map(|interfaces| {
+      let props = interfaces.get("net.connman.iwd.KnownNetwork")?;
+
+      Some(KnownNetworkInfo {
+        name: string_property(props, "Name")?,
+        security: string_property(props, "Type").unwrap_or_default(),
+        auto_connect: bool_property(props, "AutoConnect").unwrap_or(true),
+        hidden: bool_property(props, "Hidden").unwrap_or(false),
+      })
+    })
+    .collect();
 
-  let objects: HashMap<zbus::zvariant::OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>> =
-    proxy.call("GetManagedObjects", &())?;
+  known_networks.sort_by_key(|network| network.name.to_lowercase());
 
-  // Find the first object that implements net.connman.iwd.Station
-  for (path, interfaces) in objects {
-    if interfaces.contains_key("net.connman.iwd.Station") {
-      return Ok(path.to_string());
-    }
-  }
+  Ok(known_networks)
+}
 
-  Err("No iwd station found".into())
+/// Find the object path of the iwd KnownNetwork with the given name
+fn find_known_network_path(
+  connection: &zbus::blocking::Connection,
+  name: &str,
+) -> NetworkResult<OwnedObjectPath> {
+  let objects = get_iwd_managed_objects(connection)?;
+
+  objects
+    .into_iter()
+    .find_map(|(path, interfaces)| {
+      let props = interfaces.get("net.connman.iwd.KnownNetwork")?;
+      (string_property(props, "Name").as_deref() == Some(name)).then_some(path)
+    })
+    .ok_or_else(|| NetworkError::KnownNetworkNotFound(name.to_string()))
 }
 
-enum NeedsPassword {
-  Yes,
-  No,
+fn forget_known_network_dbus(name: &str) -> NetworkResult<()> {
+  let connection = system_bus()?;
+  let path = find_known_network_path(&connection, name)?;
+
+  let proxy = iwd_proxy(&connection, &path, "net.connman.iwd.KnownNetwork")?;
+  proxy.call::<_, _, ()>("Forget", &())?;
+  Ok(())
 }
 
-/// Check if a network needs a password to connect
-fn check_network_needs_password(ssid: &str) -> Result<NeedsPassword, Box<dyn std::error::Error>> {
-  let connection = zbus::blocking::Connection::system()?;
+fn set_known_network_autoconnect_dbus(name: &str, enabled: bool) -> NetworkResult<()> {
+  let connection = system_bus()?;
+  let path = find_known_network_path(&connection, name)?;
 
-  let proxy = zbus::blocking::Proxy::new(
-    &connection,
-    "net.connman.iwd",
-    "/",
-    "org.freedesktop.DBus.ObjectManager",
-  )?;
+  let proxy = iwd_proxy(&connection, &path, "net.connman.iwd.KnownNetwork")?;
+  proxy.set_property("AutoConnect", enabled)?;
+  Ok(())
+}
 
-  let objects: HashMap<zbus::zvariant::OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>> =
-    proxy.call("GetManagedObjects", &())?;
+/// Find the iwd device object path (the Device interface survives mode switches)
+fn find_iwd_device_path(connection: &zbus::blocking::Connection) -> NetworkResult<OwnedObjectPath> {
+  let objects = get_iwd_managed_objects(connection)?;
+  find_iwd_object_path(&objects, "net.connman.iwd.Device").ok_or(NetworkError::NoDevice)
+}
 
-  for (_path, interfaces) in objects {
-    if let Some(network_props) = interfaces.get("net.connman.iwd.Network") {
-      if let Some(name_value) = network_props.get("Name") {
-        if let Ok(name) = TryInto::<String>::try_into(name_value.clone()) {
-          if name == ssid {
-            let network_type = network_props.get("Type")
-              .and_then(|v| TryInto::<String>::try_into(v.clone()).ok())
-              .unwrap_or_default();
+fn get_hotspot_info() -> HotspotInfo {
+  get_hotspot_info_dbus().unwrap_or_default()
+}
 
-            let known_network = network_props.get("KnownNetwork")
-              .and_then(|v| TryInto::<zbus::zvariant::OwnedObjectPath>::try_into(v.clone()).ok());
+/// Query the access point state and connected client count of the wireless device
+fn get_hotspot_info_dbus() -> NetworkResult<HotspotInfo> {
+  let connection = system_bus()?;
+  let device_path = find_iwd_device_path(&connection)?;
 
-            let is_known = known_network.is_some();
-            let is_open = network_type == "open";
+  let ap_proxy = iwd_proxy(&connection, &device_path, "net.connman.iwd.AccessPoint")?;
 
-            if is_open || is_known {
-              return Ok(NeedsPassword::No);
-            } else {
-              return Ok(NeedsPassword::Yes);
-            }
-          }
-        }
+  // The AccessPoint interface only exists while the device is in "ap" mode
+  let active: bool = match ap_proxy.get_property("Started") {
+    Ok(started) => started,
+    Err(_) => return Ok(HotspotInfo::default()),
+  };
+
+  if !active {
+    return Ok(HotspotInfo::default());
+  }
+
+  let ssid: String = ap_proxy.get_property("Name").unwrap_or_default();
+
+  let diagnostic_proxy = iwd_proxy(&connection, &device_path, "net.connman.iwd.AccessPointDiagnostic")?;
+
+  // One dictionary per associated station
+  let clients = diagnostic_proxy
+    .call::<_, _, Vec<HashMap<String, OwnedValue>>>("GetDiagnostics", &())
+    .map(|stations| stations.len() as u32)
+    .unwrap_or(0);
+
+  Ok(HotspotInfo { active, ssid, clients })
+}
+
+fn set_device_mode_dbus(mode: &str) -> NetworkResult<()> {
+  let connection = system_bus()?;
+  let device_path = find_iwd_device_path(&connection)?;
+
+  let proxy = iwd_proxy(&connection, &device_path, "net.connman.iwd.Device")?;
+  proxy.set_property("Mode", mode)?;
+  Ok(())
+}
+
+fn start_hotspot_dbus(ssid: &str, passphrase: &str) -> NetworkResult<()> {
+  set_device_mode_dbus("ap")?;
+
+  let connection = system_bus()?;
+  let device_path = find_iwd_device_path(&connection)?;
+
+  let proxy = iwd_proxy(&connection, &device_path, "net.connman.iwd.AccessPoint")?;
+
+  // iwd publishes the AccessPoint interface asynchronously after the mode switch
+  let mut last_error = None;
+  for _ in 0..10 {
+    match proxy.call::<_, _, ()>("Start", &(ssid, passphrase)) {
+      Ok(()) => return Ok(()),
+      Err(e) => {
+        last_error = Some(e);
+        std::thread::sleep(std::time::Duration::from_millis(200));
       }
     }
   }
 
-  Err(format!("Network '{}' not found", ssid).into())
+  Err(last_error.map(NetworkError::Dbus).unwrap_or(NetworkError::AccessPointUnavailable))
+}
+
+fn stop_hotspot_dbus() -> NetworkResult<()> {
+  let connection = system_bus()?;
+  let device_path = find_iwd_device_path(&connection)?;
+
+  let proxy = iwd_proxy(&connection, &device_path, "net.connman.iwd.AccessPoint")?;
+
+  if let Err(e) = proxy.call::<_, _, ()>("Stop", &()) {
+    eprintln!("[NetworkService] AccessPoint.Stop failed: {}", e);
+  }
+
+  set_device_mode_dbus("station")
 }
 
+/// Send a desktop notification from any thread
+fn send_notification(title: &str, body: &str) {
+  let title = title.to_string();
+  let body = body.to_string();
+  glib::MainContext::default().invoke(move || {
+    if let Some(app) = gtk::gio::Application::default() {
+      let notification = gtk::gio::Notification::new(&title);
+      notification.set_body(Some(&body));
+      app.send_notification(None, &notification);
+    }
+  });
+}
+
+enum NeedsPassword {
+  Yes,
+  No,
+  Enterprise,
+}
+
+/// Check if a network needs a password to connect
+fn check_network_needs_password(ssid: &str) -> NetworkResult<NeedsPassword> {
+  let connection = system_bus()?;
+  let objects = get_iwd_managed_objects(&connection)?;
+  let (_path, network_props) = find_network(&objects, ssid)?;
+
+  let network_type = string_property(network_props, "Type").unwrap_or_default();
+  let is_known = network_props.contains_key("KnownNetwork");
+  let is_open = network_type == "open";
+
+  if is_open || is_known {
+    Ok(NeedsPassword::No)
+  } else if network_type == "8021x" {
+    Ok(NeedsPassword::Enterprise)
+  } else {
+    Ok(NeedsPassword::Yes)
+  }
+}
 /// Show a confirmation dialog for disconnecting from a WiFi network
 fn show_disconnect_confirmation_dialog(ssid: &str) {
   let ssid = ssid.to_string();
@@ -895,171 +1244,295 @@ fn show_password_dialog(ssid: &str) {
   dialog.present();
 }
 
-/// Connect to a WiFi network via D-Bus by finding the network and calling Connect
-/// This should only be called for networks that don't need a password (open or known)
-fn connect_to_wifi_dbus(ssid: &str) -> Result<(), Box<dyn std::error::Error>> {
-  let connection = zbus::blocking::Connection::system()?;
+/// Show a dialog asking for the SSID and optional passphrase of a hidden network
+fn show_hidden_network_dialog() {
+  let dialog = gtk::Window::builder()
+    .title("Connect to Hidden Network")
+    .modal(true)
+    .default_width(350)
+    .default_height(180)
+    .resizable(false)
+    .build();
 
-  // Query ObjectManager to find the Network object with matching SSID
-  let proxy = zbus::blocking::Proxy::new(
-    &connection,
-    "net.connman.iwd",
-    "/",
-    "org.freedesktop.DBus.ObjectManager",
-  )?;
+  let content = gtk::Box::new(gtk::Orientation::Vertical, 12);
+  content.set_margin_top(20);
+  content.set_margin_bottom(20);
+  content.set_margin_start(20);
+  content.set_margin_end(20);
 
-  let objects: HashMap<zbus::zvariant::OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>> =
-    proxy.call("GetManagedObjects", &())?;
-
-  // Find the Network object with the matching SSID
-  for (path, interfaces) in objects {
-    if let Some(network_props) = interfaces.get("net.connman.iwd.Network") {
-      if let Some(name_value) = network_props.get("Name") {
-        if let Ok(name) = TryInto::<String>::try_into(name_value.clone()) {
-          if name == ssid {
-            // Call Connect on this Network
-            let network_proxy = zbus::blocking::Proxy::new(
-              &connection,
-              "net.connman.iwd",
-              path.as_str(),
-              "net.connman.iwd.Network",
-            )?;
-
-            let result: Result<(), zbus::Error> = network_proxy.call("Connect", &());
-
-            if let Err(e) = result {
-              eprintln!("[NetworkService] Connect call failed for {}: {}", ssid, e);
-              return Err(e.into());
-            }
+  let ssid_label = gtk::Label::new(Some("Network name (SSID):"));
+  ssid_label.set_halign(gtk::Align::Start);
+  content.append(&ssid_label);
 
-            return Ok(());
-          }
-        }
+  let ssid_entry = gtk::Entry::new();
+  ssid_entry.set_hexpand(true);
+  content.append(&ssid_entry);
+
+  let password_label = gtk::Label::new(Some("Password (leave empty for open networks):"));
+  password_label.set_halign(gtk::Align::Start);
+  content.append(&password_label);
+
+  let password_entry = gtk::PasswordEntry::new();
+  password_entry.set_show_peek_icon(true);
+  password_entry.set_hexpand(true);
+  content.append(&password_entry);
+
+  let button_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+  button_box.set_halign(gtk::Align::End);
+  button_box.set_margin_top(8);
+
+  let cancel_button = gtk::Button::with_label("Cancel");
+  let connect_button = gtk::Button::with_label("Connect");
+  connect_button.add_css_class("suggested-action");
+
+  button_box.append(&cancel_button);
+  button_box.append(&connect_button);
+  content.append(&button_box);
+
+  dialog.set_child(Some(&content));
+
+  // Connect signals
+  let dialog_weak = dialog.downgrade();
+  cancel_button.connect_clicked(move |_| {
+    if let Some(d) = dialog_weak.upgrade() {
+      d.close();
+    }
+  });
+
+  let dialog_weak = dialog.downgrade();
+  let ssid_clone = ssid_entry.clone();
+  let password_clone = password_entry.clone();
+  let submit = move || {
+    let ssid = ssid_clone.text().to_string();
+    if !ssid.is_empty() {
+      NetworkService::connect_to_hidden_wifi(&ssid, &password_clone.text());
+      if let Some(d) = dialog_weak.upgrade() {
+        d.close();
       }
     }
-  }
+  };
 
-  Err(format!("Network '{}' not found", ssid).into())
-}
+  let submit_clone = submit.clone();
+  connect_button.connect_clicked(move |_| submit_clone());
 
-/// Fallback to iwctl parsing if D-Bus fails
-fn get_wifi_connections_iwctl_fallback() -> Vec<WifiInfo> {
-  let output = Command::new("iwctl")
-    .args(&["station", "wlan0", "get-networks"])
-    .output();
+  // Allow Enter key to submit from either field
+  let submit_clone = submit.clone();
+  ssid_entry.connect_activate(move |_| submit_clone());
+  password_entry.connect_activate(move |_| submit());
 
-  if let Ok(output) = output {
-    if output.status.success() {
-      let stdout = String::from_utf8_lossy(&output.stdout);
+  dialog.present();
+}
 
-      let networks: Vec<WifiInfo> = stdout.lines()
-        .skip(4)
-        .filter_map(|line| {
-          if line.trim().is_empty() || line.contains("----") {
-            return None;
-          }
+/// Show an identity/password dialog for connecting to an 802.1X network
+fn show_enterprise_dialog(ssid: &str) {
+  let ssid = ssid.to_string();
 
-          let cleaned = strip_ansi_codes(line);
-          let connected = cleaned.trim_start().starts_with('>');
-          let parts: Vec<&str> = cleaned.split_whitespace().collect();
+  let dialog = gtk::Window::builder()
+    .title(format!("Connect to {}", ssid))
+    .modal(true)
+    .default_width(350)
+    .default_height(180)
+    .resizable(false)
+    .build();
 
-          if parts.len() < 3 {
-            return None;
-          }
+  let content = gtk::Box::new(gtk::Orientation::Vertical, 12);
+  content.set_margin_top(20);
+  content.set_margin_bottom(20);
+  content.set_margin_start(20);
+  content.set_margin_end(20);
 
-          let mut ssid_parts = Vec::new();
-          let mut security = String::new();
+  let label = gtk::Label::new(Some(&format!("\"{}\" requires enterprise (802.1X) credentials:", ssid)));
+  label.set_halign(gtk::Align::Start);
+  label.set_wrap(true);
+  content.append(&label);
 
-          for part in parts.iter() {
-            if part == &">" {
-              continue;
-            }
-            if *part == "psk" || *part == "open" || *part == "8021x" {
-              security = part.to_string();
-              break;
-            }
-            ssid_parts.push(*part);
-          }
+  let username_entry = gtk::Entry::new();
+  username_entry.set_placeholder_text(Some("Username"));
+  username_entry.set_hexpand(true);
+  content.append(&username_entry);
 
-          if ssid_parts.is_empty() {
-            return None;
-          }
+  let password_entry = gtk::PasswordEntry::new();
+  password_entry.set_show_peek_icon(true);
+  password_entry.set_hexpand(true);
+  content.append(&password_entry);
 
-          let ssid = ssid_parts.join(" ");
-          // Fallback: use asterisk counting
-          let bar_count = count_bright_asterisks(line);
-          let signal = ((bar_count * 25).min(100)) as u8;
+  let button_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+  button_box.set_halign(gtk::Align::End);
+  button_box.set_margin_top(8);
 
-          Some(WifiInfo { ssid, signal, security, connected })
-        })
-        .collect();
+  let cancel_button = gtk::Button::with_label("Cancel");
+  let connect_button = gtk::Button::with_label("Connect");
+  connect_button.add_css_class("suggested-action");
 
-      return networks;
-    }
-  }
+  button_box.append(&cancel_button);
+  button_box.append(&connect_button);
+  content.append(&button_box);
 
-  Vec::new()
-}
+  dialog.set_child(Some(&content));
 
-fn strip_ansi_codes(s: &str) -> String {
-  let mut result = String::new();
-  let mut in_escape = false;
+  // Connect signals
+  let dialog_weak = dialog.downgrade();
+  cancel_button.connect_clicked(move |_| {
+    if let Some(d) = dialog_weak.upgrade() {
+      d.close();
+    }
+  });
 
-  for c in s.chars() {
-    if c == '\x1b' {
-      in_escape = true;
-    } else if in_escape {
-      if c == 'm' {
-        in_escape = false;
+  let dialog_weak = dialog.downgrade();
+  let username_clone = username_entry.clone();
+  let password_clone = password_entry.clone();
+  let submit = move || {
+    let username = username_clone.text().to_string();
+    let password = password_clone.text().to_string();
+    if !username.is_empty() && !password.is_empty() {
+      NetworkService::connect_to_enterprise_wifi(&ssid, &username, &password);
+      if let Some(d) = dialog_weak.upgrade() {
+        d.close();
       }
-    } else {
-      result.push(c);
     }
-  }
+  };
+
+  let submit_clone = submit.clone();
+  connect_button.connect_clicked(move |_| submit_clone());
 
-  result
+  // Allow Enter key to submit
+  password_entry.connect_activate(move |_| submit());
+
+  dialog.present();
 }
 
-/// Count bright (non-dimmed) asterisks in iwctl signal output.
-/// iwctl shows signal as `****` where dimmed asterisks use `\x1b[1;90m`
-/// Example: `*\x1b[1;90m***\x1b[0m` = 1 bright + 3 dimmed = 1 bar
-fn count_bright_asterisks(s: &str) -> usize {
-  let mut count = 0;
-  let mut in_escape = false;
-  let mut is_dimmed = false;
+/// Show a dialog for the hotspot SSID and passphrase, prefilled with the saved values
+fn show_hotspot_dialog<F>(ssid: &str, passphrase: &str, on_confirm: F)
+where
+  F: Fn(String, String) + 'static
+{
+  let dialog = gtk::Window::builder()
+    .title("Enable Hotspot")
+    .modal(true)
+    .default_width(350)
+    .default_height(180)
+    .resizable(false)
+    .build();
+
+  let content = gtk::Box::new(gtk::Orientation::Vertical, 12);
+  content.set_margin_top(20);
+  content.set_margin_bottom(20);
+  content.set_margin_start(20);
+  content.set_margin_end(20);
+
+  let ssid_label = gtk::Label::new(Some("Hotspot name (SSID):"));
+  ssid_label.set_halign(gtk::Align::Start);
+  content.append(&ssid_label);
 
-  let chars: Vec<char> = s.chars().collect();
-  let mut i = 0;
+  let ssid_entry = gtk::Entry::new();
+  ssid_entry.set_text(ssid);
+  ssid_entry.set_hexpand(true);
+  content.append(&ssid_entry);
 
-  while i < chars.len() {
-    let c = chars[i];
+  let password_label = gtk::Label::new(Some("Passphrase (8-63 characters):"));
+  password_label.set_halign(gtk::Align::Start);
+  content.append(&password_label);
 
-    if c == '\x1b' {
-      in_escape = true;
-      // Check if this is the dim sequence [1;90m
-      let rest: String = chars[i..].iter().collect();
-      if rest.starts_with("\x1b[1;90m") {
-        is_dimmed = true;
-      } else if rest.starts_with("\x1b[0m") {
-        is_dimmed = false;
-      }
-    } else if in_escape {
-      if c == 'm' {
-        in_escape = false;
-      }
-    } else if c == '*' && !is_dimmed {
-      count += 1;
+  let password_entry = gtk::PasswordEntry::new();
+  password_entry.set_text(passphrase);
+  password_entry.set_show_peek_icon(true);
+  password_entry.set_hexpand(true);
+  content.append(&password_entry);
+
+  let button_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+  button_box.set_halign(gtk::Align::End);
+  button_box.set_margin_top(8);
+
+  let cancel_button = gtk::Button::with_label("Cancel");
+  let start_button = gtk::Button::with_label("Start Hotspot");
+  start_button.add_css_class("suggested-action");
+
+  button_box.append(&cancel_button);
+  button_box.append(&start_button);
+  content.append(&button_box);
+
+  dialog.set_child(Some(&content));
+
+  // Connect signals
+  let dialog_weak = dialog.downgrade();
+  cancel_button.connect_clicked(move |_| {
+    if let Some(d) = dialog_weak.upgrade() {
+      d.close();
     }
+  });
 
-    i += 1;
-  }
+  let dialog_weak = dialog.downgrade();
+  let ssid_clone = ssid_entry.clone();
+  let password_clone = password_entry.clone();
+  let on_confirm = std::rc::Rc::new(on_confirm);
+  let submit = move || {
+    let ssid = ssid_clone.text().to_string();
+    let passphrase = password_clone.text().to_string();
+
+    // WPA2-PSK passphrases must be 8 to 63 characters long
+    if ssid.is_empty() || !(8..=63).contains(&passphrase.len()) {
+      password_clone.add_css_class("error");
+      return;
+    }
+
+    on_confirm(ssid, passphrase);
+    if let Some(d) = dialog_weak.upgrade() {
+      d.close();
+    }
+  };
+
+  let submit_clone = submit.clone();
+  start_button.connect_clicked(move |_| submit_clone());
 
-  count
+  // Allow Enter key to submit
+  password_entry.connect_activate(move |_| submit());
+
+  dialog.present();
+}
+
+/// Connect to a WiFi network via D-Bus by finding the network and calling Connect.
+/// `credentials` answer iwd's agent requests; pass None for open or known networks.
+fn connect_to_wifi_dbus(ssid: &str, credentials: Option<AgentCredentials>) -> NetworkResult<()> {
+  let connection = system_bus()?;
+  let objects = get_iwd_managed_objects(&connection)?;
+  let (path, _props) = find_network(&objects, ssid)?;
+
+  // Connect from the agent's connection so iwd asks our agent for any secrets
+  iwd_agent::with_credentials(credentials, |agent_connection| {
+    let network_proxy = iwd_proxy(agent_connection, path, "net.connman.iwd.Network")?;
+    network_proxy.call::<_, _, ()>("Connect", &())?;
+    Ok(())
+  })?
+}
+
+/// Connect to a network that doesn't broadcast its SSID
+fn connect_to_hidden_wifi_dbus(ssid: &str, credentials: Option<AgentCredentials>) -> NetworkResult<()> {
+  let connection = system_bus()?;
+  let objects = get_iwd_managed_objects(&connection)?;
+  let station_path = find_iwd_object_path(&objects, "net.connman.iwd.Station")
+    .ok_or(NetworkError::NoStation)?;
+
+  iwd_agent::with_credentials(credentials, |agent_connection| {
+    let station_proxy = iwd_proxy(agent_connection, &station_path, "net.connman.iwd.Station")?;
+    station_proxy.call::<_, _, ()>("ConnectHiddenNetwork", &(ssid,))?;
+    Ok(())
+  })?
 }
 
-async fn monitor_dbus_signals() -> ZbusResult<()> {
-  let connection = Connection::system().await?;
+fn disconnect_wifi_dbus() -> NetworkResult<()> {
+  let connection = system_bus()?;
+  let objects = get_iwd_managed_objects(&connection)?;
+  let station_path = find_iwd_object_path(&objects, "net.connman.iwd.Station")
+    .ok_or(NetworkError::NoStation)?;
 
+  let proxy = iwd_proxy(&connection, &station_path, "net.connman.iwd.Station")?;
+  proxy.call::<_, _, ()>("Disconnect", &())?;
+  Ok(())
+}
+
+/// Follow iwd and networkd property changes on `connection`, which is the async side of
+/// `system_bus`
+async fn monitor_dbus_signals(connection: &Connection) -> ZbusResult<()> {
   use zbus::MatchRule;
   use futures::future::select;
   use futures::pin_mut;
@@ -1067,7 +1540,7 @@ async fn monitor_dbus_signals() -> ZbusResult<()> {
   // Monitor iwd PropertiesChanged signals
   let iwd_rule = MatchRule::builder()
     .msg_type(zbus::message::Type::Signal)
-    .sender("net.connman.iwd")?
+    .sender(IWD_SERVICE)?
     .interface("org.freedesktop.DBus.Properties")?
     .member("PropertiesChanged")?
     .build();
@@ -1075,20 +1548,20 @@ async fn monitor_dbus_signals() -> ZbusResult<()> {
   // Monitor systemd-networkd PropertiesChanged signals
   let networkd_rule = MatchRule::builder()
     .msg_type(zbus::message::Type::Signal)
-    .sender("org.freedesktop.network1")?
+    .sender(NETWORKD_SERVICE)?
     .interface("org.freedesktop.DBus.Properties")?
     .member("PropertiesChanged")?
     .build();
 
   let mut iwd_stream = zbus::MessageStream::for_match_rule(
     iwd_rule,
-    &connection,
+    connection,
     None,
   ).await?;
 
   let mut networkd_stream = zbus::MessageStream::for_match_rule(
     networkd_rule,
-    &connection,
+    connection,
     None,
   ).await?;
 
@@ -1105,7 +1578,10 @@ async fn monitor_dbus_signals() -> ZbusResult<()> {
             HashMap<String, OwnedValue>,
             Vec<String>,
           )>() {
-            handle_iwd_property_change(&interface, &changed_props);
+            // The handler queries iwd with blocking calls
+            let _ = tokio::task::spawn_blocking(move || {
+              handle_iwd_property_change(&interface, &changed_props);
+            }).await;
           }
         }
       }
@@ -1116,7 +1592,9 @@ async fn monitor_dbus_signals() -> ZbusResult<()> {
             HashMap<String, OwnedValue>,
             Vec<String>,
           )>() {
-            handle_networkd_property_change(&interface, &changed_props);
+            let _ = tokio::task::spawn_blocking(move || {
+              handle_networkd_property_change(&interface, &changed_props);
+            }).await;
           }
         }
       }
@@ -1156,8 +1634,8 @@ fn handle_iwd_property_change(
             }
             "connected" => {
               // When connected, query iwd directly for the connection info.
-              // Avoid get_primary_connection() here because it relies on `ip route show default`
-              // which may not be updated yet (DHCP/networkd runs after iwd connects).
+              // Avoid get_primary_connection() here because it waits for networkd to report the
+              // link routable, which happens later (DHCP runs after iwd connects).
               let (conn_type, conn_name, signal) = get_wlan_connection();
               NetworkService::update_connection_state(
                 Some(true),  // networking must be enabled if we're connected
@@ -1176,7 +1654,7 @@ fn handle_iwd_property_change(
 
       // ConnectedNetwork property changed
       if changed_props.contains_key("ConnectedNetwork") {
-        // Fetch updated connection info directly from iwd (not ip route)
+        // Fetch updated connection info directly from iwd rather than networkd
         let (conn_type, conn_name, signal) = get_wlan_connection();
         NetworkService::update_connection_state(
           None,
@@ -1193,7 +1671,7 @@ fn handle_iwd_property_change(
       if let Some(connected_value) = changed_props.get("Connected") {
         if let Ok(connected) = TryInto::<bool>::try_into(connected_value.clone()) {
           if connected {
-            // This network became connected - query iwd directly (not ip route)
+            // This network became connected - query iwd directly rather than networkd
             let (conn_type, conn_name, signal) = get_wlan_connection();
             NetworkService::update_connection_state(
               Some(true),  // networking must be enabled if connected
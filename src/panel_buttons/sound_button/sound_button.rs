@@ -115,7 +115,12 @@ impl SoundButton {
   fn play_feedback_sound() {
     // Play a system sound for volume feedback
     // Using the freedesktop sound theme's audio-volume-change sound
-    process::spawn_detached("paplay /usr/share/sounds/freedesktop/stereo/audio-volume-change.oga");
+    Self::play_sound("audio-volume-change");
+  }
+
+  /// Play a sound from the freedesktop sound theme, e.g. `alarm-clock-elapsed`
+  pub fn play_sound(name: &str) {
+    process::spawn_detached(&format!("paplay /usr/share/sounds/freedesktop/stereo/{}.oga", name));
   }
 }
 
@@ -1,11 +1,11 @@
 use gtk::{
     prelude::*,
-    Align, Box as GtkBox, Button, Entry, Image, Label, Orientation,
+    Align, Box as GtkBox, Button, Entry, Expander, Grid, Image, Label, Orientation, PolicyType, ScrolledWindow,
 };
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::cell::RefCell;
-use super::weather_service::WeatherData;
+use super::weather_service::{ForecastPeriod, WeatherAlert, WeatherData};
 
 #[derive(Clone)]
 pub struct ForecastWidget {
@@ -144,28 +144,32 @@ impl ForecastWidget {
             self.weather_box.remove(&child);
         }
 
+        // Alerts come first, so they aren't missed
+        for alert in &weather.alerts {
+            self.weather_box.append(&Self::create_alert(alert));
+        }
+
         // Add current conditions
         let current_box = self.create_forecast_row(
             "",
             weather.temperature as i32,
-            "F",
+            &weather.temperature_unit,
             &weather.short_forecast,
             &weather.icon,
             true,
         );
         self.weather_box.append(&current_box);
+        self.weather_box.append(&Self::create_details(weather));
 
-        // Add separator
-        let separator = gtk::Separator::new(Orientation::Horizontal);
-        separator.set_margin_top(8);
-        separator.set_margin_bottom(8);
-        self.weather_box.append(&separator);
+        // Add hourly forecast
+        if !weather.hourly_forecast.is_empty() {
+            self.append_separator();
+            self.append_header("Hourly");
+            self.weather_box.append(&Self::create_hourly_strip(weather));
+        }
 
-        // Add forecast header
-        let forecast_header = Label::new(Some("Forecast"));
-        forecast_header.add_css_class("heading");
-        forecast_header.set_halign(Align::Start);
-        self.weather_box.append(&forecast_header);
+        self.append_separator();
+        self.append_header("Forecast");
 
         // Add detailed forecast periods
         for period in &weather.detailed_forecast {
@@ -173,7 +177,7 @@ impl ForecastWidget {
                 &period.name,
                 period.temperature,
                 &period.temperature_unit,
-                &period.short_forecast,
+                &Self::period_forecast(period),
                 &period.icon_name,
                 false,
             );
@@ -181,22 +185,149 @@ impl ForecastWidget {
         }
     }
 
-    fn create_forecast_row(
-        &self,
-        name: &str,
-        temperature: i32,
-        temp_unit: &str,
-        forecast: &str,
-        icon_name: &str,
-        is_current: bool,
-    ) -> GtkBox {
-        let row = GtkBox::new(Orientation::Horizontal, 12);
-        row.set_margin_top(4);
-        row.set_margin_bottom(4);
+    fn append_separator(&self) {
+        let separator = gtk::Separator::new(Orientation::Horizontal);
+        separator.set_margin_top(8);
+        separator.set_margin_bottom(8);
+        self.weather_box.append(&separator);
+    }
 
-        // Icon
+    fn append_header(&self, text: &str) {
+        let header = Label::new(Some(text));
+        header.add_css_class("heading");
+        header.set_halign(Align::Start);
+        self.weather_box.append(&header);
+    }
+
+    /// An alert's event and headline, with the full text folded away
+    fn create_alert(alert: &WeatherAlert) -> GtkBox {
+        let container = GtkBox::new(Orientation::Vertical, 4);
+        container.add_css_class("weather-alert");
+        if alert.is_severe() {
+            container.add_css_class("severe");
+        }
+
+        let title_row = GtkBox::new(Orientation::Horizontal, 8);
+        title_row.append(&Image::from_icon_name("dialog-warning-symbolic"));
+        let event_label = Label::new(Some(&alert.event));
+        event_label.add_css_class("heading");
+        event_label.set_halign(Align::Start);
+        event_label.set_hexpand(true);
+        title_row.append(&event_label);
+        if let Some(ends) = &alert.ends {
+            let ends_label = Label::new(Some(&format!("Until {}", ends)));
+            ends_label.add_css_class("caption");
+            title_row.append(&ends_label);
+        }
+        container.append(&title_row);
+
+        let headline = Label::new(Some(&alert.headline));
+        headline.add_css_class("caption");
+        headline.set_halign(Align::Start);
+        headline.set_xalign(0.0);
+        headline.set_wrap(true);
+        headline.set_max_width_chars(40);
+        container.append(&headline);
+
+        let details = [Some(&alert.description), alert.instruction.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|text| text.trim())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if !details.is_empty() {
+            let details_label = Label::new(Some(&details));
+            details_label.add_css_class("caption");
+            details_label.set_xalign(0.0);
+            details_label.set_wrap(true);
+            details_label.set_max_width_chars(40);
+            details_label.set_selectable(true);
+            let expander = Expander::builder().label("Details").child(&details_label).build();
+            container.append(&expander);
+        }
+
+        container
+    }
+
+    fn period_forecast(period: &ForecastPeriod) -> String {
+        match period.precipitation_probability {
+            Some(chance) if chance > 0 => format!("{} · {}% precipitation", period.short_forecast, chance),
+            _ => period.short_forecast.clone(),
+        }
+    }
+
+    /// Two columns of name and value for whatever the provider gave
+    fn create_details(weather: &WeatherData) -> Grid {
+        let wind = match &weather.wind_direction {
+            Some(direction) => format!("{} {} {}", direction, weather.wind_speed.round() as i32, weather.wind_speed_unit),
+            None => format!("{} {}", weather.wind_speed.round() as i32, weather.wind_speed_unit),
+        };
+        let details = [
+            ("Feels like", weather.feels_like.map(|t| format!("{}°{}", t as i32, weather.temperature_unit))),
+            ("Wind", Some(wind)),
+            ("Humidity", weather.humidity.map(|h| format!("{}%", h))),
+            ("Precipitation", weather.precipitation_probability.map(|p| format!("{}%", p))),
+            ("UV index", weather.uv_index.map(|uv| format!("{:.0}", uv))),
+            ("Sunrise", weather.sunrise.clone()),
+            ("Sunset", weather.sunset.clone()),
+        ];
+
+        let grid = Grid::builder().column_spacing(12).row_spacing(4).build();
+        for (i, (name, value)) in details.into_iter().filter_map(|(name, value)| Some((name, value?))).enumerate() {
+            let column = (i % 2) as i32 * 2;
+            let row = (i / 2) as i32;
+
+            let name_label = Label::new(Some(name));
+            name_label.add_css_class("caption");
+            name_label.add_css_class("dim-label");
+            name_label.set_halign(Align::Start);
+            grid.attach(&name_label, column, row, 1, 1);
+
+            let value_label = Label::new(Some(&value));
+            value_label.add_css_class("caption");
+            value_label.set_halign(Align::Start);
+            value_label.set_hexpand(true);
+            grid.attach(&value_label, column + 1, row, 1, 1);
+        }
+        grid
+    }
+
+    /// One column per hour, scrolling sideways
+    fn create_hourly_strip(weather: &WeatherData) -> ScrolledWindow {
+        let strip = GtkBox::new(Orientation::Horizontal, 12);
+        for hour in &weather.hourly_forecast {
+            let column = GtkBox::new(Orientation::Vertical, 2);
+
+            let time_label = Label::new(Some(&hour.time));
+            time_label.add_css_class("caption");
+            column.append(&time_label);
+
+            column.append(&Self::create_icon(&hour.icon_name, 24));
+
+            let temp_label = Label::new(Some(&format!("{}°", hour.temperature)));
+            column.append(&temp_label);
+
+            let chance = hour.precipitation_probability.filter(|chance| *chance > 0);
+            let chance_label = Label::new(chance.map(|chance| format!("{}%", chance)).as_deref());
+            chance_label.add_css_class("caption");
+            chance_label.add_css_class("dim-label");
+            column.append(&chance_label);
+
+            strip.append(&column);
+        }
+
+        ScrolledWindow::builder()
+            .hscrollbar_policy(PolicyType::Automatic)
+            .vscrollbar_policy(PolicyType::Never)
+            .propagate_natural_height(true)
+            .child(&strip)
+            .build()
+    }
+
+    fn create_icon(icon_name: &str, pixel_size: i32) -> Image {
         let icon = Image::builder()
-            .pixel_size(32)
+            .pixel_size(pixel_size)
             .halign(Align::Start)
             .build();
 
@@ -209,7 +340,24 @@ impl ForecastWidget {
             icon.set_from_file(Some(&Self::get_icon_path("partly-cloudy")));
         }
 
-        row.append(&icon);
+        icon
+    }
+
+    fn create_forecast_row(
+        &self,
+        name: &str,
+        temperature: i32,
+        temp_unit: &str,
+        forecast: &str,
+        icon_name: &str,
+        is_current: bool,
+    ) -> GtkBox {
+        let row = GtkBox::new(Orientation::Horizontal, 12);
+        row.set_margin_top(4);
+        row.set_margin_bottom(4);
+
+        // Icon
+        row.append(&Self::create_icon(icon_name, 32));
 
         // Content box (period name, temp, forecast)
         let content_box = GtkBox::new(Orientation::Vertical, 4);
@@ -0,0 +1,135 @@
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::config::GeocoderKind;
+
+/// A geocoded location
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Place {
+    pub lat: f64,
+    pub lon: f64,
+    /// Short name for display, e.g. "Springfield, IL"
+    pub name: String,
+}
+
+/// Turns the free-form location from the config into coordinates
+pub trait Geocoder: Send + Sync {
+    fn geocode(&self, client: &Client, location: &str) -> Result<Place, String>;
+}
+
+pub fn geocoder(kind: GeocoderKind) -> Box<dyn Geocoder> {
+    match kind {
+        GeocoderKind::Nominatim => Box::new(Nominatim),
+        GeocoderKind::OpenMeteo => Box::new(OpenMeteoGeocoder),
+    }
+}
+
+fn join_name(place: &str, region: &str) -> String {
+    [place, region]
+        .iter()
+        .filter(|s| !s.is_empty())
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Deserialize)]
+struct NominatimResult {
+    lat: String,
+    lon: String,
+    address: NominatimAddress,
+}
+
+#[derive(Debug, Deserialize)]
+struct NominatimAddress {
+    city: Option<String>,
+    town: Option<String>,
+    village: Option<String>,
+    hamlet: Option<String>,
+    county: Option<String>,
+    state: Option<String>,
+    #[serde(rename = "ISO3166-2-lvl4")]
+    state_code: Option<String>,
+    country: Option<String>,
+}
+
+pub struct Nominatim;
+
+impl Geocoder for Nominatim {
+    fn geocode(&self, client: &Client, location: &str) -> Result<Place, String> {
+        let results: Vec<NominatimResult> = client
+            .get("https://nominatim.openstreetmap.org/search")
+            .query(&[("q", location), ("format", "json"), ("limit", "1"), ("addressdetails", "1")])
+            .send()
+            .map_err(|e| e.to_string())?
+            .json()
+            .map_err(|e| e.to_string())?;
+        let first = results.into_iter().next().ok_or("no results")?;
+        let lat = first.lat.parse::<f64>().map_err(|e| e.to_string())?;
+        let lon = first.lon.parse::<f64>().map_err(|e| e.to_string())?;
+        let place = first.address.city
+            .or(first.address.town)
+            .or(first.address.village)
+            .or(first.address.hamlet)
+            .or(first.address.county)
+            .unwrap_or_default();
+        let state_abbr = first.address.state_code
+            .as_deref()
+            .and_then(|s| s.split('-').next_back())
+            .map(|s| s.to_string())
+            .or(first.address.state)
+            .or(first.address.country)
+            .unwrap_or_default();
+        Ok(Place { lat, lon, name: join_name(&place, &state_abbr) })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenMeteoSearch {
+    #[serde(default)]
+    results: Vec<OpenMeteoPlace>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenMeteoPlace {
+    latitude: f64,
+    longitude: f64,
+    name: String,
+    admin1: Option<String>,
+    country: Option<String>,
+    country_code: Option<String>,
+}
+
+pub struct OpenMeteoGeocoder;
+
+impl Geocoder for OpenMeteoGeocoder {
+    fn geocode(&self, client: &Client, location: &str) -> Result<Place, String> {
+        // The search only matches place names, so "Springfield, Illinois" is looked up as
+        // "Springfield" and the rest picks between the results
+        let (name, qualifier) = match location.split_once(',') {
+            Some((name, qualifier)) => (name.trim(), qualifier.trim().to_lowercase()),
+            None => (location.trim(), String::new()),
+        };
+        let search: OpenMeteoSearch = client
+            .get("https://geocoding-api.open-meteo.com/v1/search")
+            .query(&[("name", name), ("count", "10"), ("format", "json")])
+            .send()
+            .map_err(|e| e.to_string())?
+            .json()
+            .map_err(|e| e.to_string())?;
+        let matches_qualifier = |place: &OpenMeteoPlace| {
+            [&place.admin1, &place.country, &place.country_code]
+                .into_iter()
+                .flatten()
+                .any(|region| region.to_lowercase() == qualifier)
+        };
+        let index = search.results.iter().position(matches_qualifier).unwrap_or(0);
+        let first = search.results.into_iter().nth(index).ok_or("no results")?;
+        let region = first.admin1.or(first.country_code).unwrap_or_default();
+        Ok(Place {
+            lat: first.latitude,
+            lon: first.longitude,
+            name: join_name(&first.name, &region),
+        })
+    }
+}
@@ -0,0 +1,373 @@
+use chrono::{DateTime, Local, NaiveDate, Timelike};
+use indexmap::IndexMap;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use super::geocoder::Place;
+use super::weather_provider::{
+    apparent_temperature, clock_time, compass_point, day_and_time, day_name, day_summary, hour_label, Units,
+    WeatherProvider,
+};
+use super::weather_service::{ForecastPeriod, HourlyPeriod, WeatherAlert, WeatherData};
+
+const HOURLY_PERIODS: usize = 24;
+
+#[derive(Debug, Deserialize)]
+struct LocationForecast {
+    properties: Properties,
+}
+
+#[derive(Debug, Deserialize)]
+struct Properties {
+    timeseries: Vec<TimeStep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimeStep {
+    /// RFC 3339, in UTC
+    time: String,
+    data: TimeStepData,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimeStepData {
+    instant: Instant,
+    next_1_hours: Option<Period>,
+    next_6_hours: Option<Period>,
+    next_12_hours: Option<Period>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Instant {
+    details: InstantDetails,
+}
+
+#[derive(Debug, Deserialize)]
+struct InstantDetails {
+    /// Celsius
+    air_temperature: f64,
+    /// Meters per second
+    wind_speed: f64,
+    /// Degrees
+    wind_from_direction: Option<f64>,
+    /// Percent
+    relative_humidity: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Period {
+    summary: Summary,
+}
+
+#[derive(Debug, Deserialize)]
+struct Summary {
+    symbol_code: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SunResponse {
+    properties: SunProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct SunProperties {
+    sunrise: Option<SunEvent>,
+    sunset: Option<SunEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SunEvent {
+    /// RFC 3339, e.g. "2025-01-31T08:12+01:00"
+    time: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetAlerts {
+    features: Vec<MetAlertFeature>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetAlertFeature {
+    properties: MetAlertProperties,
+    when: Option<MetAlertWhen>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetAlertProperties {
+    id: String,
+    /// e.g. "Gale warning"
+    event_awareness_name: Option<String>,
+    /// A code, e.g. "gale"
+    event: String,
+    title: Option<String>,
+    #[serde(default)]
+    severity: String,
+    #[serde(default)]
+    description: String,
+    instruction: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetAlertWhen {
+    /// RFC 3339 start and end
+    interval: Vec<String>,
+}
+
+impl TimeStep {
+    fn local_time(&self) -> Option<DateTime<Local>> {
+        DateTime::parse_from_rfc3339(&self.time).ok().map(|time| time.with_timezone(&Local))
+    }
+}
+
+impl TimeStepData {
+    /// The symbol for the shortest period that has one
+    fn symbol_code(&self) -> Option<&str> {
+        [&self.next_1_hours, &self.next_6_hours, &self.next_12_hours]
+            .into_iter()
+            .flatten()
+            .map(|period| period.summary.symbol_code.as_str())
+            .next()
+    }
+}
+
+/// The Norwegian Meteorological Institute's locationforecast API. It always answers in Celsius
+/// and meters per second, in UTC.
+pub struct MetNorway;
+
+impl MetNorway {
+    /// Today's sunrise and sunset from the separate sunrise API. Not worth failing the whole
+    /// forecast over, so errors just leave them out.
+    fn fetch_sun(client: &Client, place: &Place) -> (Option<String>, Option<String>) {
+        let now = Local::now();
+        let response = client
+            .get("https://api.met.no/weatherapi/sunrise/3.0/sun")
+            .query(&[
+                ("lat", format!("{:.4}", place.lat)),
+                ("lon", format!("{:.4}", place.lon)),
+                ("date", now.format("%Y-%m-%d").to_string()),
+                ("offset", now.format("%:z").to_string()),
+            ])
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json::<SunResponse>());
+
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                eprintln!("waltopanel: failed to fetch sunrise and sunset: {}", e);
+                return (None, None);
+            }
+        };
+
+        // Times are rounded to the minute and have no seconds, which RFC 3339 requires
+        let format = |event: Option<SunEvent>| {
+            let time = event?.time;
+            DateTime::parse_from_str(&time, "%Y-%m-%dT%H:%M%:z")
+                .or_else(|_| DateTime::parse_from_rfc3339(&time))
+                .ok()
+                .map(|time| clock_time(time.with_timezone(&Local)))
+        };
+        (format(response.properties.sunrise), format(response.properties.sunset))
+    }
+}
+
+impl WeatherProvider for MetNorway {
+    /// MetAlerts, which covers Norway and its waters
+    fn fetch_alerts(&self, client: &Client, place: &Place) -> Result<Vec<WeatherAlert>, String> {
+        let response = client
+            .get("https://api.met.no/weatherapi/metalerts/2.0/current.json")
+            .query(&[
+                ("lat", format!("{:.4}", place.lat)),
+                ("lon", format!("{:.4}", place.lon)),
+                ("lang", "en".to_string()),
+            ])
+            .send()
+            .map_err(|e| e.to_string())?
+            .error_for_status()
+            .map_err(|e| e.to_string())?
+            .json::<MetAlerts>()
+            .map_err(|e| e.to_string())?;
+
+        Ok(response
+            .features
+            .into_iter()
+            .map(|feature| {
+                let alert = feature.properties;
+                let event = alert.event_awareness_name.unwrap_or(alert.event);
+                let ends = feature.when.and_then(|when| when.interval.last().cloned());
+                WeatherAlert {
+                    id: alert.id,
+                    headline: alert.title.unwrap_or_else(|| event.clone()),
+                    event,
+                    severity: alert.severity,
+                    description: alert.description,
+                    instruction: alert.instruction,
+                    ends: ends
+                        .and_then(|ends| DateTime::parse_from_rfc3339(&ends).ok())
+                        .map(day_and_time),
+                }
+            })
+            .collect())
+    }
+
+    fn fetch(&self, client: &Client, place: &Place, units: Units) -> Result<WeatherData, String> {
+        // The API's terms ask for coordinates rounded to four decimals, which helps its caching
+        let response = client
+            .get("https://api.met.no/weatherapi/locationforecast/2.0/compact")
+            .query(&[("lat", format!("{:.4}", place.lat)), ("lon", format!("{:.4}", place.lon))])
+            .send()
+            .map_err(|e| e.to_string())?
+            .error_for_status()
+            .map_err(|e| e.to_string())?
+            .json::<LocationForecast>()
+            .map_err(|e| e.to_string())?;
+
+        let timeseries = response.properties.timeseries;
+        let current = timeseries.first().ok_or("empty forecast")?;
+        let current_symbol = current.data.symbol_code().unwrap_or("cloudy");
+        let condition = symbol_to_description(current_symbol);
+        let details = &current.data.instant.details;
+
+        // Steps are hourly for the first couple of days, then six-hourly
+        let hourly_forecast: Vec<HourlyPeriod> = timeseries
+            .iter()
+            .take(HOURLY_PERIODS)
+            .filter_map(|step| {
+                Some(HourlyPeriod {
+                    time: hour_label(step.local_time()?),
+                    temperature: units.temperature.convert_celsius(step.data.instant.details.air_temperature) as i32,
+                    icon_name: symbol_to_icon(step.data.symbol_code()?),
+                    precipitation_probability: None,
+                })
+            })
+            .collect();
+
+        let (sunrise, sunset) = Self::fetch_sun(client, place);
+
+        // Days are split on local time, which is the location's own when it's nearby
+        let mut days: IndexMap<NaiveDate, Vec<&TimeStep>> = IndexMap::new();
+        for step in &timeseries {
+            if let Some(time) = step.local_time() {
+                days.entry(time.date_naive()).or_default().push(step);
+            }
+        }
+
+        let detailed_forecast: Vec<ForecastPeriod> = days
+            .iter()
+            .take(7)
+            .enumerate()
+            .map(|(i, (date, steps))| {
+                let temperatures = steps.iter().map(|step| step.data.instant.details.air_temperature);
+                let high = temperatures.clone().fold(f64::MIN, f64::max);
+                let low = temperatures.fold(f64::MAX, f64::min);
+
+                // The day's weather is the symbol for the step closest to midday
+                let symbol = steps
+                    .iter()
+                    .filter(|step| step.data.symbol_code().is_some())
+                    .min_by_key(|step| step.local_time().map_or(24, |time| (time.hour() as i32 - 12).abs()))
+                    .and_then(|step| step.data.symbol_code())
+                    .unwrap_or("cloudy");
+
+                ForecastPeriod {
+                    name: day_name(i, *date),
+                    temperature: units.temperature.convert_celsius(high) as i32,
+                    temperature_unit: units.temperature.symbol().to_string(),
+                    short_forecast: day_summary(
+                        &symbol_to_description(symbol),
+                        units.temperature.convert_celsius(low) as i32,
+                        units,
+                    ),
+                    // Each day is summed up by its daytime weather
+                    icon_name: symbol_to_icon(symbol.replace("_night", "_day").as_str()),
+                    precipitation_probability: None,
+                }
+            })
+            .collect();
+
+        Ok(WeatherData {
+            temperature: units.temperature.convert_celsius(details.air_temperature),
+            temperature_unit: units.temperature.symbol().to_string(),
+            wind_speed: units.wind_speed.convert_meters_per_second(details.wind_speed),
+            wind_speed_unit: units.wind_speed.label().to_string(),
+            wind_direction: details.wind_from_direction.map(compass_point),
+            feels_like: details.relative_humidity.map(|humidity| {
+                units.temperature.convert_celsius(apparent_temperature(details.air_temperature, humidity, details.wind_speed))
+            }),
+            humidity: details.relative_humidity.map(|humidity| humidity.round() as u8),
+            precipitation_probability: None,
+            uv_index: None,
+            sunrise,
+            sunset,
+            condition: condition.clone(),
+            icon: symbol_to_icon(current_symbol),
+            short_forecast: condition,
+            hourly_forecast,
+            detailed_forecast,
+            location_name: place.name.clone(),
+            fetched_at: Local::now().timestamp(),
+            // Filled in by the service from `fetch_alerts`
+            alerts: Vec::new(),
+        })
+    }
+}
+
+/// Split a symbol code like `lightrainshowersandthunder_day` into its weather and whether it's
+/// for the night
+fn parse_symbol(code: &str) -> (&str, bool) {
+    match code.split_once('_') {
+        Some((weather, variant)) => (weather, variant == "night"),
+        None => (code, false),
+    }
+}
+
+fn symbol_to_description(code: &str) -> String {
+    let (weather, _) = parse_symbol(code);
+    match weather {
+        "clearsky" => return "Clear sky".to_string(),
+        "fair" => return "Fair".to_string(),
+        "partlycloudy" => return "Partly cloudy".to_string(),
+        "cloudy" => return "Cloudy".to_string(),
+        "fog" => return "Fog".to_string(),
+        _ => {}
+    }
+
+    // Everything else is [light|heavy]{rain|sleet|snow}[showers][andthunder]
+    let (intensity, rest) = if let Some(rest) = weather.strip_prefix("light") {
+        ("Light ", rest)
+    } else if let Some(rest) = weather.strip_prefix("heavy") {
+        ("Heavy ", rest)
+    } else {
+        ("", weather)
+    };
+    let (rest, thunder) = match rest.strip_suffix("andthunder") {
+        Some(rest) => (rest, " and thunder"),
+        None => (rest, ""),
+    };
+    let (precipitation, showers) = match rest.strip_suffix("showers") {
+        Some(precipitation) => (precipitation, " showers"),
+        None => (rest, ""),
+    };
+
+    let description = format!("{}{}{}{}", intensity, precipitation, showers, thunder);
+    let mut chars = description.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => "Unknown".to_string(),
+    }
+}
+
+fn symbol_to_icon(code: &str) -> String {
+    let (weather, night) = parse_symbol(code);
+    match weather {
+        "clearsky" => if night { "clear-night" } else { "clear" },
+        "fair" | "partlycloudy" => if night { "partly-cloudy-night" } else { "partly-cloudy" },
+        "cloudy" => "cloudy",
+        "fog" => "fog",
+        w if w.contains("thunder") => "storm",
+        w if w.contains("snow") || w.contains("sleet") => "snow",
+        w if w.contains("rain") => "rain",
+        _ => if night { "partly-cloudy-night" } else { "partly-cloudy" },
+    }.to_string()
+}
@@ -1,4 +1,9 @@
 mod weather_service;
+mod weather_provider;
+mod geocoder;
+mod open_meteo;
+mod met_norway;
+mod nws;
 mod forecast_widget;
 mod weather_button;
 
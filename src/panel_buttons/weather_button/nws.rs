@@ -0,0 +1,319 @@
+use chrono::{DateTime, Local};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use super::geocoder::Place;
+use super::weather_provider::{
+    apparent_temperature, day_and_time, day_name, day_summary, hour_label, Units, WeatherProvider,
+};
+use super::weather_service::{ForecastPeriod, HourlyPeriod, WeatherAlert, WeatherData};
+
+const MPH_PER_METER_PER_SECOND: f64 = 2.236_936;
+const HOURLY_PERIODS: usize = 24;
+
+/// Rough bounds of the US and its territories as (south, north, west, east). They take in
+/// bits of Canada and Mexico too, where the NWS just reports no alerts.
+const US_BOUNDS: [(f64, f64, f64, f64); 8] = [
+    (24.3, 49.4, -125.0, -66.9), // Contiguous states
+    (51.2, 71.5, -180.0, -129.9), // Alaska
+    (51.2, 53.1, 172.4, 180.0), // Western Aleutians
+    (18.9, 22.3, -160.3, -154.8), // Hawaii
+    (17.6, 18.6, -67.3, -64.5), // Puerto Rico and the Virgin Islands
+    (13.2, 20.6, 144.6, 146.1), // Guam and the Northern Marianas
+    (-14.6, -11.0, -171.1, -168.1), // American Samoa
+    (18.0, 28.5, -178.4, -161.0), // Northwestern Hawaiian Islands
+];
+
+#[derive(Debug, Deserialize)]
+struct PointResponse {
+    properties: PointProperties,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PointProperties {
+    forecast: String,
+    forecast_hourly: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForecastResponse {
+    properties: ForecastProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForecastProperties {
+    periods: Vec<NwsPeriod>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NwsPeriod {
+    /// RFC 3339, in the location's own time zone
+    start_time: String,
+    is_daytime: bool,
+    temperature: f64,
+    temperature_unit: String,
+    /// e.g. "10 mph" or "5 to 15 mph"
+    wind_speed: String,
+    /// Compass point, e.g. "NW"
+    wind_direction: Option<String>,
+    icon: String,
+    short_forecast: String,
+    /// Percent
+    probability_of_precipitation: Option<QuantitativeValue>,
+    /// Percent; only in the hourly forecast
+    relative_humidity: Option<QuantitativeValue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuantitativeValue {
+    value: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlertsResponse {
+    features: Vec<AlertFeature>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlertFeature {
+    properties: AlertProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlertProperties {
+    id: String,
+    event: String,
+    headline: Option<String>,
+    severity: String,
+    #[serde(default)]
+    description: String,
+    instruction: Option<String>,
+    /// RFC 3339; when the hazard ends, which can be later than the alert expires
+    ends: Option<String>,
+    expires: Option<String>,
+}
+
+impl NwsPeriod {
+    fn celsius(&self) -> f64 {
+        match self.temperature_unit.as_str() {
+            "F" => (self.temperature - 32.0) * 5.0 / 9.0,
+            _ => self.temperature,
+        }
+    }
+
+    fn temperature(&self, units: Units) -> f64 {
+        units.temperature.convert_celsius(self.celsius())
+    }
+
+    /// The top of the wind speed range, in meters per second
+    fn meters_per_second(&self) -> f64 {
+        let speed = self
+            .wind_speed
+            .split_whitespace()
+            .filter_map(|word| word.parse::<f64>().ok())
+            .next_back()
+            .unwrap_or(0.0);
+        match self.wind_speed.ends_with("km/h") {
+            true => speed / 3.6,
+            false => speed / MPH_PER_METER_PER_SECOND,
+        }
+    }
+
+    fn wind_speed(&self, units: Units) -> f64 {
+        units.wind_speed.convert_meters_per_second(self.meters_per_second())
+    }
+
+    fn precipitation_probability(&self) -> Option<u8> {
+        Some(self.probability_of_precipitation.as_ref()?.value?.round() as u8)
+    }
+
+    fn humidity(&self) -> Option<f64> {
+        self.relative_humidity.as_ref()?.value
+    }
+}
+
+/// The US National Weather Service. The location's forecast office and grid come from a
+/// points lookup, and there's nothing for places outside the US.
+pub struct Nws;
+
+impl Nws {
+    fn get<T: for<'de> Deserialize<'de>>(client: &Client, url: &str) -> Result<T, String> {
+        client
+            .get(url)
+            .header("Accept", "application/geo+json")
+            .send()
+            .map_err(|e| e.to_string())?
+            .error_for_status()
+            .map_err(|e| e.to_string())?
+            .json::<T>()
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl WeatherProvider for Nws {
+    fn fetch_alerts(&self, client: &Client, place: &Place) -> Result<Vec<WeatherAlert>, String> {
+        fetch_alerts(client, place)
+    }
+
+    fn fetch(&self, client: &Client, place: &Place, units: Units) -> Result<WeatherData, String> {
+        let point: PointResponse =
+            Self::get(client, &format!("https://api.weather.gov/points/{:.4},{:.4}", place.lat, place.lon))?;
+        let hourly: ForecastResponse = Self::get(client, &point.properties.forecast_hourly)?;
+        let daily: ForecastResponse = Self::get(client, &point.properties.forecast)?;
+
+        // The current hour stands in for current conditions, which would need a station lookup
+        let current = hourly.properties.periods.first().ok_or("empty forecast")?;
+
+        let hourly_forecast: Vec<HourlyPeriod> = hourly
+            .properties
+            .periods
+            .iter()
+            .take(HOURLY_PERIODS)
+            .map(|period| HourlyPeriod {
+                time: match DateTime::parse_from_rfc3339(&period.start_time) {
+                    Ok(start) => hour_label(start),
+                    Err(_) => period.start_time.clone(),
+                },
+                temperature: period.temperature(units) as i32,
+                icon_name: icon_url_to_icon(&period.icon),
+                precipitation_probability: period.precipitation_probability(),
+            })
+            .collect();
+
+        // Periods alternate day and night; each day takes its low from the night after it
+        let mut detailed_forecast = Vec::new();
+        let mut periods = daily.properties.periods.iter().peekable();
+        while let Some(period) = periods.next() {
+            if detailed_forecast.len() == 7 {
+                break;
+            }
+
+            if !period.is_daytime {
+                // Only the first period can be a night on its own, when it's already evening
+                detailed_forecast.push(ForecastPeriod {
+                    name: "Tonight".to_string(),
+                    temperature: period.temperature(units) as i32,
+                    temperature_unit: units.temperature.symbol().to_string(),
+                    short_forecast: period.short_forecast.clone(),
+                    icon_name: icon_url_to_icon(&period.icon),
+                    precipitation_probability: period.precipitation_probability(),
+                });
+                continue;
+            }
+
+            let name = match DateTime::parse_from_rfc3339(&period.start_time) {
+                Ok(start) => day_name(detailed_forecast.len(), start.date_naive()),
+                Err(_) => period.start_time.clone(),
+            };
+            let night = periods.next_if(|night| !night.is_daytime);
+            let short_forecast = match night {
+                Some(night) => day_summary(&period.short_forecast, night.temperature(units) as i32, units),
+                None => period.short_forecast.clone(),
+            };
+
+            // The day's chance of precipitation is the higher of its day and night halves
+            let precipitation_probability = [Some(period), night]
+                .into_iter()
+                .flatten()
+                .filter_map(NwsPeriod::precipitation_probability)
+                .max();
+
+            detailed_forecast.push(ForecastPeriod {
+                name,
+                temperature: period.temperature(units) as i32,
+                temperature_unit: units.temperature.symbol().to_string(),
+                short_forecast,
+                icon_name: icon_url_to_icon(&period.icon),
+                precipitation_probability,
+            });
+        }
+
+        Ok(WeatherData {
+            temperature: current.temperature(units),
+            temperature_unit: units.temperature.symbol().to_string(),
+            wind_speed: current.wind_speed(units),
+            wind_speed_unit: units.wind_speed.label().to_string(),
+            wind_direction: current.wind_direction.clone().filter(|direction| !direction.is_empty()),
+            feels_like: current.humidity().map(|humidity| {
+                units.temperature.convert_celsius(apparent_temperature(current.celsius(), humidity, current.meters_per_second()))
+            }),
+            humidity: current.humidity().map(|humidity| humidity.round() as u8),
+            precipitation_probability: current.precipitation_probability(),
+            // Neither is in the forecast API
+            uv_index: None,
+            sunrise: None,
+            sunset: None,
+            condition: current.short_forecast.clone(),
+            icon: icon_url_to_icon(&current.icon),
+            short_forecast: current.short_forecast.clone(),
+            hourly_forecast,
+            detailed_forecast,
+            location_name: place.name.clone(),
+            fetched_at: Local::now().timestamp(),
+            // Filled in by the service from `fetch_alerts`
+            alerts: Vec::new(),
+        })
+    }
+}
+
+/// Whether a place is in the US, the only place the NWS issues alerts for
+pub fn covers(place: &Place) -> bool {
+    US_BOUNDS
+        .iter()
+        .any(|&(south, north, west, east)| (south..=north).contains(&place.lat) && (west..=east).contains(&place.lon))
+}
+
+/// Active alerts covering a point, from the NWS's alerts API. Also used by Open-Meteo, which
+/// has no alerts of its own, for places in the US.
+pub fn fetch_alerts(client: &Client, place: &Place) -> Result<Vec<WeatherAlert>, String> {
+    let response: AlertsResponse = Nws::get(
+        client,
+        &format!("https://api.weather.gov/alerts/active?point={:.4},{:.4}", place.lat, place.lon),
+    )?;
+
+    Ok(response
+        .features
+        .into_iter()
+        .map(|feature| {
+            let alert = feature.properties;
+            let ends = alert.ends.or(alert.expires);
+            WeatherAlert {
+                id: alert.id,
+                headline: alert.headline.unwrap_or_else(|| alert.event.clone()),
+                event: alert.event,
+                severity: alert.severity,
+                description: alert.description,
+                instruction: alert.instruction,
+                ends: ends
+                    .and_then(|ends| DateTime::parse_from_rfc3339(&ends).ok())
+                    .map(day_and_time),
+            }
+        })
+        .collect())
+}
+
+/// Map an icon URL like `https://api.weather.gov/icons/land/night/sct/rain_showers,20?size=medium`
+/// to one of ours. When a period has two conditions the later one is used.
+fn icon_url_to_icon(url: &str) -> String {
+    let path = url.split('?').next().unwrap_or(url);
+    let mut segments = path.split('/').skip_while(|segment| *segment != "land" && *segment != "marine").skip(1);
+    let night = segments.next() == Some("night");
+    let code = segments
+        .last()
+        .and_then(|segment| segment.split(',').next())
+        .unwrap_or_default();
+
+    match code {
+        "skc" | "few" => if night { "clear-night" } else { "clear" },
+        "sct" | "bkn" => if night { "partly-cloudy-night" } else { "partly-cloudy" },
+        "ovc" => "cloudy",
+        "fog" | "haze" | "smoke" | "dust" => "fog",
+        "tsra" | "tsra_sct" | "tsra_hi" | "tornado" | "hurricane" | "tropical_storm" => "storm",
+        "rain" | "rain_showers" | "rain_showers_hi" => "rain",
+        c if c.starts_with("wind_") => "windy",
+        c if c.contains("snow") || c.contains("sleet") || c.contains("fzra") || c == "blizzard" => "snow",
+        _ => if night { "partly-cloudy-night" } else { "partly-cloudy" },
+    }.to_string()
+}
@@ -0,0 +1,216 @@
+use chrono::{Local, NaiveDate};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use crate::config::{TemperatureUnit, WindSpeedUnit};
+use super::geocoder::Place;
+use super::nws;
+use super::weather_provider::{
+    clock_time, compass_point, day_name, day_summary, hour_label, parse_local_time, Units, WeatherProvider,
+};
+use super::weather_service::{ForecastPeriod, HourlyPeriod, WeatherAlert, WeatherData};
+
+// Open-Meteo API response structures
+#[derive(Debug, Deserialize)]
+struct OpenMeteoResponse {
+    current: CurrentWeather,
+    hourly: HourlyForecast,
+    daily: DailyForecast,
+}
+
+#[derive(Debug, Deserialize)]
+struct CurrentWeather {
+    temperature_2m: f64,
+    apparent_temperature: Option<f64>,
+    relative_humidity_2m: Option<f64>,
+    wind_speed_10m: f64,
+    wind_direction_10m: Option<f64>,
+    weather_code: i32,
+    is_day: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct HourlyForecast {
+    /// Local to the location, e.g. "2025-01-31T15:00"
+    time: Vec<String>,
+    temperature_2m: Vec<f64>,
+    precipitation_probability: Vec<Option<f64>>,
+    weather_code: Vec<i32>,
+    is_day: Vec<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DailyForecast {
+    time: Vec<String>,
+    temperature_2m_max: Vec<f64>,
+    temperature_2m_min: Vec<f64>,
+    weather_code: Vec<i32>,
+    precipitation_probability_max: Vec<Option<f64>>,
+    uv_index_max: Vec<Option<f64>>,
+    sunrise: Vec<String>,
+    sunset: Vec<String>,
+}
+
+/// open-meteo.com, which converts units itself
+pub struct OpenMeteo;
+
+impl WeatherProvider for OpenMeteo {
+    fn fetch(&self, client: &Client, place: &Place, units: Units) -> Result<WeatherData, String> {
+        let temperature_unit = match units.temperature {
+            TemperatureUnit::Fahrenheit => "fahrenheit",
+            TemperatureUnit::Celsius => "celsius",
+        };
+        let wind_speed_unit = match units.wind_speed {
+            WindSpeedUnit::Kmh => "kmh",
+            WindSpeedUnit::Mph => "mph",
+            WindSpeedUnit::Ms => "ms",
+        };
+
+        // Single request for current weather and the hourly and daily forecasts. Times come
+        // back in the location's own zone, and the hours start with the current one.
+        let url = format!(
+            "https://api.open-meteo.com/v1/forecast?latitude={}&longitude={}\
+             &current=temperature_2m,apparent_temperature,relative_humidity_2m,wind_speed_10m,wind_direction_10m,weather_code,is_day\
+             &hourly=temperature_2m,precipitation_probability,weather_code,is_day&forecast_hours=24\
+             &daily=temperature_2m_max,temperature_2m_min,weather_code,precipitation_probability_max,uv_index_max,sunrise,sunset\
+             &temperature_unit={}&wind_speed_unit={}&timezone=auto",
+            place.lat, place.lon, temperature_unit, wind_speed_unit
+        );
+
+        let response = client
+            .get(&url)
+            .send()
+            .map_err(|e| e.to_string())?
+            .json::<OpenMeteoResponse>()
+            .map_err(|e| e.to_string())?;
+
+        let current_code = response.current.weather_code;
+        let is_day = response.current.is_day == 1;
+        let condition = weather_code_to_description(current_code);
+
+        let hourly = &response.hourly;
+        let hourly_forecast: Vec<HourlyPeriod> = hourly.time
+            .iter()
+            .enumerate()
+            .map(|(i, time)| HourlyPeriod {
+                time: parse_local_time(time).map(hour_label).unwrap_or_else(|| time.clone()),
+                temperature: hourly.temperature_2m.get(i).copied().unwrap_or(0.0) as i32,
+                icon_name: weather_code_to_icon(
+                    hourly.weather_code.get(i).copied().unwrap_or(0),
+                    hourly.is_day.get(i) == Some(&1),
+                ),
+                precipitation_probability: percent(hourly.precipitation_probability.get(i)),
+            })
+            .collect();
+
+        let daily = &response.daily;
+        let detailed_forecast: Vec<ForecastPeriod> = daily.time
+            .iter()
+            .enumerate()
+            .take(7)
+            .map(|(i, date)| {
+                let name = match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                    Ok(date) => day_name(i, date),
+                    Err(_) => date.clone(),
+                };
+                let high = daily.temperature_2m_max.get(i).copied().unwrap_or(0.0) as i32;
+                let low = daily.temperature_2m_min.get(i).copied().unwrap_or(0.0) as i32;
+                let code = daily.weather_code.get(i).copied().unwrap_or(0);
+
+                // Each day is summed up by its daytime weather
+                ForecastPeriod {
+                    name,
+                    temperature: high,
+                    temperature_unit: units.temperature.symbol().to_string(),
+                    short_forecast: day_summary(&weather_code_to_description(code), low, units),
+                    icon_name: weather_code_to_icon(code, true),
+                    precipitation_probability: percent(daily.precipitation_probability_max.get(i)),
+                }
+            })
+            .collect();
+
+        let today_time = |times: &[String]| times.first().and_then(|time| parse_local_time(time)).map(clock_time);
+
+        Ok(WeatherData {
+            temperature: response.current.temperature_2m,
+            temperature_unit: units.temperature.symbol().to_string(),
+            wind_speed: response.current.wind_speed_10m,
+            wind_speed_unit: units.wind_speed.label().to_string(),
+            wind_direction: response.current.wind_direction_10m.map(compass_point),
+            feels_like: response.current.apparent_temperature,
+            humidity: response.current.relative_humidity_2m.map(|humidity| humidity.round() as u8),
+            precipitation_probability: hourly_forecast.first().and_then(|hour| hour.precipitation_probability),
+            uv_index: daily.uv_index_max.first().copied().flatten(),
+            sunrise: today_time(&daily.sunrise),
+            sunset: today_time(&daily.sunset),
+            condition: condition.clone(),
+            icon: weather_code_to_icon(current_code, is_day),
+            short_forecast: condition,
+            hourly_forecast,
+            detailed_forecast,
+            location_name: place.name.clone(),
+            fetched_at: Local::now().timestamp(),
+            // Filled in by the service from `fetch_alerts`
+            alerts: Vec::new(),
+        })
+    }
+
+    /// The NWS's alerts for places in the US; there's no source for anywhere else yet
+    fn fetch_alerts(&self, client: &Client, place: &Place) -> Result<Vec<WeatherAlert>, String> {
+        if nws::covers(place) {
+            nws::fetch_alerts(client, place)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// A percentage from a list that has gaps where the model has no value
+fn percent(value: Option<&Option<f64>>) -> Option<u8> {
+    value.copied().flatten().map(|value| value.round() as u8)
+}
+
+fn weather_code_to_description(code: i32) -> String {
+    // WMO Weather interpretation codes
+    match code {
+        0 => "Clear sky",
+        1 => "Mainly clear",
+        2 => "Partly cloudy",
+        3 => "Overcast",
+        45 | 48 => "Foggy",
+        51 => "Light drizzle",
+        53 => "Moderate drizzle",
+        55 => "Dense drizzle",
+        56 | 57 => "Freezing drizzle",
+        61 => "Slight rain",
+        63 => "Moderate rain",
+        65 => "Heavy rain",
+        66 | 67 => "Freezing rain",
+        71 => "Slight snow",
+        73 => "Moderate snow",
+        75 => "Heavy snow",
+        77 => "Snow grains",
+        80 => "Slight rain showers",
+        81 => "Moderate rain showers",
+        82 => "Violent rain showers",
+        85 => "Slight snow showers",
+        86 => "Heavy snow showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => "Unknown",
+    }.to_string()
+}
+
+fn weather_code_to_icon(code: i32, is_day: bool) -> String {
+    match code {
+        0 => if is_day { "clear" } else { "clear-night" },
+        1 | 2 => if is_day { "partly-cloudy" } else { "partly-cloudy-night" },
+        3 => "cloudy",
+        45 | 48 => "fog",
+        51 | 53 | 55 | 56 | 57 => "rain",
+        61 | 63 | 65 | 66 | 67 | 80 | 81 | 82 => "rain",
+        71 | 73 | 75 | 77 | 85 | 86 => "snow",
+        95 | 96 | 99 => "storm",
+        _ => if is_day { "partly-cloudy" } else { "partly-cloudy-night" },
+    }.to_string()
+}
@@ -1,18 +1,24 @@
-use gtk::{glib, prelude::*, Image, Widget};
+use gtk::{glib, prelude::*, Align, Image, Overlay, Widget};
+use std::cell::RefCell;
 use std::path::PathBuf;
+use std::rc::Rc;
 
-use crate::{traits::CompositeWidget, widgets::PanelButtonBuilder};
+use crate::{config::{PanelLayoutConfig, WeatherButtonConfig}, traits::CompositeWidget, widgets::PanelButtonBuilder};
 use crate::widgets::PanelButton;
-use super::weather_service::WeatherService;
+use super::weather_service::{WeatherAlert, WeatherData, WeatherService};
 use super::forecast_widget::ForecastWidget;
 
+/// How often the button checks whether the weather it shows has gone stale
+const STALE_CHECK_SECS: u32 = 60;
+
 pub struct WeatherButton {
   panel_button: PanelButton,
 }
 
 impl WeatherButton {
-  pub fn new(location: &str) -> Self {
-    let initial_data = WeatherService::start(300, location);
+  pub fn new(config: &WeatherButtonConfig) -> Self {
+    // Cached weather, if any; fresh data arrives through the subscription
+    let initial_data = WeatherService::start(300, config);
 
     // Create dropdown widget before the builder so it stays alive
     let forecast_widget = ForecastWidget::new();
@@ -25,22 +31,25 @@ impl WeatherButton {
 
     let panel_button =
       if let Some(ref weather_data) = initial_data {
-        let weather_icon = Self::get_weather_icon(&weather_data.icon);
-        let temperature_text = format!("{}°F", weather_data.temperature as i32);
+        let weather_icon = Self::create_icon_widget(weather_data);
+        let temperature_text = format!("{}°{}", weather_data.temperature as i32, weather_data.temperature_unit);
 
         PanelButtonBuilder::new()
-          .custom_widget(Some(weather_icon.upcast()))
+          .custom_widget(Some(weather_icon))
           .text(&temperature_text)
           .dropdown_widget(dropdown_widget)
           .build()
       }
       else {
         PanelButtonBuilder::new()
-          .text("--°F")
+          .text(format!("--°{}", config.temperature_unit.symbol()))
           .dropdown_widget(dropdown_widget)
           .build()
       };
 
+    let latest = Rc::new(RefCell::new(initial_data));
+    Self::update_tooltip(&panel_button, latest.borrow().as_ref());
+
     // Connect location change from entry
     forecast_widget.connect_location_changed(move |new_location| {
       WeatherService::update_location(&new_location);
@@ -49,42 +58,109 @@ impl WeatherButton {
     // Subscribe to weather updates
     let panel_button_clone = panel_button.clone();
     let forecast_widget_clone = forecast_widget.clone();
+    let latest_clone = latest.clone();
     WeatherService::subscribe(move |weather| {
       let panel_button = panel_button_clone.clone();
       let forecast_widget = forecast_widget_clone.clone();
+      let latest = latest_clone.clone();
       let weather_clone = weather.clone();
 
       // Defer widget updates to the next GTK main loop iteration
       glib::idle_add_local_once(move || {
         // Update panel button text
-        let temp_text = format!("{}°F", weather_clone.temperature as i32);
+        let temp_text = format!("{}°{}", weather_clone.temperature as i32, weather_clone.temperature_unit);
         panel_button.set_text(&temp_text);
 
-        // Update tooltip
-        let tooltip = format!(
-          "{}\n{}°F",
-          weather_clone.short_forecast,
-          weather_clone.temperature as i32
-        );
-        panel_button.set_tooltip_text(Some(&tooltip));
-
         // Update panel button icon
-        let new_icon = WeatherButton::get_weather_icon(&weather_clone.icon);
-        panel_button.set_custom_widget(Some(new_icon.upcast_ref()));
+        let new_icon = WeatherButton::create_icon_widget(&weather_clone);
+        panel_button.set_custom_widget(Some(&new_icon));
 
         // Save the successfully geocoded location to config
-        save_location_to_config(&WeatherService::current_location());
+        let location = WeatherService::current_location();
+        if let Err(e) = PanelLayoutConfig::update_button_config("weather", |button| button["location"] = location.clone().into()) {
+          eprintln!("waltopanel: failed to save weather location: {}", e);
+        }
 
         // Update forecast dropdown
         forecast_widget.update(&weather_clone);
+
+        *latest.borrow_mut() = Some(weather_clone);
+        WeatherButton::update_tooltip(&panel_button, latest.borrow().as_ref());
       });
     });
 
+    // Data goes stale on its own while fetches keep failing
+    let panel_button_clone = panel_button.clone();
+    glib::timeout_add_seconds_local(STALE_CHECK_SECS, move || {
+      Self::update_tooltip(&panel_button_clone, latest.borrow().as_ref());
+      glib::ControlFlow::Continue
+    });
+
     Self {
       panel_button,
     }
   }
 
+  /// The weather icon, with a warning badge in the corner while there are alerts
+  fn create_icon_widget(weather: &WeatherData) -> Widget {
+    let icon = Self::get_weather_icon(&weather.icon);
+    if weather.alerts.is_empty() {
+      return icon.upcast();
+    }
+
+    let badge = Image::builder()
+      .icon_name("dialog-warning-symbolic")
+      .pixel_size(10)
+      .halign(Align::End)
+      .valign(Align::End)
+      .css_classes(["weather-alert-badge"])
+      .build();
+    if weather.alerts.iter().any(WeatherAlert::is_severe) {
+      badge.add_css_class("severe");
+    }
+
+    let overlay = Overlay::builder().child(&icon).build();
+    overlay.add_overlay(&badge);
+    overlay.upcast()
+  }
+
+  /// Set the tooltip, listing alerts and saying how old the weather is, and dim the button
+  /// when it's stale
+  fn update_tooltip(panel_button: &PanelButton, weather: Option<&WeatherData>) {
+    let Some(weather) = weather else {
+      panel_button.set_tooltip_text(Some("Weather unavailable"));
+      return;
+    };
+
+    let mut tooltip: String = weather.alerts.iter().map(|alert| format!("⚠ {}\n", alert.event)).collect();
+    tooltip.push_str(&format!(
+      "{}\n{}°{}, wind {} {}",
+      weather.short_forecast,
+      weather.temperature as i32,
+      weather.temperature_unit,
+      weather.wind_speed.round() as i32,
+      weather.wind_speed_unit
+    ));
+
+    if weather.is_stale() {
+      tooltip.push_str(&format!("\nLast updated {} ago", Self::describe_age(weather.age())));
+      panel_button.add_css_class("weather-stale");
+    } else {
+      panel_button.remove_css_class("weather-stale");
+    }
+
+    panel_button.set_tooltip_text(Some(&tooltip));
+  }
+
+  fn describe_age(age: chrono::Duration) -> String {
+    match (age.num_days(), age.num_hours(), age.num_minutes()) {
+      (1, _, _) => "1 day".to_string(),
+      (days, _, _) if days > 1 => format!("{} days", days),
+      (_, hours, _) if hours > 0 => format!("{} h", hours),
+      (_, _, minutes) => format!("{} min", minutes),
+    }
+  }
+
   fn get_weather_icon(icon_name: &str) -> Image {
     let image = Image::builder()
         .pixel_size(16)
@@ -277,30 +353,3 @@ impl CompositeWidget for WeatherButton {
     }
 }
  */
-
-fn save_location_to_config(new_location: &str) {
-    let config_path = match std::env::var("HOME").ok() {
-        Some(home) => std::path::PathBuf::from(home).join(".config/waltopanel/config.json"),
-        None => return,
-    };
-    let content = match std::fs::read_to_string(&config_path) {
-        Ok(c) => c,
-        Err(_) => return,
-    };
-    let mut json: serde_json::Value = match serde_json::from_str(&content) {
-        Ok(v) => v,
-        Err(_) => return,
-    };
-    for section in ["left", "center", "right"] {
-        if let Some(arr) = json.get_mut(section).and_then(|v| v.as_array_mut()) {
-            for entry in arr.iter_mut() {
-                if entry.get("type").and_then(|t| t.as_str()) == Some("weather") {
-                    entry["location"] = serde_json::Value::String(new_location.to_string());
-                }
-            }
-        }
-    }
-    if let Ok(updated) = serde_json::to_string_pretty(&json) {
-        let _ = std::fs::write(&config_path, updated);
-    }
-}
@@ -0,0 +1,97 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike, Weekday};
+use reqwest::blocking::Client;
+
+use crate::config::{TemperatureUnit, WeatherProviderKind, WindSpeedUnit};
+use super::geocoder::Place;
+use super::met_norway::MetNorway;
+use super::nws::Nws;
+use super::open_meteo::OpenMeteo;
+use super::weather_service::{WeatherAlert, WeatherData};
+
+/// The units weather is shown in
+#[derive(Debug, Clone, Copy)]
+pub struct Units {
+    pub temperature: TemperatureUnit,
+    pub wind_speed: WindSpeedUnit,
+}
+
+/// A forecast source. Each one maps its own API's response into `WeatherData`, converted to
+/// the requested units.
+pub trait WeatherProvider: Send + Sync {
+    fn fetch(&self, client: &Client, place: &Place, units: Units) -> Result<WeatherData, String>;
+
+    /// Active warnings for the location. None unless the provider has a source for them.
+    fn fetch_alerts(&self, _client: &Client, _place: &Place) -> Result<Vec<WeatherAlert>, String> {
+        Ok(Vec::new())
+    }
+}
+
+pub fn provider(kind: WeatherProviderKind) -> Box<dyn WeatherProvider> {
+    match kind {
+        WeatherProviderKind::OpenMeteo => Box::new(OpenMeteo),
+        WeatherProviderKind::MetNorway => Box::new(MetNorway),
+        WeatherProviderKind::Nws => Box::new(Nws),
+    }
+}
+
+/// "Today", "Tomorrow", then weekday names, for the `index`th day of a forecast
+pub fn day_name(index: usize, date: NaiveDate) -> String {
+    match index {
+        0 => "Today",
+        1 => "Tomorrow",
+        _ => match date.weekday() {
+            Weekday::Mon => "Monday",
+            Weekday::Tue => "Tuesday",
+            Weekday::Wed => "Wednesday",
+            Weekday::Thu => "Thursday",
+            Weekday::Fri => "Friday",
+            Weekday::Sat => "Saturday",
+            Weekday::Sun => "Sunday",
+        },
+    }
+    .to_string()
+}
+
+/// The summary line for one day of the forecast, which shows its high separately
+pub fn day_summary(condition: &str, low: i32, units: Units) -> String {
+    format!("{} (Low: {}°{})", condition, low, units.temperature.symbol())
+}
+
+/// A time of day for display, e.g. "7:12 AM"
+pub fn clock_time(time: impl Timelike) -> String {
+    let (pm, hour) = time.hour12();
+    format!("{}:{:02} {}", hour, time.minute(), if pm { "PM" } else { "AM" })
+}
+
+/// A day and time for display in local time, e.g. "Tue 6:00 PM"
+pub fn day_and_time<Tz: TimeZone>(time: DateTime<Tz>) -> String {
+    let time = time.with_timezone(&Local);
+    format!("{} {}", time.format("%a"), clock_time(time))
+}
+
+/// An hour for the hourly forecast, e.g. "3 PM"
+pub fn hour_label(time: impl Timelike) -> String {
+    let (pm, hour) = time.hour12();
+    format!("{} {}", hour, if pm { "PM" } else { "AM" })
+}
+
+/// Parse the minute-precision local times some APIs use, e.g. "2025-01-31T07:12"
+pub fn parse_local_time(time: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M").ok()
+}
+
+/// The compass point a wind from `degrees` blows from, e.g. "NW"
+pub fn compass_point(degrees: f64) -> String {
+    const POINTS: [&str; 16] = [
+        "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW", "NNW",
+    ];
+    let index = (degrees.rem_euclid(360.0) / 22.5).round() as usize % POINTS.len();
+    POINTS[index].to_string()
+}
+
+/// Australian Bureau of Meteorology apparent temperature, for providers that don't give a
+/// feels-like temperature. Takes and returns Celsius, with wind in meters per second.
+pub fn apparent_temperature(celsius: f64, humidity: f64, wind_speed: f64) -> f64 {
+    let vapour_pressure = humidity / 100.0 * 6.105 * (17.27 * celsius / (237.7 + celsius)).exp();
+    celsius + 0.33 * vapour_pressure - 0.70 * wind_speed - 4.0
+}
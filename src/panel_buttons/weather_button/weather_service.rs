@@ -1,37 +1,72 @@
+use chrono::Local;
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
 use gtk::glib;
 use gtk::prelude::ApplicationExt;
 
-#[derive(Debug, Deserialize)]
-struct NominatimResult {
-    lat: String,
-    lon: String,
-    address: NominatimAddress,
-}
-
-#[derive(Debug, Deserialize)]
-struct NominatimAddress {
-    city: Option<String>,
-    town: Option<String>,
-    village: Option<String>,
-    hamlet: Option<String>,
-    county: Option<String>,
-    state: Option<String>,
-    #[serde(rename = "ISO3166-2-lvl4")]
-    state_code: Option<String>,
-    country: Option<String>,
-}
+use crate::config::{GeocoderKind, WeatherButtonConfig, WeatherProviderKind};
+use crate::panel_buttons::network_button::{ConnectionType, NetworkService};
+use super::geocoder::{geocoder, Place};
+use super::weather_provider::{provider, Units};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WeatherData {
     pub temperature: f64,
+    /// "F" or "C", shown after the degree sign
+    pub temperature_unit: String,
+    pub wind_speed: f64,
+    /// e.g. "mph"
+    pub wind_speed_unit: String,
+    /// Compass point the wind blows from, e.g. "NW"
+    pub wind_direction: Option<String>,
+    pub feels_like: Option<f64>,
+    /// Relative humidity, in percent
+    pub humidity: Option<u8>,
+    /// Chance of precipitation this hour, in percent
+    pub precipitation_probability: Option<u8>,
+    /// Today's maximum
+    pub uv_index: Option<f64>,
+    /// Today's sunrise and sunset, formatted for display
+    pub sunrise: Option<String>,
+    pub sunset: Option<String>,
     pub condition: String,
     pub icon: String,
     pub short_forecast: String,
+    /// The next 24 hours, starting with the current one
+    pub hourly_forecast: Vec<HourlyPeriod>,
     pub detailed_forecast: Vec<ForecastPeriod>,
     pub location_name: String,
+    /// When it was fetched, in Unix seconds
+    pub fetched_at: i64,
+    /// Active warnings for the location
+    #[serde(default)]
+    pub alerts: Vec<WeatherAlert>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeatherAlert {
+    /// Stays the same across fetches, so each alert is only notified once
+    pub id: String,
+    /// e.g. "Winter Storm Warning"
+    pub event: String,
+    pub headline: String,
+    /// CAP severity: "Extreme", "Severe", "Moderate", "Minor" or "Unknown"
+    pub severity: String,
+    pub description: String,
+    pub instruction: Option<String>,
+    /// When it ends, formatted for display
+    pub ends: Option<String>,
+}
+
+impl WeatherAlert {
+    pub fn is_severe(&self) -> bool {
+        matches!(self.severity.as_str(), "Extreme" | "Severe")
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,39 +76,74 @@ pub struct ForecastPeriod {
     pub temperature_unit: String,
     pub short_forecast: String,
     pub icon_name: String,
+    /// Chance of precipitation, in percent
+    pub precipitation_probability: Option<u8>,
 }
 
-// Open-Meteo API response structures
-#[derive(Debug, Deserialize)]
-struct OpenMeteoResponse {
-    current: CurrentWeather,
-    daily: DailyForecast,
-}
-
-#[derive(Debug, Deserialize)]
-struct CurrentWeather {
-    temperature_2m: f64,
-    weather_code: i32,
-    is_day: i32,
-}
-
-#[derive(Debug, Deserialize)]
-struct DailyForecast {
-    time: Vec<String>,
-    temperature_2m_max: Vec<f64>,
-    temperature_2m_min: Vec<f64>,
-    weather_code: Vec<i32>,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HourlyPeriod {
+    /// e.g. "3 PM"
+    pub time: String,
+    pub temperature: i32,
+    pub icon_name: String,
+    /// Chance of precipitation, in percent
+    pub precipitation_probability: Option<u8>,
 }
 
 type Callback = Box<dyn Fn(WeatherData) + 'static>;
 
+/// First wait after a failed fetch, doubling with each failure after that
+const MIN_RETRY_SECS: u32 = 15;
+/// Data older than this is marked stale
+const STALE_AFTER_SECS: i64 = 30 * 60;
+
 thread_local! {
     static SUBSCRIBERS: RefCell<Vec<Callback>> = RefCell::new(Vec::new());
+    static STARTED: Cell<bool> = const { Cell::new(false) };
+    /// Retry pending after a failed fetch, and how long the next one will wait
+    static RETRY: RefCell<Option<glib::SourceId>> = const { RefCell::new(None) };
+    static RETRY_SECS: Cell<u32> = const { Cell::new(MIN_RETRY_SECS) };
+    static UPDATE_INTERVAL_SECS: Cell<u32> = const { Cell::new(300) };
+    /// Whether NetworkService last said we're connected
+    static ONLINE: Cell<Option<bool>> = const { Cell::new(None) };
 }
 
 lazy_static::lazy_static! {
     static ref CURRENT_WEATHER: Arc<Mutex<Option<WeatherData>>> = Arc::new(Mutex::new(None));
     static ref CURRENT_LOCATION: Arc<Mutex<String>> = Arc::new(Mutex::new(String::new()));
+    /// Where the current location geocoded to, so it's only looked up once
+    static ref CURRENT_PLACE: Arc<Mutex<Option<(String, Place)>>> = Arc::new(Mutex::new(None));
+    static ref SOURCES: Arc<Mutex<Option<Sources>>> = Arc::new(Mutex::new(None));
+    /// Ids of alerts already notified about
+    static ref SEEN_ALERTS: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+}
+
+/// Where weather comes from and the units it's shown in, from the button's config
+#[derive(Clone, Copy)]
+struct Sources {
+    provider: WeatherProviderKind,
+    geocoder: GeocoderKind,
+    units: Units,
+}
+
+/// The last successful fetch, persisted in `$XDG_CACHE_HOME/waltopanel/weather.json` so
+/// there's something to show straight away at startup, even offline
+#[derive(Serialize, Deserialize)]
+struct WeatherCache {
+    /// The location as configured, which `place` is the geocoded form of
+    location: String,
+    place: Place,
+    weather: WeatherData,
+}
+
+impl WeatherData {
+    pub fn age(&self) -> chrono::Duration {
+        chrono::Duration::seconds(Local::now().timestamp() - self.fetched_at)
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.age().num_seconds() > STALE_AFTER_SECS
+    }
 }
 
 pub struct WeatherService;
@@ -82,21 +152,24 @@ impl WeatherService {
     pub fn update_location(new_location: &str) {
         let loc = new_location.to_string();
         std::thread::spawn(move || {
-            if let Some(weather) = Self::fetch_weather_blocking(&loc) {
-                // Only commit the new location if geocoding and fetch succeeded
-                *CURRENT_LOCATION.lock().unwrap() = loc;
-                let location_name = weather.location_name.clone();
-                *CURRENT_WEATHER.lock().unwrap() = Some(weather.clone());
-                Self::notify_subscribers(weather);
-                Self::send_notification(
-                    "Location Updated",
-                    &format!("Now showing weather for {}", location_name),
-                );
-            } else {
-                Self::send_notification(
-                    "Location Not Found",
-                    &format!("Couldn't find weather for \"{}\"", loc),
-                );
+            match Self::fetch_weather_blocking(&loc) {
+                Ok((place, weather)) => {
+                    // Only commit the new location if geocoding and fetch succeeded
+                    *CURRENT_LOCATION.lock().unwrap() = loc.clone();
+                    let location_name = weather.location_name.clone();
+                    Self::publish(loc, place, weather);
+                    Self::send_notification(
+                        "Location Updated",
+                        &format!("Now showing weather for {}", location_name),
+                    );
+                }
+                Err(e) => {
+                    eprintln!("waltopanel: {}", e);
+                    Self::send_notification(
+                        "Location Not Found",
+                        &format!("Couldn't find weather for \"{}\"", loc),
+                    );
+                }
             }
         });
     }
@@ -105,6 +178,22 @@ impl WeatherService {
         CURRENT_LOCATION.lock().unwrap().clone()
     }
 
+    fn send_alert_notification(alert: &WeatherAlert) {
+        let alert = alert.clone();
+        glib::MainContext::default().invoke(move || {
+            if let Some(app) = gtk::gio::Application::default() {
+                let notification = gtk::gio::Notification::new(&alert.event);
+                notification.set_body(Some(&alert.headline));
+                notification.set_icon(&gtk::gio::ThemedIcon::new("dialog-warning-symbolic"));
+                notification.set_priority(match alert.is_severe() {
+                    true => gtk::gio::NotificationPriority::Urgent,
+                    false => gtk::gio::NotificationPriority::High,
+                });
+                app.send_notification(Some(&format!("weather-alert-{}", alert.id)), &notification);
+            }
+        });
+    }
+
     fn send_notification(title: &str, body: &str) {
         let title = title.to_string();
         let body = body.to_string();
@@ -117,36 +206,60 @@ impl WeatherService {
         });
     }
 
-    pub fn start(update_interval_secs: u64, location: &str) -> Option<WeatherData> {
-        *CURRENT_LOCATION.lock().unwrap() = location.to_string();
-        let location = location.to_string();
+    /// Start fetching in the background, returning the cached weather to show meanwhile. Each
+    /// panel's button calls this, but only the first call starts anything.
+    pub fn start(update_interval_secs: u64, config: &WeatherButtonConfig) -> Option<WeatherData> {
+        if STARTED.replace(true) {
+            return CURRENT_WEATHER.lock().unwrap().clone();
+        }
+
+        *CURRENT_LOCATION.lock().unwrap() = config.location.clone();
+        let units = Units {
+            temperature: config.temperature_unit,
+            wind_speed: config.wind_speed_unit(),
+        };
+        *SOURCES.lock().unwrap() = Some(Sources {
+            provider: config.provider,
+            geocoder: config.geocoder,
+            units,
+        });
+        UPDATE_INTERVAL_SECS.set(update_interval_secs as u32);
 
-        // Fetch initial weather data
-        let initial_weather = Self::fetch_weather_blocking(&location);
+        // The cached coordinates are good as long as the location hasn't changed, but the
+        // weather is only worth showing if it's in the units now configured
+        let cached = Self::load_cache().filter(|cache| cache.location == config.location);
 
-        if let Some(ref weather) = initial_weather {
-            *CURRENT_WEATHER.lock().unwrap() = Some(weather.clone());
+        // Alerts shown before a restart were already notified about
+        if let Some(cache) = &cached {
+            SEEN_ALERTS.lock().unwrap().extend(cache.weather.alerts.iter().map(|alert| alert.id.clone()));
         }
+        let initial_weather = cached.and_then(|cache| {
+            *CURRENT_PLACE.lock().unwrap() = Some((cache.location, cache.place));
+            Some(cache.weather).filter(|weather| {
+                weather.temperature_unit == units.temperature.symbol()
+                    && weather.wind_speed_unit == units.wind_speed.label()
+            })
+        });
+        *CURRENT_WEATHER.lock().unwrap() = initial_weather.clone();
+
+        Self::refresh();
 
-        // Start periodic updates using glib timer
         glib::timeout_add_seconds_local(update_interval_secs as u32, move || {
-            eprintln!("Weather timer fired, fetching update...");
-            let location = CURRENT_LOCATION.lock().unwrap().clone();
-            // Spawn a thread to fetch weather data
-            std::thread::spawn(move || {
-                match Self::fetch_weather_blocking(&location) {
-                    Some(weather) => {
-                        eprintln!("Weather update successful: {:.1}°F", weather.temperature);
-                        *CURRENT_WEATHER.lock().unwrap() = Some(weather.clone());
-                        Self::notify_subscribers(weather);
-                    }
-                    None => {
-                        eprintln!("Weather fetch failed!");
-                    }
+            Self::refresh();
+            glib::ControlFlow::Continue
+        });
+
+        // Buttons are all built by the time this runs, so a network button has already started
+        // the network service at its own interval. Without one it's started here to follow
+        // connectivity, polling its network lists only as often as the weather is fetched.
+        glib::idle_add_local_once(move || {
+            NetworkService::start(update_interval_secs as u32);
+            NetworkService::subscribe(|metrics| {
+                let online = !matches!(metrics.connection_type, ConnectionType::Disconnected);
+                if ONLINE.replace(Some(online)) == Some(false) && online {
+                    Self::refresh();
                 }
             });
-
-            glib::ControlFlow::Continue
         });
 
         initial_weather
@@ -172,170 +285,131 @@ impl WeatherService {
         });
     }
 
-    fn geocode(client: &reqwest::blocking::Client, location: &str) -> Option<(f64, f64, String)> {
-        let results: Vec<NominatimResult> = client
-            .get("https://nominatim.openstreetmap.org/search")
-            .query(&[("q", location), ("format", "json"), ("limit", "1"), ("addressdetails", "1")])
-            .send()
-            .ok()?
-            .json()
-            .ok()?;
-        let first = results.into_iter().next()?;
-        let lat = first.lat.parse::<f64>().ok()?;
-        let lon = first.lon.parse::<f64>().ok()?;
-        let place = first.address.city
-            .or(first.address.town)
-            .or(first.address.village)
-            .or(first.address.hamlet)
-            .or(first.address.county)
-            .unwrap_or_default();
-        let state_abbr = first.address.state_code
-            .as_deref()
-            .and_then(|s| s.split('-').last())
-            .map(|s| s.to_string())
-            .or(first.address.state)
-            .unwrap_or_default();
-        let display_name = [place.as_str(), state_abbr.as_str()]
-            .iter()
-            .filter(|s| !s.is_empty())
-            .cloned()
-            .collect::<Vec<_>>()
-            .join(", ");
-        Some((lat, lon, display_name))
-    }
+    /// Fetch in the background, and if it fails keep retrying, waiting twice as long each
+    /// time up to the update interval
+    fn refresh() {
+        if let Some(source) = RETRY.take() {
+            source.remove();
+        }
 
-    fn fetch_weather_blocking(location: &str) -> Option<WeatherData> {
-        let client = reqwest::blocking::Client::builder()
-            .user_agent("WaltoPanel/1.0")
-            .timeout(std::time::Duration::from_secs(10))
-            .build()
-            .ok()?;
-
-        let (lat, lon, location_name) = Self::geocode(&client, location).or_else(|| {
-            eprintln!("waltopanel: failed to geocode location '{}'", location);
-            None
-        })?;
-
-        // Open-Meteo API - single request for current weather and daily forecast
-        let url = format!(
-            "https://api.open-meteo.com/v1/forecast?latitude={}&longitude={}&current=temperature_2m,weather_code,is_day&daily=temperature_2m_max,temperature_2m_min,weather_code&temperature_unit=fahrenheit&timezone=auto",
-            lat, lon
-        );
-
-        let response = client
-            .get(&url)
-            .send()
-            .ok()?
-            .json::<OpenMeteoResponse>()
-            .ok()?;
-
-        let current_temp = response.current.temperature_2m;
-        let current_code = response.current.weather_code;
-        let is_day = response.current.is_day == 1;
-        let condition = Self::weather_code_to_description(current_code);
-        let icon = Self::weather_code_to_icon(current_code, is_day);
-
-        eprintln!("Weather API (Open-Meteo) - Temp: {:.1}°F, Condition: '{}'", current_temp, condition);
-
-        // Build daily forecast
-        let day_names = ["Today", "Tomorrow"];
-        let detailed_forecast: Vec<ForecastPeriod> = response.daily.time
-            .iter()
-            .enumerate()
-            .take(7)
-            .map(|(i, date)| {
-                let name = if i < day_names.len() {
-                    day_names[i].to_string()
-                } else {
-                    // Parse date and get day name
-                    Self::date_to_day_name(date)
-                };
-                let high = response.daily.temperature_2m_max.get(i).copied().unwrap_or(0.0) as i32;
-                let low = response.daily.temperature_2m_min.get(i).copied().unwrap_or(0.0) as i32;
-                let code = response.daily.weather_code.get(i).copied().unwrap_or(0);
-                let forecast_desc = Self::weather_code_to_description(code);
-
-                ForecastPeriod {
-                    name,
-                    temperature: high,
-                    temperature_unit: "F".to_string(),
-                    short_forecast: format!("{} (Low: {}°F)", forecast_desc, low),
-                    icon_name: Self::weather_code_to_icon(code, true),
+        let location = CURRENT_LOCATION.lock().unwrap().clone();
+        std::thread::spawn(move || {
+            let result = Self::fetch_weather_blocking(&location);
+            glib::MainContext::default().invoke(move || match result {
+                Ok((place, weather)) => {
+                    RETRY_SECS.set(MIN_RETRY_SECS);
+                    Self::publish(location, place, weather);
                 }
-            })
-            .collect();
-
-        Some(WeatherData {
-            temperature: current_temp,
-            condition: condition.clone(),
-            icon,
-            short_forecast: condition,
-            detailed_forecast,
-            location_name,
-        })
+                Err(e) => {
+                    let wait = RETRY_SECS.get();
+                    eprintln!("waltopanel: {}; retrying in {}s", e, wait);
+                    RETRY_SECS.set((wait * 2).min(UPDATE_INTERVAL_SECS.get()));
+                    let retry = glib::timeout_add_seconds_local_once(wait, || {
+                        RETRY.take();
+                        Self::refresh();
+                    });
+                    // Another fetch can fail while this one was in flight
+                    if let Some(old) = RETRY.replace(Some(retry)) {
+                        old.remove();
+                    }
+                }
+            });
+        });
+    }
+
+    /// Make newly fetched weather current, cache it and tell subscribers, and notify about
+    /// any alerts that weren't there before
+    fn publish(location: String, place: Place, weather: WeatherData) {
+        let new_alerts: Vec<WeatherAlert> = {
+            let mut seen = SEEN_ALERTS.lock().unwrap();
+            weather.alerts.iter().filter(|alert| seen.insert(alert.id.clone())).cloned().collect()
+        };
+        for alert in &new_alerts {
+            Self::send_alert_notification(alert);
+        }
+
+        let cache = WeatherCache { location, place, weather };
+        *CURRENT_PLACE.lock().unwrap() = Some((cache.location.clone(), cache.place.clone()));
+        *CURRENT_WEATHER.lock().unwrap() = Some(cache.weather.clone());
+        Self::save_cache(&cache);
+        Self::notify_subscribers(cache.weather);
     }
 
-    fn weather_code_to_description(code: i32) -> String {
-        // WMO Weather interpretation codes
-        match code {
-            0 => "Clear sky",
-            1 => "Mainly clear",
-            2 => "Partly cloudy",
-            3 => "Overcast",
-            45 | 48 => "Foggy",
-            51 => "Light drizzle",
-            53 => "Moderate drizzle",
-            55 => "Dense drizzle",
-            56 | 57 => "Freezing drizzle",
-            61 => "Slight rain",
-            63 => "Moderate rain",
-            65 => "Heavy rain",
-            66 | 67 => "Freezing rain",
-            71 => "Slight snow",
-            73 => "Moderate snow",
-            75 => "Heavy snow",
-            77 => "Snow grains",
-            80 => "Slight rain showers",
-            81 => "Moderate rain showers",
-            82 => "Violent rain showers",
-            85 => "Slight snow showers",
-            86 => "Heavy snow showers",
-            95 => "Thunderstorm",
-            96 | 99 => "Thunderstorm with hail",
-            _ => "Unknown",
-        }.to_string()
+    fn cache_path() -> Option<PathBuf> {
+        let cache_dir = env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(cache_dir.join("waltopanel").join("weather.json"))
     }
 
-    fn weather_code_to_icon(code: i32, is_day: bool) -> String {
-        match code {
-            0 => if is_day { "clear" } else { "clear-night" },
-            1 | 2 => if is_day { "partly-cloudy" } else { "partly-cloudy-night" },
-            3 => "cloudy",
-            45 | 48 => "fog",
-            51 | 53 | 55 | 56 | 57 => "rain",
-            61 | 63 | 65 | 66 | 67 | 80 | 81 | 82 => "rain",
-            71 | 73 | 75 | 77 | 85 | 86 => "snow",
-            95 | 96 | 99 => "storm",
-            _ => if is_day { "partly-cloudy" } else { "partly-cloudy-night" },
-        }.to_string()
+    fn load_cache() -> Option<WeatherCache> {
+        let content = fs::read_to_string(Self::cache_path()?).ok()?;
+        serde_json::from_str(&content)
+            .map_err(|e| eprintln!("waltopanel: ignoring unreadable weather cache: {}", e))
+            .ok()
     }
 
-    fn date_to_day_name(date: &str) -> String {
-        // Parse YYYY-MM-DD format and return day name
-        use chrono::{NaiveDate, Datelike, Weekday};
-
-        if let Ok(parsed) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
-            match parsed.weekday() {
-                Weekday::Mon => "Monday",
-                Weekday::Tue => "Tuesday",
-                Weekday::Wed => "Wednesday",
-                Weekday::Thu => "Thursday",
-                Weekday::Fri => "Friday",
-                Weekday::Sat => "Saturday",
-                Weekday::Sun => "Sunday",
-            }.to_string()
-        } else {
-            date.to_string()
+    fn save_cache(cache: &WeatherCache) {
+        let Some(path) = Self::cache_path() else { return };
+        if let Some(dir) = path.parent()
+            && let Err(e) = fs::create_dir_all(dir)
+        {
+            eprintln!("waltopanel: can't create {}: {}", dir.display(), e);
+            return;
         }
+
+        match serde_json::to_string(cache) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&path, json) {
+                    eprintln!("waltopanel: can't write {}: {}", path.display(), e);
+                }
+            }
+            Err(e) => eprintln!("waltopanel: can't serialize weather cache: {}", e),
+        }
+    }
+
+    /// Geocode `location` unless it's the one already looked up, then fetch its weather
+    fn fetch_weather_blocking(location: &str) -> Result<(Place, WeatherData), String> {
+        let sources = (*SOURCES.lock().unwrap()).ok_or("weather service not started")?;
+        let client = reqwest::blocking::Client::builder()
+            .user_agent("WaltoPanel/1.0")
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let known_place = CURRENT_PLACE
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|(known_location, _)| known_location == location)
+            .map(|(_, place)| place.clone());
+        let place = match known_place {
+            Some(place) => place,
+            None => geocoder(sources.geocoder)
+                .geocode(&client, location)
+                .map_err(|e| format!("failed to geocode location '{}': {}", location, e))?,
+        };
+
+        let provider = provider(sources.provider);
+        let mut weather = provider
+            .fetch(&client, &place, sources.units)
+            .map_err(|e| format!("failed to fetch weather for '{}': {}", place.name, e))?;
+
+        // Alerts failing isn't worth losing the forecast over; keep showing the last ones
+        weather.alerts = match provider.fetch_alerts(&client, &place) {
+            Ok(alerts) => alerts,
+            Err(e) => {
+                eprintln!("waltopanel: failed to fetch weather alerts for '{}': {}", place.name, e);
+                CURRENT_WEATHER
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .filter(|current| current.location_name == place.name)
+                    .map(|current| current.alerts.clone())
+                    .unwrap_or_default()
+            }
+        };
+
+        Ok((place, weather))
     }
 }
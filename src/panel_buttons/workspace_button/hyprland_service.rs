@@ -1,267 +1,75 @@
-use gtk::glib;
 use serde_json::Value;
-use std::cell::RefCell;
-use std::collections::HashMap;
 use std::env;
+use std::fs;
 use std::io::{BufRead, BufReader, Read, Write};
 use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
-use std::rc::Rc;
-use std::thread;
-use std::time::Duration;
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct WorkspaceInfo {
-    pub id: i32,
-    pub name: String,
-    pub monitor: String,
-    pub windows: i32,
-    pub has_fullscreen: bool,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct WindowInfo {
-    pub address: String,
-    pub workspace_id: i32,
-    pub class: String,
-    pub title: String,
-    pub monitor: i32,
-    pub pid: i32,
-}
+use std::sync::Mutex;
 
-#[derive(Debug, Clone)]
-pub struct WorkspaceState {
-    pub workspaces: Vec<WorkspaceInfo>,
-    pub windows: Vec<WindowInfo>,
-    pub active_workspace_id: i32,
-    pub active_window_address: Option<String>,
-    pub _current_monitor: String,
-}
-
-type WorkspaceCallback = Box<dyn Fn(WorkspaceState)>;
+use super::workspace_backend::{
+    DpmsSupport, MonitorInfo, WindowInfo, WorkspaceBackend, WorkspaceEvent, WorkspaceInfo, WorkspaceUpdate,
+};
 
-struct MonitorSubscription {
-    monitor_name: String,
-    callback: WorkspaceCallback,
+/// Hyprland backend talking to `.socket.sock` and `.socket2.sock`
+pub struct HyprlandService {
+    /// The instance we talk to. Starts out from `$HYPRLAND_INSTANCE_SIGNATURE`, but a restarted
+    /// Hyprland gets a new signature that only shows up under `$XDG_RUNTIME_DIR/hypr`.
+    instance_signature: Mutex<Option<String>>,
 }
 
-struct HyprlandServiceState {
-    all_workspaces: Vec<WorkspaceInfo>,
-    all_windows: Vec<WindowInfo>,
-    active_workspace_id: i32,
-    active_window_address: Option<String>,
-    subscribers: Vec<MonitorSubscription>,
-    _running: bool,
-}
-
-thread_local! {
-    static HYPRLAND_SERVICE: RefCell<Option<Rc<RefCell<HyprlandServiceState>>>> = RefCell::new(None);
-}
-
-pub struct HyprlandService;
-
 impl HyprlandService {
-    /// Start the Hyprland service and return initial workspace state for a monitor
-    pub fn start(monitor_name: String) -> WorkspaceState {
-        HYPRLAND_SERVICE.with(|service| {
-            // If service already running, just return current state for this monitor
-            if let Some(state_ref) = service.borrow().as_ref() {
-                let state = state_ref.borrow();
-                return Self::filter_workspace_state_for_monitor(
-                    &state.all_workspaces,
-                    &state.all_windows,
-                    state.active_workspace_id,
-                    state.active_window_address.clone(),
-                    &monitor_name,
-                );
-            }
-
-            // First time initialization
-            let all_workspaces = Self::query_workspaces().unwrap_or_default();
-            let all_windows = Self::query_windows().unwrap_or_default();
-            let active_workspace_id = Self::query_active_workspace().unwrap_or(1);
-            let active_window_address = Self::query_active_window().unwrap_or(None);
-
-            let service_state = Rc::new(RefCell::new(HyprlandServiceState {
-                all_workspaces: all_workspaces.clone(),
-                all_windows: all_windows.clone(),
-                active_workspace_id,
-                active_window_address: active_window_address.clone(),
-                subscribers: Vec::new(),
-                _running: true,
-            }));
-
-            *service.borrow_mut() = Some(service_state.clone());
-
-            // Start event listener in a separate thread
-            Self::start_event_listener();
-            Self::start_dpms_watcher();
-
-            // Return filtered state for this monitor
-            Self::filter_workspace_state_for_monitor(
-                &all_workspaces,
-                &all_windows,
-                active_workspace_id,
-                active_window_address,
-                &monitor_name,
-            )
-        })
-    }
-
-    pub fn _stop() {
-        HYPRLAND_SERVICE.with(|service| {
-            if let Some(state) = service.borrow().as_ref() {
-                state.borrow_mut()._running = false;
-            }
-            *service.borrow_mut() = None;
-        });
-    }
-
-    pub fn subscribe<F>(monitor_name: String, callback: F)
-    where
-        F: Fn(WorkspaceState) + 'static,
-    {
-        HYPRLAND_SERVICE.with(|service| {
-            if let Some(state) = service.borrow().as_ref() {
-                state.borrow_mut().subscribers.push(MonitorSubscription {
-                    monitor_name,
-                    callback: Box::new(callback),
-                });
-            }
-        });
-    }
-
-    /// Switch to a workspace by ID
-    pub fn switch_workspace(workspace_id: i32) {
-        if let Err(e) = Self::send_command(&format!("dispatch workspace {}", workspace_id)) {
-            eprintln!("Failed to switch workspace: {}", e);
-        }
-    }
-
-    /// Focus a window by its address
-    pub fn focus_window(window_address: &str) {
-        if let Err(e) = Self::send_command(&format!("dispatch focuswindow address:{}", window_address)) {
-            eprintln!("Failed to focus window: {}", e);
+    pub fn new() -> Self {
+        Self {
+            instance_signature: Mutex::new(env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()),
         }
     }
 
-    /// Create a new workspace and switch to it, or switch to existing empty workspace
-    pub fn create_new_workspace_on_monitor(monitor_name: &str) {
-        // Check if this monitor already has an empty workspace
-        if let Ok(workspaces) = Self::query_workspaces() {
-            let empty_workspace = workspaces
-                .iter()
-                .find(|ws| ws.monitor == monitor_name && ws.windows == 0);
-
-            if let Some(empty_ws) = empty_workspace {
-                Self::switch_workspace(empty_ws.id);
-                return;
-            }
-        }
-
-        // No empty workspace on this monitor, create a new one
-        // Find the next available workspace ID
-        let workspaces = Self::query_workspaces().unwrap_or_default();
-        let max_id = workspaces.iter().map(|ws| ws.id).max().unwrap_or(0);
-        let new_workspace_id = max_id + 1;
-
-        // First, focus this monitor to ensure the workspace is created on the correct monitor
-        if let Err(e) = Self::send_command(&format!("dispatch focusmonitor {}", monitor_name)) {
-            eprintln!("Failed to focus monitor: {}", e);
-        }
+    fn get_hypr_dir() -> Result<PathBuf, String> {
+        let runtime_dir = env::var("XDG_RUNTIME_DIR")
+            .map_err(|_| "XDG_RUNTIME_DIR not set".to_string())?;
 
-        // Now switch to the new workspace ID (this will create it on the focused monitor)
-        if let Err(e) = Self::send_command(&format!("dispatch workspace {}", new_workspace_id)) {
-            eprintln!("Failed to create new workspace: {}", e);
-        }
+        Ok(PathBuf::from(runtime_dir).join("hypr"))
     }
 
-    /// Move all workspaces from external monitors to the first internal (eDP-*) display.
-    /// Intended to be called when switching a KVM switch away from this machine.
-    pub fn move_all_to_laptop() {
-        let monitors_json = match Self::send_command("j/monitors") {
-            Ok(j) => j,
-            Err(e) => { eprintln!("[HyprlandService] move_all_to_laptop: failed to query monitors: {}", e); return; }
-        };
-        let monitors: Value = match serde_json::from_str(&monitors_json) {
-            Ok(v) => v,
-            Err(e) => { eprintln!("[HyprlandService] move_all_to_laptop: failed to parse monitors: {}", e); return; }
-        };
-        let monitors_array = match monitors.as_array() {
-            Some(a) => a,
-            None => return,
-        };
-        let laptop = match monitors_array.iter().find_map(|m| {
-            let name = m["name"].as_str()?;
-            if name.starts_with("eDP") { Some(name.to_string()) } else { None }
-        }) {
-            Some(l) => l,
-            None => { eprintln!("[HyprlandService] move_all_to_laptop: no eDP monitor found"); return; }
-        };
-        let laptop_only = vec![laptop.clone()];
-        Self::move_orphaned_workspaces_to(&laptop, &laptop_only);
-    }
-
-    /// Move any workspaces assigned to a connector not in `active_connectors`
-    /// to `target_monitor`. Uses the GDK-provided connector list rather than
-    /// querying Hyprland's monitor list, because Hyprland's IPC can lag behind
-    /// GDK when multiple monitors disconnect simultaneously.
-    pub fn move_orphaned_workspaces_to(target_monitor: &str, active_connectors: &[String]) {
-        let workspaces_json = match Self::send_command("j/workspaces") {
-            Ok(r) => r,
-            Err(e) => { eprintln!("[HyprlandService] Failed to query workspaces: {}", e); return; }
-        };
+    fn get_instance_dir(&self) -> Result<PathBuf, String> {
+        let instance_sig = self.instance_signature.lock().unwrap().clone()
+            .ok_or_else(|| "No Hyprland instance signature".to_string())?;
 
-        let workspaces: Value = match serde_json::from_str(&workspaces_json) {
-            Ok(v) => v,
-            Err(e) => { eprintln!("[HyprlandService] Failed to parse workspaces: {}", e); return; }
-        };
-
-        if let Some(ws_array) = workspaces.as_array() {
-            for ws in ws_array {
-                if let (Some(ws_id), Some(ws_monitor)) = (ws["id"].as_i64(), ws["monitor"].as_str()) {
-                    if !active_connectors.contains(&ws_monitor.to_string()) {
-                        let _ = Self::send_command(&format!(
-                            "dispatch moveworkspacetomonitor {} {}",
-                            ws_id, target_monitor
-                        ));
-                    }
-                }
-            }
-        }
+        Ok(Self::get_hypr_dir()?.join(instance_sig))
     }
 
     /// Get the socket path for Hyprland IPC
-    fn get_socket_path() -> Result<PathBuf, String> {
-        let runtime_dir = env::var("XDG_RUNTIME_DIR")
-            .map_err(|_| "XDG_RUNTIME_DIR not set".to_string())?;
-
-        let instance_sig = env::var("HYPRLAND_INSTANCE_SIGNATURE")
-            .map_err(|_| "HYPRLAND_INSTANCE_SIGNATURE not set".to_string())?;
-
-        Ok(PathBuf::from(runtime_dir)
-            .join("hypr")
-            .join(instance_sig)
-            .join(".socket.sock"))
+    fn get_socket_path(&self) -> Result<PathBuf, String> {
+        Ok(self.get_instance_dir()?.join(".socket.sock"))
     }
 
     /// Get the event socket path for Hyprland IPC
-    fn get_event_socket_path() -> Result<PathBuf, String> {
-        let runtime_dir = env::var("XDG_RUNTIME_DIR")
-            .map_err(|_| "XDG_RUNTIME_DIR not set".to_string())?;
-
-        let instance_sig = env::var("HYPRLAND_INSTANCE_SIGNATURE")
-            .map_err(|_| "HYPRLAND_INSTANCE_SIGNATURE not set".to_string())?;
+    fn get_event_socket_path(&self) -> Result<PathBuf, String> {
+        Ok(self.get_instance_dir()?.join(".socket2.sock"))
+    }
 
-        Ok(PathBuf::from(runtime_dir)
-            .join("hypr")
-            .join(instance_sig)
-            .join(".socket2.sock"))
+    /// Find the newest instance under `$XDG_RUNTIME_DIR/hypr` whose socket accepts connections.
+    /// Crashed instances leave their directories behind, so existence alone isn't enough.
+    fn discover_instance() -> Result<String, String> {
+        let hypr_dir = Self::get_hypr_dir()?;
+        let entries = fs::read_dir(&hypr_dir)
+            .map_err(|e| format!("Failed to read {}: {}", hypr_dir.display(), e))?;
+
+        entries
+            .flatten()
+            .filter(|entry| UnixStream::connect(entry.path().join(".socket.sock")).is_ok())
+            .filter_map(|entry| {
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                Some((modified, entry.file_name().to_string_lossy().into_owned()))
+            })
+            .max()
+            .map(|(_, signature)| signature)
+            .ok_or_else(|| "No running Hyprland instance found".to_string())
     }
 
     /// Send a command to Hyprland
-    fn send_command(command: &str) -> Result<String, String> {
-        let socket_path = Self::get_socket_path()?;
+    fn send_command(&self, command: &str) -> Result<String, String> {
+        let socket_path = self.get_socket_path()?;
 
         let mut stream = UnixStream::connect(&socket_path)
             .map_err(|e| format!("Failed to connect to Hyprland socket: {}", e))?;
@@ -278,9 +86,153 @@ impl HyprlandService {
         Ok(response)
     }
 
+    /// Parse one `EVENT>>DATA` line from `.socket2.sock`. Hyprland sends most events in both a
+    /// v1 and a v2 form; only the v2 form is used where it exists since it carries workspace IDs.
+    fn parse_event(line: &str) -> Option<WorkspaceEvent> {
+        let (name, data) = line.split_once(">>")?;
+
+        let update = match name {
+            "monitoradded" | "monitorremoved" => return Some(WorkspaceEvent::MonitorsChanged),
+            "workspacev2" => {
+                let (id, _name) = data.split_once(',')?;
+                WorkspaceUpdate::WorkspaceFocused { id: id.parse().ok()? }
+            }
+            "focusedmonv2" => {
+                let (monitor, workspace_id) = data.split_once(',')?;
+                WorkspaceUpdate::MonitorFocused {
+                    monitor: monitor.to_string(),
+                    workspace_id: workspace_id.parse().ok()?,
+                }
+            }
+            "createworkspacev2" => {
+                let (id, name) = data.split_once(',')?;
+                let id: i32 = id.parse().ok()?;
+                // The event doesn't name the monitor; new workspaces open on the focused one
+                WorkspaceUpdate::WorkspaceCreated(WorkspaceInfo {
+                    id,
+                    index: Self::workspace_index(id),
+                    name: name.to_string(),
+                    monitor: String::new(),
+                    windows: 0,
+                    has_fullscreen: false,
+                    urgent: false,
+                    special: Self::is_special(name),
+                })
+            }
+            "destroyworkspacev2" => {
+                let (id, _name) = data.split_once(',')?;
+                WorkspaceUpdate::WorkspaceDestroyed { id: id.parse().ok()? }
+            }
+            "moveworkspacev2" => {
+                // Names may contain commas, monitor names don't
+                let (workspace, monitor) = data.rsplit_once(',')?;
+                let (id, _name) = workspace.split_once(',')?;
+                WorkspaceUpdate::WorkspaceMoved { id: id.parse().ok()?, monitor: monitor.to_string() }
+            }
+            "activespecialv2" => {
+                // ID and name are empty when the special workspace was hidden
+                let (workspace, monitor) = data.rsplit_once(',')?;
+                let (id, _name) = workspace.split_once(',')?;
+                WorkspaceUpdate::SpecialWorkspaceToggled { monitor: monitor.to_string(), id: id.parse().ok() }
+            }
+            "renameworkspace" => {
+                let (id, name) = data.split_once(',')?;
+                WorkspaceUpdate::WorkspaceRenamed { id: id.parse().ok()?, name: name.to_string() }
+            }
+            "openwindow" => {
+                // Titles may contain commas, so the title is everything after the third one
+                let mut fields = data.splitn(4, ',');
+                WorkspaceUpdate::WindowOpened {
+                    address: Self::window_address(fields.next()?),
+                    workspace_name: fields.next()?.to_string(),
+                    class: fields.next()?.to_string(),
+                    title: fields.next().unwrap_or_default().to_string(),
+                }
+            }
+            "closewindow" => WorkspaceUpdate::WindowClosed { address: Self::window_address(data) },
+            "movewindowv2" => {
+                let mut fields = data.splitn(3, ',');
+                let address = Self::window_address(fields.next()?);
+                WorkspaceUpdate::WindowMoved { address, workspace_id: fields.next()?.parse().ok()? }
+            }
+            "windowtitlev2" => {
+                let (address, title) = data.split_once(',')?;
+                WorkspaceUpdate::WindowTitleChanged {
+                    address: Self::window_address(address),
+                    title: title.to_string(),
+                }
+            }
+            "activewindowv2" => {
+                // Sent with an empty or "," payload when focus leaves all windows
+                let address = data.trim_matches(',');
+                WorkspaceUpdate::ActiveWindowChanged {
+                    address: (!address.is_empty()).then(|| Self::window_address(address)),
+                }
+            }
+            "urgent" => WorkspaceUpdate::WindowUrgent { address: Self::window_address(data) },
+            "fullscreen" => WorkspaceUpdate::FullscreenChanged { fullscreen: data == "1" },
+            _ => return None,
+        };
+
+        Some(WorkspaceEvent::Update(update))
+    }
+
+    /// Special workspaces are named `special:NAME`; they have negative IDs, as do named
+    /// workspaces
+    fn is_special(name: &str) -> bool {
+        name.starts_with("special:")
+    }
+
+    /// Numbered workspaces sort by ID. Named workspaces get IDs counting down from -1337 as
+    /// they're created, so negating puts them after the numbered ones in creation order.
+    fn workspace_index(id: i32) -> i32 {
+        id.abs()
+    }
+
+    /// How dispatchers refer to a workspace. A negative number would be read as relative to
+    /// the current workspace, so named workspaces are referred to by name.
+    fn workspace_selector(&self, workspace_id: i32) -> Result<String, String> {
+        if workspace_id > 0 {
+            return Ok(workspace_id.to_string());
+        }
+
+        self.query_workspaces()?
+            .into_iter()
+            .find(|ws| ws.id == workspace_id)
+            .map(|ws| if ws.special { ws.name } else { format!("name:{}", ws.name) })
+            .ok_or_else(|| format!("No workspace with ID {}", workspace_id))
+    }
+
+    /// Events give window addresses as bare hex, while `j/clients` prefixes them with 0x
+    fn window_address(raw: &str) -> String {
+        format!("0x{}", raw.trim_start_matches("0x"))
+    }
+
+    /// Send a dispatcher command, treating anything but "ok" as an error
+    fn dispatch(&self, dispatcher: &str) -> Result<(), String> {
+        let response = self.send_command(&format!("dispatch {}", dispatcher))?;
+        if response.trim() == "ok" {
+            Ok(())
+        } else {
+            Err(response)
+        }
+    }
+}
+
+impl Default for HyprlandService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WorkspaceBackend for HyprlandService {
+    fn name(&self) -> &'static str {
+        "Hyprland"
+    }
+
     /// Query workspace information from Hyprland
-    fn query_workspaces() -> Result<Vec<WorkspaceInfo>, String> {
-        let response = Self::send_command("j/workspaces")?;
+    fn query_workspaces(&self) -> Result<Vec<WorkspaceInfo>, String> {
+        let response = self.send_command("j/workspaces")?;
         let workspaces: Value = serde_json::from_str(&response)
             .map_err(|e| format!("Failed to parse JSON: {}. Response was: {}", e, response))?;
 
@@ -296,10 +248,14 @@ impl HyprlandService {
                 ) {
                     result.push(WorkspaceInfo {
                         id: id as i32,
+                        index: Self::workspace_index(id as i32),
                         name: name.to_string(),
                         monitor: monitor.to_string(),
                         windows: windows as i32,
                         has_fullscreen: ws["hasfullscreen"].as_bool().unwrap_or(false),
+                        // Only known from `urgent` events; the model keeps track of it
+                        urgent: false,
+                        special: Self::is_special(name),
                     });
                 }
             }
@@ -308,21 +264,9 @@ impl HyprlandService {
         Ok(result)
     }
 
-    /// Get the active workspace ID (globally focused)
-    fn query_active_workspace() -> Result<i32, String> {
-        let response = Self::send_command("j/activeworkspace")?;
-        let workspace: Value = serde_json::from_str(&response)
-            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
-
-        workspace["id"]
-            .as_i64()
-            .map(|id| id as i32)
-            .ok_or_else(|| "Failed to get active workspace ID".to_string())
-    }
-
     /// Query all windows from Hyprland
-    fn query_windows() -> Result<Vec<WindowInfo>, String> {
-        let response = Self::send_command("j/clients")?;
+    fn query_windows(&self) -> Result<Vec<WindowInfo>, String> {
+        let response = self.send_command("j/clients")?;
         let clients: Value = serde_json::from_str(&response)
             .map_err(|e| format!("Failed to parse clients JSON: {}. Response was: {}", e, response))?;
 
@@ -343,6 +287,8 @@ impl HyprlandService {
                         title: title.to_string(),
                         monitor: client["monitor"].as_i64().unwrap_or(0) as i32,
                         pid: client["pid"].as_i64().unwrap_or(0) as i32,
+                        position: 0,
+                        urgent: false,
                     });
                 }
             }
@@ -351,9 +297,54 @@ impl HyprlandService {
         Ok(result)
     }
 
+    /// Query monitors along with their active workspace and DPMS state
+    fn query_monitors(&self) -> Result<Vec<MonitorInfo>, String> {
+        let response = self.send_command("j/monitors")?;
+        let monitors: Value = serde_json::from_str(&response)
+            .map_err(|e| format!("Failed to parse monitors JSON: {}", e))?;
+
+        Ok(monitors
+            .as_array()
+            .map(|monitors_array| {
+                monitors_array
+                    .iter()
+                    .filter_map(|monitor| {
+                        Some(MonitorInfo {
+                            name: monitor["name"].as_str()?.to_string(),
+                            active_workspace_id: monitor["activeWorkspace"]["id"].as_i64().map(|id| id as i32),
+                            // Reported with ID 0 when no special workspace is shown
+                            special_workspace_id: monitor["specialWorkspace"]["id"]
+                                .as_i64()
+                                .filter(|id| *id != 0)
+                                .map(|id| id as i32),
+                            dpms_on: monitor["dpmsStatus"].as_bool().unwrap_or(true),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Hyprland's event socket has no DPMS event
+    fn dpms_support(&self) -> DpmsSupport {
+        DpmsSupport::Polled
+    }
+
+    /// Get the active workspace ID (globally focused)
+    fn query_active_workspace(&self) -> Result<i32, String> {
+        let response = self.send_command("j/activeworkspace")?;
+        let workspace: Value = serde_json::from_str(&response)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        workspace["id"]
+            .as_i64()
+            .map(|id| id as i32)
+            .ok_or_else(|| "Failed to get active workspace ID".to_string())
+    }
+
     /// Get the currently focused window address
-    fn query_active_window() -> Result<Option<String>, String> {
-        let response = Self::send_command("j/activewindow")?;
+    fn query_active_window(&self) -> Result<Option<String>, String> {
+        let response = self.send_command("j/activewindow")?;
         let window: Value = serde_json::from_str(&response)
             .map_err(|e| format!("Failed to parse active window JSON: {}", e))?;
 
@@ -367,258 +358,256 @@ impl HyprlandService {
         Ok(None)
     }
 
-    /// Get the active workspace ID for a specific monitor
-    fn query_active_workspace_for_monitor(monitor_name: &str) -> Result<i32, String> {
-        let response = Self::send_command("j/monitors")?;
-        let monitors: Value = serde_json::from_str(&response)
-            .map_err(|e| format!("Failed to parse monitors JSON: {}", e))?;
+    fn switch_workspace(&self, workspace_id: i32) -> Result<(), String> {
+        self.dispatch(&format!("workspace {}", self.workspace_selector(workspace_id)?))
+    }
 
-        if let Some(monitors_array) = monitors.as_array() {
-            for monitor in monitors_array {
-                if let Some(name) = monitor["name"].as_str() {
-                    if name == monitor_name {
-                        if let Some(workspace_id) = monitor["activeWorkspace"]["id"].as_i64() {
-                            return Ok(workspace_id as i32);
-                        }
-                    }
-                }
+    fn toggle_special_workspace(&self, workspace_name: &str) -> Result<(), String> {
+        let name = workspace_name.strip_prefix("special:").unwrap_or(workspace_name);
+        self.dispatch(&format!("togglespecialworkspace {}", name))
+    }
+
+    fn focus_window(&self, window_address: &str) -> Result<(), String> {
+        self.dispatch(&format!("focuswindow address:{}", window_address))
+    }
+
+    fn close_window(&self, window_address: &str) -> Result<(), String> {
+        self.dispatch(&format!("closewindow address:{}", window_address))
+    }
+
+    fn can_arrange_windows(&self) -> bool {
+        true
+    }
+
+    fn toggle_floating(&self, window_address: &str) -> Result<(), String> {
+        self.dispatch(&format!("togglefloating address:{}", window_address))
+    }
+
+    fn toggle_fullscreen(&self, window_address: &str) -> Result<(), String> {
+        // The fullscreen dispatcher only acts on the focused window
+        self.focus_window(window_address)?;
+        self.dispatch("fullscreen 0")
+    }
+
+    fn move_window_to_workspace(&self, window_address: &str, workspace_id: i32) -> Result<(), String> {
+        self.dispatch(&format!(
+            "movetoworkspacesilent {},address:{}",
+            self.workspace_selector(workspace_id)?,
+            window_address
+        ))
+    }
+
+    fn create_workspace_on_monitor(&self, monitor_name: &str, workspace_id: i32) -> Result<(), String> {
+        // First, focus this monitor to ensure the workspace is created on the correct monitor
+        self.dispatch(&format!("focusmonitor {}", monitor_name))?;
+
+        // Now switch to the new workspace ID (this will create it on the focused monitor)
+        self.dispatch(&format!("workspace {}", workspace_id))
+    }
+
+    fn move_workspace_to_monitor(&self, workspace_id: i32, monitor_name: &str) -> Result<(), String> {
+        self.dispatch(&format!(
+            "moveworkspacetomonitor {} {}",
+            self.workspace_selector(workspace_id)?,
+            monitor_name
+        ))
+    }
+
+    /// Keep the current instance if it still answers, otherwise switch to whichever instance
+    /// is running now (e.g. after Hyprland was restarted)
+    fn reconnect(&self) -> Result<(), String> {
+        if self.get_socket_path().is_ok_and(|path| UnixStream::connect(path).is_ok()) {
+            return Ok(());
+        }
+
+        let signature = Self::discover_instance()?;
+        eprintln!("[HyprlandService] Switching to Hyprland instance {}", signature);
+        *self.instance_signature.lock().unwrap() = Some(signature);
+        Ok(())
+    }
+
+    /// Listen to Hyprland events on the event socket
+    fn listen(&self, on_event: &dyn Fn(WorkspaceEvent)) -> Result<(), String> {
+        let event_socket_path = self.get_event_socket_path()?;
+
+        let stream = UnixStream::connect(&event_socket_path)
+            .map_err(|e| format!("Failed to connect to Hyprland event socket: {}", e))?;
+
+        let reader = BufReader::new(stream);
+
+        for line in reader.lines() {
+            let line = line.map_err(|e| format!("Failed to read Hyprland event: {}", e))?;
+
+            if let Some(event) = Self::parse_event(&line) {
+                on_event(event);
             }
         }
 
-        Err(format!("Could not find active workspace for monitor {}", monitor_name))
-    }
-
-    /// Filter workspace state for a specific monitor
-    fn filter_workspace_state_for_monitor(
-        all_workspaces: &[WorkspaceInfo],
-        all_windows: &[WindowInfo],
-        _global_active_workspace_id: i32,
-        active_window_address: Option<String>,
-        monitor_name: &str,
-    ) -> WorkspaceState {
-        // Filter workspaces for this monitor and apply visibility rules
-        let monitor_workspaces = Self::filter_visible_workspaces(all_workspaces, monitor_name);
-
-        // Get workspace IDs for this monitor
-        let monitor_workspace_ids: Vec<i32> = monitor_workspaces.iter().map(|ws| ws.id).collect();
-
-        // Filter windows to only those on this monitor's workspaces
-        let monitor_windows: Vec<WindowInfo> = all_windows
-            .iter()
-            .filter(|w| monitor_workspace_ids.contains(&w.workspace_id))
-            .cloned()
-            .collect();
-
-        // Get the active workspace for THIS monitor specifically
-        let active_workspace_id = Self::query_active_workspace_for_monitor(monitor_name)
-            .unwrap_or(_global_active_workspace_id);
-
-        WorkspaceState {
-            workspaces: monitor_workspaces,
-            windows: monitor_windows,
-            active_workspace_id,
-            active_window_address,
-            _current_monitor: monitor_name.to_string(),
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(line: &str) -> Option<WorkspaceUpdate> {
+        match HyprlandService::parse_event(line)? {
+            WorkspaceEvent::Update(update) => Some(update),
+            other => panic!("{} parsed as {:?}", line, other),
+        }
+    }
+
+    fn workspace(id: i32, name: &str, special: bool) -> WorkspaceUpdate {
+        WorkspaceUpdate::WorkspaceCreated(WorkspaceInfo {
+            id,
+            index: id.abs(),
+            name: name.to_string(),
+            monitor: String::new(),
+            windows: 0,
+            has_fullscreen: false,
+            urgent: false,
+            special,
+        })
+    }
+
+    #[test]
+    fn parses_monitor_events() {
+        for line in ["monitoradded>>DP-1", "monitorremoved>>HDMI-A-1"] {
+            assert_eq!(HyprlandService::parse_event(line), Some(WorkspaceEvent::MonitorsChanged));
         }
     }
 
-    /// Filter workspaces according to the visibility rules:
-    /// - Show workspaces with windows
-    /// - Keep only one empty workspace per monitor
-    fn filter_visible_workspaces(
-        workspaces: &[WorkspaceInfo],
-        monitor_name: &str,
-    ) -> Vec<WorkspaceInfo> {
-        let mut monitor_workspaces: Vec<WorkspaceInfo> = workspaces
-            .iter()
-            .filter(|ws| ws.monitor == monitor_name)
-            .cloned()
-            .collect();
-
-        // Sort by ID
-        monitor_workspaces.sort_by_key(|ws| ws.id);
-
-        // Count empty workspaces
-        let empty_workspaces: Vec<&WorkspaceInfo> = monitor_workspaces
-            .iter()
-            .filter(|ws| ws.windows == 0)
-            .collect();
-
-        // If there are multiple empty workspaces, keep only the first one
-        if empty_workspaces.len() > 1 {
-            let first_empty_id = empty_workspaces[0].id;
-            monitor_workspaces.retain(|ws| ws.windows > 0 || ws.id == first_empty_id);
+    #[test]
+    fn parses_v2_events() {
+        let cases = [
+            ("workspacev2>>3,3", WorkspaceUpdate::WorkspaceFocused { id: 3 }),
+            ("workspacev2>>-1337,web, mail", WorkspaceUpdate::WorkspaceFocused { id: -1337 }),
+            (
+                "focusedmonv2>>DP-2,4",
+                WorkspaceUpdate::MonitorFocused { monitor: "DP-2".to_string(), workspace_id: 4 },
+            ),
+            ("createworkspacev2>>5,5", workspace(5, "5", false)),
+            ("createworkspacev2>>-1337,chat, work", workspace(-1337, "chat, work", false)),
+            ("createworkspacev2>>-98,special:scratch", workspace(-98, "special:scratch", true)),
+            ("destroyworkspacev2>>5,5", WorkspaceUpdate::WorkspaceDestroyed { id: 5 }),
+            ("destroyworkspacev2>>-1337,chat, work", WorkspaceUpdate::WorkspaceDestroyed { id: -1337 }),
+            (
+                "moveworkspacev2>>2,2,HDMI-A-1",
+                WorkspaceUpdate::WorkspaceMoved { id: 2, monitor: "HDMI-A-1".to_string() },
+            ),
+            (
+                "moveworkspacev2>>-1337,chat, work,DP-1",
+                WorkspaceUpdate::WorkspaceMoved { id: -1337, monitor: "DP-1".to_string() },
+            ),
+            (
+                "activespecialv2>>-98,special:scratch,DP-1",
+                WorkspaceUpdate::SpecialWorkspaceToggled { monitor: "DP-1".to_string(), id: Some(-98) },
+            ),
+            (
+                "activespecialv2>>,,DP-1",
+                WorkspaceUpdate::SpecialWorkspaceToggled { monitor: "DP-1".to_string(), id: None },
+            ),
+            (
+                "renameworkspace>>3,code, tests",
+                WorkspaceUpdate::WorkspaceRenamed { id: 3, name: "code, tests".to_string() },
+            ),
+            (
+                "openwindow>>55d0a1b2c3d4,2,firefox,Inbox, 3 unread — Mozilla Firefox",
+                WorkspaceUpdate::WindowOpened {
+                    address: "0x55d0a1b2c3d4".to_string(),
+                    workspace_name: "2".to_string(),
+                    class: "firefox".to_string(),
+                    title: "Inbox, 3 unread — Mozilla Firefox".to_string(),
+                },
+            ),
+            (
+                "openwindow>>55d0a1b2c3d4,2,kitty,",
+                WorkspaceUpdate::WindowOpened {
+                    address: "0x55d0a1b2c3d4".to_string(),
+                    workspace_name: "2".to_string(),
+                    class: "kitty".to_string(),
+                    title: String::new(),
+                },
+            ),
+            ("closewindow>>55d0a1b2c3d4", WorkspaceUpdate::WindowClosed { address: "0x55d0a1b2c3d4".to_string() }),
+            (
+                "movewindowv2>>55d0a1b2c3d4,7,7",
+                WorkspaceUpdate::WindowMoved { address: "0x55d0a1b2c3d4".to_string(), workspace_id: 7 },
+            ),
+            (
+                "movewindowv2>>55d0a1b2c3d4,-1337,chat, work",
+                WorkspaceUpdate::WindowMoved { address: "0x55d0a1b2c3d4".to_string(), workspace_id: -1337 },
+            ),
+            (
+                "windowtitlev2>>55d0a1b2c3d4,vim src/main.rs, [+]",
+                WorkspaceUpdate::WindowTitleChanged {
+                    address: "0x55d0a1b2c3d4".to_string(),
+                    title: "vim src/main.rs, [+]".to_string(),
+                },
+            ),
+            (
+                "activewindowv2>>55d0a1b2c3d4",
+                WorkspaceUpdate::ActiveWindowChanged { address: Some("0x55d0a1b2c3d4".to_string()) },
+            ),
+            ("activewindowv2>>", WorkspaceUpdate::ActiveWindowChanged { address: None }),
+            ("activewindowv2>>,", WorkspaceUpdate::ActiveWindowChanged { address: None }),
+            ("urgent>>55d0a1b2c3d4", WorkspaceUpdate::WindowUrgent { address: "0x55d0a1b2c3d4".to_string() }),
+            ("fullscreen>>1", WorkspaceUpdate::FullscreenChanged { fullscreen: true }),
+            ("fullscreen>>0", WorkspaceUpdate::FullscreenChanged { fullscreen: false }),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(update(line), Some(expected), "{}", line);
         }
+    }
 
-        monitor_workspaces
-    }
-
-    /// Notify all subscribers with updated workspace states
-    fn notify_subscribers() {
-        HYPRLAND_SERVICE.with(|service| {
-            if let Some(state_ref) = service.borrow().as_ref() {
-                let state = state_ref.borrow();
-                let all_workspaces = state.all_workspaces.clone();
-                let all_windows = state.all_windows.clone();
-                let active_workspace_id = state.active_workspace_id;
-                let active_window_address = state.active_window_address.clone();
-
-                // Notify each subscriber with their monitor-specific state
-                for sub in &state.subscribers {
-                    let monitor_state = Self::filter_workspace_state_for_monitor(
-                        &all_workspaces,
-                        &all_windows,
-                        active_workspace_id,
-                        active_window_address.clone(),
-                        &sub.monitor_name,
-                    );
-                    (sub.callback)(monitor_state);
-                }
-            }
-        });
+    #[test]
+    fn ignores_v1_and_unknown_events() {
+        for line in [
+            "workspace>>3",
+            "focusedmon>>DP-1,3",
+            "createworkspace>>5",
+            "destroyworkspace>>5",
+            "moveworkspace>>2,HDMI-A-1",
+            "activespecial>>special:scratch,DP-1",
+            "movewindow>>55d0a1b2c3d4,7",
+            "windowtitle>>55d0a1b2c3d4",
+            "activewindow>>kitty,~",
+            "submap>>resize",
+        ] {
+            assert_eq!(HyprlandService::parse_event(line), None, "{}", line);
+        }
     }
 
-    /// Start listening to Hyprland events
-    fn start_event_listener() {
-        thread::spawn(move || {
-            let event_socket_path = match Self::get_event_socket_path() {
-                Ok(path) => path,
-                Err(e) => {
-                    eprintln!("Failed to get event socket path: {}", e);
-                    return;
-                }
-            };
+    #[test]
+    fn rejects_malformed_lines() {
+        for line in [
+            "",
+            "garbage",
+            "workspacev2",
+            "workspacev2>>3",
+            "workspacev2>>three,3",
+            "focusedmonv2>>DP-1",
+            "focusedmonv2>>DP-1,",
+            "createworkspacev2>>x,x",
+            "destroyworkspacev2>>",
+            "moveworkspacev2>>2,HDMI-A-1",
+            "moveworkspacev2>>two,2,HDMI-A-1",
+            "activespecialv2>>DP-1",
+            "renameworkspace>>three",
+            "openwindow>>55d0a1b2c3d4,2",
+            "movewindowv2>>55d0a1b2c3d4",
+            "movewindowv2>>55d0a1b2c3d4,seven,7",
+            "windowtitlev2>>55d0a1b2c3d4",
+        ] {
+            assert_eq!(HyprlandService::parse_event(line), None, "{:?}", line);
+        }
+    }
 
-            let stream = match UnixStream::connect(&event_socket_path) {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!("Failed to connect to Hyprland event socket: {}", e);
-                    return;
-                }
-            };
-
-            let reader = BufReader::new(stream);
-
-            for line in reader.lines() {
-                if let Ok(event) = line {
-                    // When Hyprland removes a monitor, immediately move orphaned workspaces
-                    // in the background thread. GDK doesn't always fire items_changed for
-                    // all monitor disconnections (e.g. DDC/CI keeps DP-2 "connected" in GDK).
-                    if event.starts_with("monitorremoved>>") {
-                        // At this point Hyprland has already removed the monitor from its
-                        // internal list, so j/monitors will give us the authoritative set.
-                        if let Ok(monitors_json) = Self::send_command("j/monitors") {
-                            if let Ok(monitors_val) = serde_json::from_str::<serde_json::Value>(&monitors_json) {
-                                let active: Vec<String> = monitors_val
-                                    .as_array()
-                                    .unwrap_or(&vec![])
-                                    .iter()
-                                    .filter_map(|m| m["name"].as_str().map(|s| s.to_string()))
-                                    .collect();
-                                if let Some(target) = active.first().cloned() {
-                                    Self::move_orphaned_workspaces_to(&target, &active);
-                                }
-                            }
-                        }
-                    }
-
-                    // Check if this is a workspace, window, or monitor-related event
-                    if event.starts_with("workspace>>")
-                        || event.starts_with("createworkspace>>")
-                        || event.starts_with("destroyworkspace>>")
-                        || event.starts_with("moveworkspace>>")
-                        || event.starts_with("moveworkspacev2>>")
-                        || event.starts_with("openwindow>>")
-                        || event.starts_with("closewindow>>")
-                        || event.starts_with("movewindow>>")
-                        || event.starts_with("activewindow>>")
-                        || event.starts_with("monitorremoved>>")
-                        || event.starts_with("monitoradded>>")
-                    {
-                        // Schedule the state update on the main thread
-                        glib::idle_add_once(move || {
-                            // Update global workspace and window state
-                            let all_workspaces = Self::query_workspaces().unwrap_or_default();
-                            let all_windows = Self::query_windows().unwrap_or_default();
-                            let active_workspace_id =
-                                Self::query_active_workspace().unwrap_or(1);
-                            let active_window_address = Self::query_active_window().unwrap_or(None);
-
-                            HYPRLAND_SERVICE.with(|service| {
-                                if let Some(state_ref) = service.borrow().as_ref() {
-                                    let mut state = state_ref.borrow_mut();
-                                    state.all_workspaces = all_workspaces;
-                                    state.all_windows = all_windows;
-                                    state.active_workspace_id = active_workspace_id;
-                                    state.active_window_address = active_window_address;
-                                }
-                            });
-
-                            // Notify all subscribers
-                            Self::notify_subscribers();
-                        });
-                    }
-                }
-            }
-        });
-    }
-
-    /// Poll monitor DPMS status every 2 seconds. When an external monitor transitions
-    /// from DPMS-on to DPMS-off (e.g. KVM switch), move its workspaces to the first
-    /// monitor that still has DPMS on.
-    fn start_dpms_watcher() {
-        thread::spawn(move || {
-            // Give Hyprland a moment to settle before we start polling.
-            thread::sleep(Duration::from_secs(3));
-
-            let mut prev_dpms: HashMap<String, bool> = HashMap::new();
-
-            loop {
-                thread::sleep(Duration::from_secs(2));
-
-                let monitors_json = match Self::send_command("j/monitors") {
-                    Ok(j) => j,
-                    Err(_) => continue,
-                };
-
-                let monitors: Value = match serde_json::from_str(&monitors_json) {
-                    Ok(v) => v,
-                    Err(_) => continue,
-                };
-
-                let monitors_array = match monitors.as_array() {
-                    Some(a) => a.clone(),
-                    None => continue,
-                };
-
-                // Collect monitors that currently have DPMS on.
-                let dpms_on: Vec<String> = monitors_array.iter()
-                    .filter_map(|m| {
-                        let name = m["name"].as_str()?.to_string();
-                        let dpms = m["dpmsStatus"].as_bool().unwrap_or(true);
-                        if dpms { Some(name) } else { None }
-                    })
-                    .collect();
-
-                for monitor in &monitors_array {
-                    let name = match monitor["name"].as_str() {
-                        Some(n) => n.to_string(),
-                        None => continue,
-                    };
-                    let dpms = monitor["dpmsStatus"].as_bool().unwrap_or(true);
-                    let was_on = *prev_dpms.get(&name).unwrap_or(&true);
-
-                    if was_on && !dpms {
-                        // This monitor just lost DPMS — KVM likely switched away.
-                        eprintln!("[HyprlandService] DPMS off on {}, moving workspaces to {:?}", name, dpms_on.first());
-                        if let Some(target) = dpms_on.first().cloned() {
-                            Self::move_orphaned_workspaces_to(&target, &dpms_on);
-                        }
-                    }
-
-                    prev_dpms.insert(name, dpms);
-                }
-            }
-        });
+    #[test]
+    fn window_addresses_get_one_prefix() {
+        assert_eq!(HyprlandService::window_address("55d0a1b2c3d4"), "0x55d0a1b2c3d4");
+        assert_eq!(HyprlandService::window_address("0x55d0a1b2c3d4"), "0x55d0a1b2c3d4");
     }
 }
@@ -1,4 +1,13 @@
-pub mod hyprland_service;
+mod hyprland_service;
+mod niri_service;
+mod screencopy;
+mod sway_service;
+mod wayland_toplevel_service;
+pub mod workspace_backend;
+mod workspace_homes;
+mod workspace_model;
+mod workspace_preview;
+pub mod workspace_service;
 mod workspace_button;
 
 pub use workspace_button::WorkspaceButton;
@@ -0,0 +1,295 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use super::workspace_backend::{MonitorInfo, WindowInfo, WorkspaceBackend, WorkspaceEvent, WorkspaceInfo};
+
+/// niri backend talking to the JSON IPC socket from `$NIRI_SOCKET`.
+/// niri gives every output its own dynamic list of workspaces, always ending in an empty one,
+/// and lays windows out in scrolling columns within a workspace.
+pub struct NiriService {
+    socket_path: PathBuf,
+}
+
+impl NiriService {
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self { socket_path }
+    }
+
+    /// Write one request and return a reader positioned after its reply
+    fn open_request(&self, request: &Value) -> Result<(Value, BufReader<UnixStream>), String> {
+        let mut stream = UnixStream::connect(&self.socket_path)
+            .map_err(|e| format!("Failed to connect to niri socket: {}", e))?;
+
+        stream
+            .write_all(format!("{}\n", request).as_bytes())
+            .map_err(|e| format!("Failed to write to socket: {}", e))?;
+
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+
+        let reply: Value = serde_json::from_str(&line)
+            .map_err(|e| format!("Failed to parse JSON: {}. Response was: {}", e, line))?;
+
+        // Replies are either {"Ok": ...} or {"Err": "message"}
+        if let Some(error) = reply.get("Err") {
+            return Err(error.as_str().unwrap_or("unknown error").to_string());
+        }
+
+        Ok((reply["Ok"].clone(), reader))
+    }
+
+    /// Send a request and return the payload of its reply variant, e.g. the array in
+    /// `{"Ok": {"Workspaces": [...]}}` for the "Workspaces" request
+    fn request(&self, request: &str) -> Result<Value, String> {
+        let (reply, _) = self.open_request(&json!(request))?;
+        Ok(reply[request].clone())
+    }
+
+    fn action(&self, action: Value) -> Result<(), String> {
+        self.open_request(&json!({ "Action": action })).map(|_| ())
+    }
+
+    fn workspace_reference(workspace_id: i32) -> Value {
+        json!({ "Id": workspace_id })
+    }
+
+    fn window_id(window_address: &str) -> Result<u64, String> {
+        window_address
+            .parse()
+            .map_err(|_| format!("Invalid niri window id: {}", window_address))
+    }
+
+    /// Output names in a stable order, used to give windows a numeric monitor
+    fn output_names(&self) -> Result<Vec<String>, String> {
+        let outputs = self.request("Outputs")?;
+        let mut names: Vec<String> = outputs
+            .as_object()
+            .map(|outputs| outputs.keys().cloned().collect())
+            .unwrap_or_default();
+        names.sort();
+        Ok(names)
+    }
+}
+
+impl WorkspaceBackend for NiriService {
+    fn name(&self) -> &'static str {
+        "niri"
+    }
+
+    fn query_workspaces(&self) -> Result<Vec<WorkspaceInfo>, String> {
+        let workspaces = self.request("Workspaces")?;
+
+        // Workspaces carry no window counts, so count them from the window list
+        let mut window_counts: HashMap<i32, i32> = HashMap::new();
+        for window in self.query_windows()? {
+            *window_counts.entry(window.workspace_id).or_insert(0) += 1;
+        }
+
+        let mut result = Vec::new();
+
+        if let Some(ws_array) = workspaces.as_array() {
+            for ws in ws_array {
+                if let (Some(id), Some(idx), Some(monitor)) = (
+                    ws["id"].as_i64(),
+                    ws["idx"].as_i64(),
+                    ws["output"].as_str(),
+                ) {
+                    let id = id as i32;
+                    result.push(WorkspaceInfo {
+                        id,
+                        index: idx as i32,
+                        name: ws["name"].as_str().map(|s| s.to_string()).unwrap_or_else(|| idx.to_string()),
+                        monitor: monitor.to_string(),
+                        windows: window_counts.get(&id).copied().unwrap_or(0),
+                        has_fullscreen: false,
+                        urgent: ws["is_urgent"].as_bool().unwrap_or(false),
+                        special: false,
+                    });
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn query_windows(&self) -> Result<Vec<WindowInfo>, String> {
+        let windows = self.request("Windows")?;
+        let workspaces = self.request("Workspaces")?;
+        let output_names = self.output_names()?;
+
+        let workspace_monitor = |workspace_id: i64| -> i32 {
+            workspaces.as_array()
+                .and_then(|ws_array| ws_array.iter().find(|ws| ws["id"].as_i64() == Some(workspace_id)))
+                .and_then(|ws| ws["output"].as_str())
+                .and_then(|output| output_names.iter().position(|name| name == output))
+                .unwrap_or(0) as i32
+        };
+
+        let mut result = Vec::new();
+
+        if let Some(windows_array) = windows.as_array() {
+            for window in windows_array {
+                // Windows without a workspace aren't shown anywhere on the bar
+                if let (Some(id), Some(workspace_id)) = (
+                    window["id"].as_i64(),
+                    window["workspace_id"].as_i64(),
+                ) {
+                    result.push(WindowInfo {
+                        address: id.to_string(),
+                        workspace_id: workspace_id as i32,
+                        class: window["app_id"].as_str().unwrap_or_default().to_string(),
+                        title: window["title"].as_str().unwrap_or_default().to_string(),
+                        monitor: workspace_monitor(workspace_id),
+                        pid: window["pid"].as_i64().unwrap_or(0) as i32,
+                        // [column, tile] for tiled windows; absent for floating windows and older niri
+                        position: window["layout"]["pos_in_scrolling_layout"][0].as_i64().unwrap_or(0) as i32,
+                        urgent: window["is_urgent"].as_bool().unwrap_or(false),
+                    });
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn query_monitors(&self) -> Result<Vec<MonitorInfo>, String> {
+        let workspaces = self.request("Workspaces")?;
+
+        let active_workspace = |output_name: &str| {
+            workspaces.as_array()?.iter()
+                .find(|ws| ws["output"].as_str() == Some(output_name) && ws["is_active"].as_bool() == Some(true))
+                .and_then(|ws| ws["id"].as_i64())
+                .map(|id| id as i32)
+        };
+
+        Ok(self
+            .output_names()?
+            .into_iter()
+            .map(|name| MonitorInfo {
+                active_workspace_id: active_workspace(&name),
+                special_workspace_id: None,
+                name,
+                // niri doesn't report power state over IPC
+                dpms_on: true,
+            })
+            .collect())
+    }
+
+    fn queries_urgency(&self) -> bool {
+        true
+    }
+
+    fn query_active_workspace(&self) -> Result<i32, String> {
+        let workspaces = self.request("Workspaces")?;
+
+        workspaces
+            .as_array()
+            .and_then(|ws_array| ws_array.iter().find(|ws| ws["is_focused"].as_bool() == Some(true)))
+            .and_then(|ws| ws["id"].as_i64())
+            .map(|id| id as i32)
+            .ok_or_else(|| "Failed to get active workspace ID".to_string())
+    }
+
+    fn query_active_window(&self) -> Result<Option<String>, String> {
+        let window = self.request("FocusedWindow")?;
+        Ok(window["id"].as_i64().map(|id| id.to_string()))
+    }
+
+    fn switch_workspace(&self, workspace_id: i32) -> Result<(), String> {
+        self.action(json!({
+            "FocusWorkspace": { "reference": Self::workspace_reference(workspace_id) }
+        }))
+    }
+
+    fn focus_window(&self, window_address: &str) -> Result<(), String> {
+        self.action(json!({ "FocusWindow": { "id": Self::window_id(window_address)? } }))
+    }
+
+    fn close_window(&self, window_address: &str) -> Result<(), String> {
+        self.action(json!({ "CloseWindow": { "id": Self::window_id(window_address)? } }))
+    }
+
+    fn can_arrange_windows(&self) -> bool {
+        true
+    }
+
+    fn toggle_floating(&self, window_address: &str) -> Result<(), String> {
+        self.action(json!({ "ToggleWindowFloating": { "id": Self::window_id(window_address)? } }))
+    }
+
+    fn toggle_fullscreen(&self, window_address: &str) -> Result<(), String> {
+        self.action(json!({ "FullscreenWindow": { "id": Self::window_id(window_address)? } }))
+    }
+
+    fn move_window_to_workspace(&self, window_address: &str, workspace_id: i32) -> Result<(), String> {
+        self.action(json!({
+            "MoveWindowToWorkspace": {
+                "window_id": Self::window_id(window_address)?,
+                "reference": Self::workspace_reference(workspace_id),
+                "focus": false,
+            }
+        }))
+    }
+
+    /// niri creates workspaces on demand and always keeps an empty one at the end of
+    /// each output, so "creating" a workspace means focusing that trailing workspace
+    fn create_workspace_on_monitor(&self, monitor_name: &str, _workspace_id: i32) -> Result<(), String> {
+        let last_workspace = self
+            .query_workspaces()?
+            .into_iter()
+            .filter(|ws| ws.monitor == monitor_name)
+            .max_by_key(|ws| ws.index)
+            .ok_or_else(|| format!("No workspaces on output {}", monitor_name))?;
+
+        self.action(json!({ "FocusMonitor": { "output": monitor_name } }))?;
+        self.switch_workspace(last_workspace.id)
+    }
+
+    fn move_workspace_to_monitor(&self, workspace_id: i32, monitor_name: &str) -> Result<(), String> {
+        self.action(json!({
+            "MoveWorkspaceToMonitor": {
+                "output": monitor_name,
+                "reference": Self::workspace_reference(workspace_id),
+            }
+        }))
+    }
+
+    fn listen(&self, on_event: &dyn Fn(WorkspaceEvent)) -> Result<(), String> {
+        let (_, reader) = self.open_request(&json!("EventStream"))?;
+
+        for line in reader.lines() {
+            let line = line.map_err(|e| format!("Failed to read niri event: {}", e))?;
+            let event: Value = match serde_json::from_str(&line) {
+                Ok(event) => event,
+                Err(_) => continue,
+            };
+
+            // Each event is an object with a single key naming the event
+            let Some(name) = event.as_object().and_then(|event| event.keys().next()) else {
+                continue;
+            };
+
+            match name.as_str() {
+                "WorkspacesChanged"
+                | "WorkspaceActivated"
+                | "WorkspaceActiveWindowChanged"
+                | "WindowsChanged"
+                | "WindowOpenedOrChanged"
+                | "WindowClosed"
+                | "WindowFocusChanged"
+                | "WindowLayoutsChanged"
+                | "WindowUrgencyChanged"
+                | "WorkspaceUrgencyChanged" => on_event(WorkspaceEvent::Changed),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
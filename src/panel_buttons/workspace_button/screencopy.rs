@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::fd::{AsFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::FileExt;
+
+use wayland_client::backend::ObjectId;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::wl_buffer::{self, WlBuffer};
+use wayland_client::protocol::wl_output::{self, WlOutput};
+use wayland_client::protocol::wl_registry::{self, WlRegistry};
+use wayland_client::protocol::wl_shm::{self, WlShm};
+use wayland_client::protocol::wl_shm_pool::{self, WlShmPool};
+use wayland_client::{Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum};
+use wayland_protocols_wlr::screencopy::v1::client::zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1};
+use wayland_protocols_wlr::screencopy::v1::client::zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1};
+
+/// A copy of a monitor's contents, as 8-bit BGRA rows (B, G, R, A in memory) with alpha
+/// forced opaque
+pub struct OutputCapture {
+    pub width: i32,
+    pub height: i32,
+    pub stride: usize,
+    pub data: Vec<u8>,
+}
+
+#[derive(Default)]
+struct CaptureState {
+    output_names: HashMap<ObjectId, String>,
+    /// Format, width, height and stride of the shm buffer the compositor wants
+    buffer: Option<(wl_shm::Format, u32, u32, u32)>,
+    buffer_done: bool,
+    y_invert: bool,
+    /// Set once the copy has finished or failed
+    finished: Option<Result<(), String>>,
+}
+
+/// Copy the current contents of the named monitor with wlr-screencopy. Uses its own Wayland
+/// connection so it can run on a background thread; fails if the compositor doesn't support
+/// the protocol.
+pub fn capture_output(output_name: &str) -> Result<OutputCapture, String> {
+    let connection = Connection::connect_to_env()
+        .map_err(|e| format!("Failed to connect to Wayland display: {}", e))?;
+    let (globals, mut event_queue) = registry_queue_init::<CaptureState>(&connection)
+        .map_err(|e| format!("Failed to read Wayland globals: {}", e))?;
+    let qh = event_queue.handle();
+    let mut state = CaptureState::default();
+
+    let manager = globals
+        .bind::<ZwlrScreencopyManagerV1, _, _>(&qh, 1..=3, ())
+        .map_err(|e| format!("Compositor lacks zwlr_screencopy_manager_v1: {}", e))?;
+    let shm = globals
+        .bind::<WlShm, _, _>(&qh, 1..=1, ())
+        .map_err(|e| format!("Compositor lacks wl_shm: {}", e))?;
+
+    // Output names arrive with wl_output version 4
+    let outputs: Vec<WlOutput> = globals.contents().with_list(|list| {
+        list.iter()
+            .filter(|g| g.interface == WlOutput::interface().name && g.version >= 4)
+            .map(|g| globals.registry().bind::<WlOutput, _, _>(g.name, 4, &qh, ()))
+            .collect()
+    });
+    roundtrip(&mut event_queue, &mut state)?;
+
+    let output = outputs
+        .iter()
+        .find(|output| state.output_names.get(&output.id()).map(String::as_str) == Some(output_name))
+        .ok_or_else(|| format!("No Wayland output named {}", output_name))?;
+
+    let frame = manager.capture_output(0, output, &qh, ());
+
+    // Version 3 lists every buffer type before buffer_done; earlier versions only send shm
+    while state.finished.is_none()
+        && !(state.buffer.is_some() && (frame.version() < 3 || state.buffer_done))
+    {
+        blocking_dispatch(&mut event_queue, &mut state)?;
+    }
+    if let Some(Err(e)) = state.finished.take() {
+        return Err(e);
+    }
+
+    let (format, width, height, stride) = state.buffer.ok_or("Compositor offered no shm buffer")?;
+    if !matches!(format, wl_shm::Format::Argb8888 | wl_shm::Format::Xrgb8888) {
+        frame.destroy();
+        return Err(format!("Unsupported screencopy format {:?}", format));
+    }
+
+    let size = stride as usize * height as usize;
+    let file = create_shm_file(size)?;
+    let pool = shm.create_pool(file.as_fd(), size as i32, &qh, ());
+    let buffer = pool.create_buffer(0, width as i32, height as i32, stride as i32, format, &qh, ());
+
+    frame.copy(&buffer);
+    while state.finished.is_none() {
+        blocking_dispatch(&mut event_queue, &mut state)?;
+    }
+
+    frame.destroy();
+    buffer.destroy();
+    pool.destroy();
+    state.finished.take().unwrap_or(Ok(()))?;
+
+    let mut data = vec![0u8; size];
+    file.read_exact_at(&mut data, 0)
+        .map_err(|e| format!("Failed to read screencopy buffer: {}", e))?;
+
+    // wl_shm formats are little-endian, so ARGB is stored as B, G, R, A. The X in XRGB is
+    // undefined, so make every pixel opaque.
+    for pixel in data.chunks_exact_mut(4) {
+        pixel[3] = 0xff;
+    }
+
+    if state.y_invert {
+        let rows: Vec<&[u8]> = data.chunks_exact(stride as usize).rev().collect();
+        data = rows.concat();
+    }
+
+    Ok(OutputCapture {
+        width: width as i32,
+        height: height as i32,
+        stride: stride as usize,
+        data,
+    })
+}
+
+fn roundtrip(event_queue: &mut EventQueue<CaptureState>, state: &mut CaptureState) -> Result<(), String> {
+    event_queue
+        .roundtrip(state)
+        .map(|_| ())
+        .map_err(|e| format!("Wayland roundtrip failed: {}", e))
+}
+
+fn blocking_dispatch(event_queue: &mut EventQueue<CaptureState>, state: &mut CaptureState) -> Result<(), String> {
+    event_queue
+        .blocking_dispatch(state)
+        .map(|_| ())
+        .map_err(|e| format!("Wayland dispatch failed: {}", e))
+}
+
+/// An anonymous memory-backed file to share with the compositor
+fn create_shm_file(size: usize) -> Result<File, String> {
+    let fd = unsafe { libc::memfd_create(c"waltopanel-screencopy".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(format!("memfd_create failed: {}", std::io::Error::last_os_error()));
+    }
+
+    let file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+    file.set_len(size as u64)
+        .map_err(|e| format!("Failed to size screencopy buffer: {}", e))?;
+    Ok(file)
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for CaptureState {
+    fn event(
+        _state: &mut Self,
+        _registry: &WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlOutput, ()> for CaptureState {
+    fn event(
+        state: &mut Self,
+        output: &WlOutput,
+        event: wl_output::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event {
+            state.output_names.insert(output.id(), name);
+        }
+    }
+}
+
+impl Dispatch<WlShm, ()> for CaptureState {
+    fn event(
+        _state: &mut Self,
+        _shm: &WlShm,
+        _event: wl_shm::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlShmPool, ()> for CaptureState {
+    fn event(
+        _state: &mut Self,
+        _pool: &WlShmPool,
+        _event: wl_shm_pool::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlBuffer, ()> for CaptureState {
+    fn event(
+        _state: &mut Self,
+        _buffer: &WlBuffer,
+        _event: wl_buffer::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrScreencopyManagerV1, ()> for CaptureState {
+    fn event(
+        _state: &mut Self,
+        _manager: &ZwlrScreencopyManagerV1,
+        _event: zwlr_screencopy_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ()> for CaptureState {
+    fn event(
+        state: &mut Self,
+        _frame: &ZwlrScreencopyFrameV1,
+        event: zwlr_screencopy_frame_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        use zwlr_screencopy_frame_v1::Event;
+
+        match event {
+            Event::Buffer { format: WEnum::Value(format), width, height, stride } => {
+                state.buffer = Some((format, width, height, stride));
+            }
+            Event::BufferDone => state.buffer_done = true,
+            Event::Flags { flags: WEnum::Value(flags) } => {
+                state.y_invert = flags.contains(zwlr_screencopy_frame_v1::Flags::YInvert);
+            }
+            Event::Ready { .. } => state.finished = Some(Ok(())),
+            Event::Failed => state.finished = Some(Err("Screencopy failed".to_string())),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use super::workspace_backend::{DpmsSupport, MonitorInfo, WindowInfo, WorkspaceBackend, WorkspaceEvent, WorkspaceInfo};
+
+const IPC_MAGIC: &[u8] = b"i3-ipc";
+
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_OUTPUTS: u32 = 3;
+const GET_TREE: u32 = 4;
+
+/// Event types have the high bit set
+const EVENT_MASK: u32 = 0x8000_0000;
+const EVENT_WORKSPACE: u32 = EVENT_MASK;
+const EVENT_OUTPUT: u32 = EVENT_MASK | 1;
+const EVENT_WINDOW: u32 = EVENT_MASK | 3;
+
+/// Workspaces without a number (e.g. `workspace mail`) are given an ID above this
+/// base so they don't collide with numbered workspaces
+const NAMED_WORKSPACE_ID_BASE: i32 = 100_000;
+
+/// Sway/i3 backend talking to the IPC socket from `$SWAYSOCK` or `$I3SOCK`
+pub struct SwayService {
+    socket_path: PathBuf,
+    /// Workspace names by the IDs handed out in `query_workspaces`
+    workspace_names: Mutex<HashMap<i32, String>>,
+}
+
+impl SwayService {
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self {
+            socket_path,
+            workspace_names: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn write_message(stream: &mut UnixStream, message_type: u32, payload: &str) -> Result<(), String> {
+        let mut message = Vec::with_capacity(IPC_MAGIC.len() + 8 + payload.len());
+        message.extend_from_slice(IPC_MAGIC);
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&message_type.to_ne_bytes());
+        message.extend_from_slice(payload.as_bytes());
+
+        stream
+            .write_all(&message)
+            .map_err(|e| format!("Failed to write to socket: {}", e))
+    }
+
+    fn read_message(stream: &mut UnixStream) -> Result<(u32, Value), String> {
+        let mut header = [0u8; 14];
+        stream
+            .read_exact(&mut header)
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+
+        if &header[..6] != IPC_MAGIC {
+            return Err("Invalid IPC response header".to_string());
+        }
+
+        let length = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+        let message_type = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+
+        let mut payload = vec![0u8; length];
+        stream
+            .read_exact(&mut payload)
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+
+        let value = serde_json::from_slice(&payload)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+        Ok((message_type, value))
+    }
+
+    /// Send a request on a fresh connection and return the reply
+    fn send_message(&self, message_type: u32, payload: &str) -> Result<Value, String> {
+        let mut stream = UnixStream::connect(&self.socket_path)
+            .map_err(|e| format!("Failed to connect to sway socket: {}", e))?;
+
+        Self::write_message(&mut stream, message_type, payload)?;
+        Self::read_message(&mut stream).map(|(_, value)| value)
+    }
+
+    /// Run one or more commands, failing if any of them failed
+    fn run_command(&self, command: &str) -> Result<(), String> {
+        let reply = self.send_message(RUN_COMMAND, command)?;
+
+        let errors: Vec<String> = reply
+            .as_array()
+            .map(|results| {
+                results
+                    .iter()
+                    .filter(|result| !result["success"].as_bool().unwrap_or(false))
+                    .map(|result| result["error"].as_str().unwrap_or("unknown error").to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    fn workspace_id(workspace: &Value) -> Option<i32> {
+        match workspace["num"].as_i64() {
+            Some(num) if num >= 0 => Some(num as i32),
+            _ => workspace["id"].as_i64().map(|id| NAMED_WORKSPACE_ID_BASE + id as i32),
+        }
+    }
+
+    /// Build the command that switches to the workspace with the given ID
+    fn workspace_command(&self, workspace_id: i32) -> String {
+        match self.workspace_names.lock().unwrap().get(&workspace_id) {
+            Some(name) if workspace_id >= NAMED_WORKSPACE_ID_BASE => {
+                format!("workspace \"{}\"", name.replace('"', "\\\""))
+            }
+            _ => format!("workspace number {}", workspace_id),
+        }
+    }
+
+    /// Walk the layout tree, collecting every window and the workspace it lives on
+    fn collect_windows(
+        node: &Value,
+        workspace_id: Option<i32>,
+        output_index: i32,
+        windows: &mut Vec<(WindowInfo, bool)>,
+    ) {
+        let children: Vec<&Value> = node["nodes"]
+            .as_array()
+            .into_iter()
+            .chain(node["floating_nodes"].as_array())
+            .flatten()
+            .collect();
+
+        let workspace_id = if node["type"].as_str() == Some("workspace") {
+            Self::workspace_id(node)
+        } else {
+            workspace_id
+        };
+
+        let is_window = children.is_empty()
+            && matches!(node["type"].as_str(), Some("con") | Some("floating_con"))
+            && (node["pid"].is_number() || node["window"].is_number());
+
+        if is_window {
+            if let Some(workspace_id) = workspace_id {
+                // Wayland clients report app_id; X11 clients report window_properties.class
+                let class = node["app_id"]
+                    .as_str()
+                    .or_else(|| node["window_properties"]["class"].as_str())
+                    .unwrap_or_default();
+
+                windows.push((
+                    WindowInfo {
+                        address: node["id"].as_i64().unwrap_or(0).to_string(),
+                        workspace_id,
+                        class: class.to_string(),
+                        title: node["name"].as_str().unwrap_or_default().to_string(),
+                        monitor: output_index,
+                        pid: node["pid"].as_i64().unwrap_or(0) as i32,
+                        position: windows.len() as i32,
+                        urgent: node["urgent"].as_bool().unwrap_or(false),
+                    },
+                    node["focused"].as_bool().unwrap_or(false),
+                ));
+            }
+            return;
+        }
+
+        for child in children {
+            Self::collect_windows(child, workspace_id, output_index, windows);
+        }
+    }
+
+    fn query_tree_windows(&self) -> Result<Vec<(WindowInfo, bool)>, String> {
+        let tree = self.send_message(GET_TREE, "")?;
+        let mut windows = Vec::new();
+
+        let outputs = tree["nodes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|output| output["name"].as_str() != Some("__i3"));
+
+        for (index, output) in outputs.enumerate() {
+            Self::collect_windows(output, None, index as i32, &mut windows);
+        }
+
+        Ok(windows)
+    }
+}
+
+impl WorkspaceBackend for SwayService {
+    fn name(&self) -> &'static str {
+        "Sway"
+    }
+
+    fn query_workspaces(&self) -> Result<Vec<WorkspaceInfo>, String> {
+        let workspaces = self.send_message(GET_WORKSPACES, "")?;
+
+        // GET_WORKSPACES has no window counts, so count them from the tree
+        let windows = self.query_tree_windows()?;
+        let mut window_counts: HashMap<i32, i32> = HashMap::new();
+        for (window, _) in &windows {
+            *window_counts.entry(window.workspace_id).or_insert(0) += 1;
+        }
+
+        let mut names = self.workspace_names.lock().unwrap();
+        names.clear();
+
+        let mut result = Vec::new();
+
+        if let Some(ws_array) = workspaces.as_array() {
+            for ws in ws_array {
+                if let (Some(id), Some(name), Some(monitor)) = (
+                    Self::workspace_id(ws),
+                    ws["name"].as_str(),
+                    ws["output"].as_str(),
+                ) {
+                    names.insert(id, name.to_string());
+                    result.push(WorkspaceInfo {
+                        id,
+                        index: ws["num"].as_i64().filter(|num| *num >= 0).unwrap_or(id as i64) as i32,
+                        name: name.to_string(),
+                        monitor: monitor.to_string(),
+                        windows: window_counts.get(&id).copied().unwrap_or(0),
+                        has_fullscreen: false,
+                        urgent: ws["urgent"].as_bool().unwrap_or(false),
+                        special: false,
+                    });
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn query_windows(&self) -> Result<Vec<WindowInfo>, String> {
+        Ok(self.query_tree_windows()?.into_iter().map(|(window, _)| window).collect())
+    }
+
+    fn query_monitors(&self) -> Result<Vec<MonitorInfo>, String> {
+        let outputs = self.send_message(GET_OUTPUTS, "")?;
+        let workspaces = self.send_message(GET_WORKSPACES, "")?;
+
+        let visible_workspace = |output_name: &str| {
+            workspaces.as_array()?.iter()
+                .find(|ws| ws["output"].as_str() == Some(output_name) && ws["visible"].as_bool() == Some(true))
+                .and_then(Self::workspace_id)
+        };
+
+        Ok(outputs
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|output| output["active"].as_bool().unwrap_or(true))
+            .filter_map(|output| {
+                let name = output["name"].as_str()?.to_string();
+                // Sway 1.8+ reports "power"; older versions and i3 report "dpms" or nothing
+                let dpms_on = output["power"].as_bool()
+                    .or_else(|| output["dpms"].as_bool())
+                    .unwrap_or(true);
+
+                Some(MonitorInfo {
+                    active_workspace_id: visible_workspace(&name),
+                    special_workspace_id: None,
+                    name,
+                    dpms_on,
+                })
+            })
+            .collect())
+    }
+
+    /// Sway sends an output event when an output is powered on or off
+    fn dpms_support(&self) -> DpmsSupport {
+        DpmsSupport::Events
+    }
+
+    fn queries_urgency(&self) -> bool {
+        true
+    }
+
+    fn query_active_workspace(&self) -> Result<i32, String> {
+        let workspaces = self.send_message(GET_WORKSPACES, "")?;
+
+        workspaces
+            .as_array()
+            .and_then(|ws_array| ws_array.iter().find(|ws| ws["focused"].as_bool() == Some(true)))
+            .and_then(Self::workspace_id)
+            .ok_or_else(|| "Failed to get active workspace ID".to_string())
+    }
+
+    fn query_active_window(&self) -> Result<Option<String>, String> {
+        Ok(self
+            .query_tree_windows()?
+            .into_iter()
+            .find(|(_, focused)| *focused)
+            .map(|(window, _)| window.address))
+    }
+
+    fn switch_workspace(&self, workspace_id: i32) -> Result<(), String> {
+        self.run_command(&self.workspace_command(workspace_id))
+    }
+
+    fn focus_window(&self, window_address: &str) -> Result<(), String> {
+        self.run_command(&format!("[con_id={}] focus", window_address))
+    }
+
+    fn close_window(&self, window_address: &str) -> Result<(), String> {
+        self.run_command(&format!("[con_id={}] kill", window_address))
+    }
+
+    fn can_arrange_windows(&self) -> bool {
+        true
+    }
+
+    fn toggle_floating(&self, window_address: &str) -> Result<(), String> {
+        self.run_command(&format!("[con_id={}] floating toggle", window_address))
+    }
+
+    fn toggle_fullscreen(&self, window_address: &str) -> Result<(), String> {
+        self.run_command(&format!("[con_id={}] fullscreen toggle", window_address))
+    }
+
+    fn move_window_to_workspace(&self, window_address: &str, workspace_id: i32) -> Result<(), String> {
+        self.run_command(&format!(
+            "[con_id={}] move container to {}",
+            window_address,
+            self.workspace_command(workspace_id)
+        ))
+    }
+
+    fn create_workspace_on_monitor(&self, monitor_name: &str, workspace_id: i32) -> Result<(), String> {
+        self.run_command(&format!(
+            "focus output \"{}\"; workspace number {}",
+            monitor_name, workspace_id
+        ))
+    }
+
+    fn move_workspace_to_monitor(&self, workspace_id: i32, monitor_name: &str) -> Result<(), String> {
+        // Sway can only move the focused workspace, so focus it first
+        self.run_command(&format!(
+            "{}; move workspace to output \"{}\"",
+            self.workspace_command(workspace_id),
+            monitor_name
+        ))
+    }
+
+    fn listen(&self, on_event: &dyn Fn(WorkspaceEvent)) -> Result<(), String> {
+        let mut stream = UnixStream::connect(&self.socket_path)
+            .map_err(|e| format!("Failed to connect to sway socket: {}", e))?;
+
+        Self::write_message(&mut stream, SUBSCRIBE, r#"["workspace","window","output"]"#)?;
+
+        let (_, reply) = Self::read_message(&mut stream)?;
+        if !reply["success"].as_bool().unwrap_or(false) {
+            return Err("Failed to subscribe to sway events".to_string());
+        }
+
+        loop {
+            let (event_type, _payload) = Self::read_message(&mut stream)?;
+
+            match event_type {
+                EVENT_WORKSPACE | EVENT_WINDOW => on_event(WorkspaceEvent::Changed),
+                EVENT_OUTPUT => on_event(WorkspaceEvent::MonitorsChanged),
+                _ => {}
+            }
+        }
+    }
+}
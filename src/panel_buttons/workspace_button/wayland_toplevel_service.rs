@@ -0,0 +1,1366 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use wayland_client::backend::ObjectId;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::wl_output::{self, WlOutput};
+use wayland_client::protocol::wl_registry::{self, WlRegistry};
+use wayland_client::protocol::wl_seat::{self, WlSeat};
+use wayland_client::{event_created_child, Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum};
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_group_handle_v1::{
+    self, ExtWorkspaceGroupHandleV1, GroupCapabilities,
+};
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::{self, ExtWorkspaceHandleV1};
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_manager_v1::{self, ExtWorkspaceManagerV1};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_handle_v1::{
+    self, ZwlrForeignToplevelHandleV1,
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_manager_v1::{
+    self, ZwlrForeignToplevelManagerV1,
+};
+
+use super::workspace_backend::{MonitorInfo, WindowInfo, WorkspaceBackend, WorkspaceEvent, WorkspaceInfo};
+
+struct OutputRecord {
+    output: WlOutput,
+    global_name: u32,
+    name: Option<String>,
+}
+
+struct GroupRecord {
+    handle: ExtWorkspaceGroupHandleV1,
+    outputs: Vec<WlOutput>,
+    can_create: bool,
+}
+
+struct WorkspaceRecord {
+    handle: ExtWorkspaceHandleV1,
+    id: i32,
+    name: String,
+    coordinates: Vec<u8>,
+    active: bool,
+    group: Option<ObjectId>,
+}
+
+struct ToplevelRecord {
+    handle: ZwlrForeignToplevelHandleV1,
+    title: String,
+    app_id: String,
+    outputs: Vec<WlOutput>,
+    activated: bool,
+    minimized: bool,
+    fullscreen: bool,
+    /// wlr-foreign-toplevel doesn't say which workspace a toplevel is on, so toplevels are
+    /// attributed to the active workspace of their output when they appear or gain focus
+    workspace_id: Option<i32>,
+    position: i32,
+}
+
+/// Everything the compositor has told us about outputs, workspaces and toplevels.
+/// Pure bookkeeping: the Dispatch impls feed events in and the backend reads snapshots out,
+/// so it can be driven by a mock compositor.
+#[derive(Default)]
+pub struct ToplevelModel {
+    outputs: HashMap<ObjectId, OutputRecord>,
+    groups: HashMap<ObjectId, GroupRecord>,
+    workspaces: HashMap<ObjectId, WorkspaceRecord>,
+    toplevels: HashMap<ObjectId, ToplevelRecord>,
+    workspace_manager: Option<ExtWorkspaceManagerV1>,
+    seat: Option<WlSeat>,
+    next_workspace_id: i32,
+    next_toplevel_position: i32,
+    /// Name of a workspace we asked to create, to be activated once it appears
+    pending_activation: Option<String>,
+    changed: bool,
+    monitors_changed: bool,
+}
+
+impl ToplevelModel {
+    fn output_name(&self, output: &WlOutput) -> Option<String> {
+        self.outputs.get(&output.id()).and_then(|record| record.name.clone())
+    }
+
+    fn sorted_output_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.outputs.values().filter_map(|record| record.name.clone()).collect();
+        names.sort();
+        names
+    }
+
+    fn group_for_output(&self, output: &WlOutput) -> Option<ObjectId> {
+        self.groups
+            .iter()
+            .find(|(_, group)| group.outputs.iter().any(|o| o.id() == output.id()))
+            .map(|(id, _)| id.clone())
+    }
+
+    fn group_for_monitor(&self, monitor_name: &str) -> Option<ObjectId> {
+        self.groups
+            .iter()
+            .find(|(_, group)| {
+                group.outputs.iter().any(|o| self.output_name(o).as_deref() == Some(monitor_name))
+            })
+            .map(|(id, _)| id.clone())
+    }
+
+    fn active_workspace_in_group(&self, group_id: &ObjectId) -> Option<i32> {
+        self.workspaces
+            .values()
+            .find(|ws| ws.active && ws.group.as_ref() == Some(group_id))
+            .map(|ws| ws.id)
+    }
+
+    /// The workspace a toplevel on `outputs` currently belongs to
+    fn active_workspace_for_outputs(&self, outputs: &[WlOutput]) -> Option<i32> {
+        let output = outputs.first()?;
+        if self.workspace_manager.is_some() {
+            self.active_workspace_in_group(&self.group_for_output(output)?)
+        } else {
+            self.synthesized_workspace_id(&self.output_name(output)?)
+        }
+    }
+
+    /// Without ext-workspace, every output is treated as a single workspace
+    fn synthesized_workspace_id(&self, output_name: &str) -> Option<i32> {
+        self.sorted_output_names()
+            .iter()
+            .position(|name| name == output_name)
+            .map(|index| index as i32 + 1)
+    }
+
+    fn group_monitor(&self, group_id: &ObjectId) -> String {
+        self.groups
+            .get(group_id)
+            .and_then(|group| group.outputs.iter().find_map(|o| self.output_name(o)))
+            .unwrap_or_default()
+    }
+
+    fn toplevel_workspace(&self, toplevel: &ToplevelRecord) -> Option<i32> {
+        if self.workspace_manager.is_some() {
+            toplevel.workspace_id
+        } else {
+            self.active_workspace_for_outputs(&toplevel.outputs)
+        }
+    }
+
+    pub fn workspaces(&self) -> Vec<WorkspaceInfo> {
+        let windows_on = |workspace_id: i32| {
+            self.toplevels.values().filter(move |t| self.toplevel_workspace(t) == Some(workspace_id))
+        };
+
+        if self.workspace_manager.is_none() {
+            return self
+                .sorted_output_names()
+                .into_iter()
+                .enumerate()
+                .map(|(index, name)| {
+                    let id = index as i32 + 1;
+                    WorkspaceInfo {
+                        id,
+                        index: id,
+                        name: id.to_string(),
+                        monitor: name,
+                        windows: windows_on(id).count() as i32,
+                        has_fullscreen: windows_on(id).any(|t| t.fullscreen),
+                        urgent: false,
+                        special: false,
+                    }
+                })
+                .collect();
+        }
+
+        let mut ordered: Vec<&WorkspaceRecord> = self.workspaces.values().collect();
+        ordered.sort_by(|a, b| (&a.coordinates, a.id).cmp(&(&b.coordinates, b.id)));
+
+        let mut group_positions: HashMap<Option<ObjectId>, i32> = HashMap::new();
+
+        ordered
+            .into_iter()
+            .map(|ws| {
+                let position = group_positions.entry(ws.group.clone()).or_insert(0);
+                *position += 1;
+
+                WorkspaceInfo {
+                    id: ws.id,
+                    index: *position,
+                    name: ws.name.clone(),
+                    monitor: ws.group.as_ref().map(|g| self.group_monitor(g)).unwrap_or_default(),
+                    windows: windows_on(ws.id).count() as i32,
+                    has_fullscreen: windows_on(ws.id).any(|t| t.fullscreen),
+                    urgent: false,
+                    special: false,
+                }
+            })
+            .collect()
+    }
+
+    pub fn windows(&self) -> Vec<WindowInfo> {
+        let output_names = self.sorted_output_names();
+
+        self.toplevels
+            .values()
+            .filter_map(|toplevel| {
+                let monitor = toplevel.outputs.first()
+                    .and_then(|o| self.output_name(o))
+                    .and_then(|name| output_names.iter().position(|n| *n == name))
+                    .unwrap_or(0);
+
+                Some(WindowInfo {
+                    address: toplevel.handle.id().protocol_id().to_string(),
+                    workspace_id: self.toplevel_workspace(toplevel)?,
+                    class: toplevel.app_id.clone(),
+                    title: toplevel.title.clone(),
+                    monitor: monitor as i32,
+                    // The protocol doesn't expose client PIDs
+                    pid: 0,
+                    position: toplevel.position,
+                    urgent: false,
+                })
+            })
+            .collect()
+    }
+
+    pub fn monitors(&self) -> Vec<MonitorInfo> {
+        self.outputs
+            .values()
+            .filter_map(|record| {
+                let name = record.name.clone()?;
+                let active_workspace_id = if self.workspace_manager.is_some() {
+                    self.group_for_output(&record.output).and_then(|g| self.active_workspace_in_group(&g))
+                } else {
+                    self.synthesized_workspace_id(&name)
+                };
+
+                Some(MonitorInfo {
+                    name,
+                    active_workspace_id,
+                    special_workspace_id: None,
+                    dpms_on: true,
+                })
+            })
+            .collect()
+    }
+
+    pub fn active_workspace(&self) -> Option<i32> {
+        // Prefer the workspace holding the focused toplevel, since several can be active at once
+        self.toplevels
+            .values()
+            .find(|t| t.activated)
+            .and_then(|t| self.toplevel_workspace(t))
+            .or_else(|| self.monitors().into_iter().find_map(|monitor| monitor.active_workspace_id))
+    }
+
+    pub fn active_window(&self) -> Option<String> {
+        self.toplevels
+            .values()
+            .find(|t| t.activated)
+            .map(|t| t.handle.id().protocol_id().to_string())
+    }
+
+    fn find_workspace(&self, workspace_id: i32) -> Option<&WorkspaceRecord> {
+        self.workspaces.values().find(|ws| ws.id == workspace_id)
+    }
+
+    fn find_toplevel(&self, address: &str) -> Option<&ToplevelRecord> {
+        self.toplevels
+            .values()
+            .find(|t| t.handle.id().protocol_id().to_string() == address)
+    }
+
+    /// Take the change flags accumulated since the last call
+    fn take_events(&mut self) -> Vec<WorkspaceEvent> {
+        let mut events = Vec::new();
+        if std::mem::take(&mut self.monitors_changed) {
+            events.push(WorkspaceEvent::MonitorsChanged);
+        }
+        if std::mem::take(&mut self.changed) {
+            events.push(WorkspaceEvent::Changed);
+        }
+        events
+    }
+}
+
+/// Event queue state; every Dispatch impl records into the shared model
+struct WaylandState {
+    model: Arc<Mutex<ToplevelModel>>,
+}
+
+impl WaylandState {
+    fn model(&self) -> MutexGuard<'_, ToplevelModel> {
+        self.model.lock().unwrap()
+    }
+
+    fn bind_output(&self, registry: &WlRegistry, global_name: u32, version: u32, qh: &QueueHandle<Self>) {
+        let output = registry.bind::<WlOutput, _, _>(global_name, version.min(4), qh, ());
+        self.model().outputs.insert(output.id(), OutputRecord { output, global_name, name: None });
+    }
+}
+
+/// Compositor-agnostic backend built on the ext-workspace-v1 and wlr-foreign-toplevel-management
+/// protocols. Works on any compositor that implements foreign-toplevel; without ext-workspace,
+/// each output is shown as a single workspace.
+pub struct WaylandToplevelService {
+    connection: Mutex<Connection>,
+    model: Arc<Mutex<ToplevelModel>>,
+    event_queue: Mutex<Option<(EventQueue<WaylandState>, WaylandState)>>,
+}
+
+impl WaylandToplevelService {
+    /// Connect to the compositor from `$WAYLAND_DISPLAY`
+    pub fn connect() -> Result<Self, String> {
+        let connection = Connection::connect_to_env()
+            .map_err(|e| format!("Failed to connect to Wayland display: {}", e))?;
+        Self::from_connection(connection)
+    }
+
+    /// Set up the backend on an existing connection, e.g. one end of a socket pair
+    /// served by a mock compositor
+    pub fn from_connection(connection: Connection) -> Result<Self, String> {
+        let model = Arc::new(Mutex::new(ToplevelModel::default()));
+        let event_queue = Self::bind_globals(&connection, &model)?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            model,
+            event_queue: Mutex::new(Some(event_queue)),
+        })
+    }
+
+    /// Bind the managers and outputs on `connection` and read their initial state into `model`
+    fn bind_globals(
+        connection: &Connection,
+        model: &Arc<Mutex<ToplevelModel>>,
+    ) -> Result<(EventQueue<WaylandState>, WaylandState), String> {
+        let (globals, mut event_queue) = registry_queue_init::<WaylandState>(connection)
+            .map_err(|e| format!("Failed to read Wayland globals: {}", e))?;
+        let qh = event_queue.handle();
+
+        *model.lock().unwrap() = ToplevelModel::default();
+        let mut state = WaylandState { model: model.clone() };
+
+        globals
+            .bind::<ZwlrForeignToplevelManagerV1, _, _>(&qh, 1..=3, ())
+            .map_err(|e| format!("Compositor lacks zwlr_foreign_toplevel_manager_v1: {}", e))?;
+
+        let workspace_manager = globals.bind::<ExtWorkspaceManagerV1, _, _>(&qh, 1..=1, ()).ok();
+        let seat = globals.bind::<WlSeat, _, _>(&qh, 1..=7, ()).ok();
+        {
+            let mut model = state.model();
+            model.workspace_manager = workspace_manager;
+            model.seat = seat;
+        }
+
+        globals.contents().with_list(|list| {
+            for global in list.iter().filter(|g| g.interface == WlOutput::interface().name) {
+                state.bind_output(globals.registry(), global.name, global.version, &qh);
+            }
+        });
+
+        // The first roundtrip delivers the managers' objects, the second their properties
+        for _ in 0..2 {
+            event_queue
+                .roundtrip(&mut state)
+                .map_err(|e| format!("Wayland roundtrip failed: {}", e))?;
+        }
+
+        Ok((event_queue, state))
+    }
+
+    fn model(&self) -> MutexGuard<'_, ToplevelModel> {
+        self.model.lock().unwrap()
+    }
+
+    fn flush(&self) -> Result<(), String> {
+        self.connection
+            .lock()
+            .unwrap()
+            .flush()
+            .map_err(|e| format!("Failed to flush Wayland connection: {}", e))
+    }
+
+    /// Send a workspace request followed by the commit the protocol requires
+    fn commit_workspace_request(&self, request: impl FnOnce(&mut ToplevelModel) -> Result<(), String>) -> Result<(), String> {
+        {
+            let mut model = self.model();
+            let manager = model.workspace_manager.clone()
+                .ok_or_else(|| "Compositor lacks ext_workspace_manager_v1".to_string())?;
+            request(&mut model)?;
+            manager.commit();
+        }
+        self.flush()
+    }
+
+    fn with_toplevel(&self, address: &str, request: impl FnOnce(&ToplevelRecord, &ToplevelModel)) -> Result<(), String> {
+        {
+            let model = self.model();
+            let toplevel = model.find_toplevel(address)
+                .ok_or_else(|| format!("No toplevel with id {}", address))?;
+            request(toplevel, &model);
+        }
+        self.flush()
+    }
+}
+
+impl WorkspaceBackend for WaylandToplevelService {
+    fn name(&self) -> &'static str {
+        "Wayland foreign-toplevel"
+    }
+
+    fn query_workspaces(&self) -> Result<Vec<WorkspaceInfo>, String> {
+        Ok(self.model().workspaces())
+    }
+
+    fn query_windows(&self) -> Result<Vec<WindowInfo>, String> {
+        Ok(self.model().windows())
+    }
+
+    fn query_monitors(&self) -> Result<Vec<MonitorInfo>, String> {
+        Ok(self.model().monitors())
+    }
+
+    fn query_active_workspace(&self) -> Result<i32, String> {
+        self.model()
+            .active_workspace()
+            .ok_or_else(|| "Failed to get active workspace ID".to_string())
+    }
+
+    fn query_active_window(&self) -> Result<Option<String>, String> {
+        Ok(self.model().active_window())
+    }
+
+    fn switch_workspace(&self, workspace_id: i32) -> Result<(), String> {
+        self.commit_workspace_request(|model| {
+            let workspace = model.find_workspace(workspace_id)
+                .ok_or_else(|| format!("No workspace with id {}", workspace_id))?;
+            workspace.handle.activate();
+            Ok(())
+        })
+    }
+
+    fn focus_window(&self, window_address: &str) -> Result<(), String> {
+        self.with_toplevel(window_address, |toplevel, model| {
+            if toplevel.minimized {
+                toplevel.handle.unset_minimized();
+            }
+            if let Some(seat) = &model.seat {
+                toplevel.handle.activate(seat);
+            }
+        })
+    }
+
+    fn can_minimize(&self) -> bool {
+        true
+    }
+
+    fn minimize_window(&self, window_address: &str) -> Result<(), String> {
+        self.with_toplevel(window_address, |toplevel, _| toplevel.handle.set_minimized())
+    }
+
+    fn close_window(&self, window_address: &str) -> Result<(), String> {
+        self.with_toplevel(window_address, |toplevel, _| toplevel.handle.close())
+    }
+
+    fn create_workspace_on_monitor(&self, monitor_name: &str, workspace_id: i32) -> Result<(), String> {
+        let name = workspace_id.to_string();
+        self.commit_workspace_request(|model| {
+            let group = model.group_for_monitor(monitor_name)
+                .and_then(|g| model.groups.get(&g))
+                .filter(|group| group.can_create)
+                .ok_or_else(|| format!("Can't create workspaces on {}", monitor_name))?;
+            group.handle.create_workspace(name.clone());
+            // The new workspace is activated once the compositor announces it, which the
+            // listener thread can see as soon as the request is committed
+            model.pending_activation = Some(name);
+            Ok(())
+        })
+        .inspect_err(|_| self.model().pending_activation = None)
+    }
+
+    fn move_workspace_to_monitor(&self, workspace_id: i32, monitor_name: &str) -> Result<(), String> {
+        self.commit_workspace_request(|model| {
+            let workspace = model.find_workspace(workspace_id)
+                .ok_or_else(|| format!("No workspace with id {}", workspace_id))?;
+            let group = model.group_for_monitor(monitor_name)
+                .and_then(|g| model.groups.get(&g))
+                .ok_or_else(|| format!("No workspace group on {}", monitor_name))?;
+            workspace.handle.assign(&group.handle);
+            Ok(())
+        })
+    }
+
+    /// A Wayland connection can't be resumed once dispatching has failed, so a lost one is
+    /// replaced by a new connection to `$WAYLAND_DISPLAY`
+    fn reconnect(&self) -> Result<(), String> {
+        let mut event_queue = self.event_queue.lock().unwrap();
+        if event_queue.is_some() {
+            return Ok(());
+        }
+
+        let connection = Connection::connect_to_env()
+            .map_err(|e| format!("Failed to connect to Wayland display: {}", e))?;
+        *event_queue = Some(Self::bind_globals(&connection, &self.model)?);
+        *self.connection.lock().unwrap() = connection;
+        Ok(())
+    }
+
+    fn listen(&self, on_event: &dyn Fn(WorkspaceEvent)) -> Result<(), String> {
+        let (mut event_queue, mut state) = self.event_queue.lock().unwrap().take()
+            .ok_or_else(|| "Wayland event queue is already being dispatched".to_string())?;
+
+        // Report whatever arrived between setup and now
+        on_event(WorkspaceEvent::Changed);
+
+        loop {
+            event_queue
+                .blocking_dispatch(&mut state)
+                .map_err(|e| format!("Wayland dispatch failed: {}", e))?;
+
+            let events = state.model().take_events();
+            for event in events {
+                on_event(event);
+            }
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for WaylandState {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global { name, interface, version } if interface == WlOutput::interface().name => {
+                state.bind_output(registry, name, version, qh);
+            }
+            wl_registry::Event::GlobalRemove { name } => {
+                let mut model = state.model();
+                let removed: Vec<ObjectId> = model.outputs.iter()
+                    .filter(|(_, record)| record.global_name == name)
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                for id in removed {
+                    if let Some(record) = model.outputs.remove(&id) {
+                        record.output.release();
+                        model.monitors_changed = true;
+                        model.changed = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlOutput, ()> for WaylandState {
+    fn event(
+        state: &mut Self,
+        output: &WlOutput,
+        event: wl_output::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event {
+            let mut model = state.model();
+            if let Some(record) = model.outputs.get_mut(&output.id()) {
+                record.name = Some(name);
+            }
+            model.monitors_changed = true;
+            model.changed = true;
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for WaylandState {
+    fn event(
+        _state: &mut Self,
+        _seat: &WlSeat,
+        _event: wl_seat::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for WaylandState {
+    fn event(
+        state: &mut Self,
+        _manager: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            let mut model = state.model();
+            let position = model.next_toplevel_position;
+            model.next_toplevel_position += 1;
+            model.toplevels.insert(toplevel.id(), ToplevelRecord {
+                handle: toplevel,
+                title: String::new(),
+                app_id: String::new(),
+                outputs: Vec::new(),
+                activated: false,
+                minimized: false,
+                fullscreen: false,
+                workspace_id: None,
+                position,
+            });
+        }
+    }
+
+    event_created_child!(WaylandState, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for WaylandState {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        use zwlr_foreign_toplevel_handle_v1::Event;
+
+        let mut model = state.model();
+
+        if let Event::Closed = event {
+            if model.toplevels.remove(&handle.id()).is_some() {
+                handle.destroy();
+                model.changed = true;
+            }
+            return;
+        }
+
+        if let Event::Done = event {
+            let Some((outputs, activated, workspace_id)) = model.toplevels.get(&handle.id())
+                .map(|t| (t.outputs.clone(), t.activated, t.workspace_id))
+            else {
+                return;
+            };
+
+            if workspace_id.is_none() || activated {
+                let active_workspace = model.active_workspace_for_outputs(&outputs);
+                if let Some(toplevel) = model.toplevels.get_mut(&handle.id()) {
+                    toplevel.workspace_id = active_workspace.or(workspace_id);
+                }
+            }
+            model.changed = true;
+            return;
+        }
+
+        let Some(toplevel) = model.toplevels.get_mut(&handle.id()) else { return };
+
+        match event {
+            Event::Title { title } => toplevel.title = title,
+            Event::AppId { app_id } => toplevel.app_id = app_id,
+            Event::OutputEnter { output } => toplevel.outputs.push(output),
+            Event::OutputLeave { output } => toplevel.outputs.retain(|o| o.id() != output.id()),
+            Event::State { state } => {
+                let states: Vec<u32> = state
+                    .chunks_exact(4)
+                    .map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    .collect();
+                toplevel.minimized = states.contains(&(zwlr_foreign_toplevel_handle_v1::State::Minimized as u32));
+                toplevel.activated = states.contains(&(zwlr_foreign_toplevel_handle_v1::State::Activated as u32));
+                toplevel.fullscreen = states.contains(&(zwlr_foreign_toplevel_handle_v1::State::Fullscreen as u32));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceManagerV1, ()> for WaylandState {
+    fn event(
+        state: &mut Self,
+        manager: &ExtWorkspaceManagerV1,
+        event: ext_workspace_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let mut model = state.model();
+
+        match event {
+            ext_workspace_manager_v1::Event::WorkspaceGroup { workspace_group } => {
+                model.groups.insert(workspace_group.id(), GroupRecord {
+                    handle: workspace_group,
+                    outputs: Vec::new(),
+                    can_create: false,
+                });
+            }
+            ext_workspace_manager_v1::Event::Workspace { workspace } => {
+                model.next_workspace_id += 1;
+                let id = model.next_workspace_id;
+                model.workspaces.insert(workspace.id(), WorkspaceRecord {
+                    handle: workspace,
+                    id,
+                    name: String::new(),
+                    coordinates: Vec::new(),
+                    active: false,
+                    group: None,
+                });
+            }
+            ext_workspace_manager_v1::Event::Done => {
+                // Activate a workspace we created once the compositor has announced it
+                if let Some(pending) = model.pending_activation.clone()
+                    && let Some(workspace) = model.workspaces.values().find(|ws| ws.name == pending)
+                {
+                    workspace.handle.activate();
+                    manager.commit();
+                    model.pending_activation = None;
+                }
+                model.changed = true;
+            }
+            ext_workspace_manager_v1::Event::Finished => {
+                model.workspace_manager = None;
+                model.changed = true;
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(WaylandState, ExtWorkspaceManagerV1, [
+        ext_workspace_manager_v1::EVT_WORKSPACE_GROUP_OPCODE => (ExtWorkspaceGroupHandleV1, ()),
+        ext_workspace_manager_v1::EVT_WORKSPACE_OPCODE => (ExtWorkspaceHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtWorkspaceGroupHandleV1, ()> for WaylandState {
+    fn event(
+        state: &mut Self,
+        handle: &ExtWorkspaceGroupHandleV1,
+        event: ext_workspace_group_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        use ext_workspace_group_handle_v1::Event;
+
+        let mut model = state.model();
+
+        match event {
+            Event::Capabilities { capabilities } => {
+                if let Some(group) = model.groups.get_mut(&handle.id()) {
+                    group.can_create = matches!(
+                        capabilities,
+                        WEnum::Value(caps) if caps.contains(GroupCapabilities::CreateWorkspace)
+                    );
+                }
+            }
+            Event::OutputEnter { output } => {
+                if let Some(group) = model.groups.get_mut(&handle.id()) {
+                    group.outputs.push(output);
+                }
+            }
+            Event::OutputLeave { output } => {
+                if let Some(group) = model.groups.get_mut(&handle.id()) {
+                    group.outputs.retain(|o| o.id() != output.id());
+                }
+            }
+            Event::WorkspaceEnter { workspace } => {
+                if let Some(ws) = model.workspaces.get_mut(&workspace.id()) {
+                    ws.group = Some(handle.id());
+                }
+            }
+            Event::WorkspaceLeave { workspace } => {
+                if let Some(ws) = model.workspaces.get_mut(&workspace.id()) {
+                    ws.group = None;
+                }
+            }
+            Event::Removed if model.groups.remove(&handle.id()).is_some() => handle.destroy(),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceHandleV1, ()> for WaylandState {
+    fn event(
+        state: &mut Self,
+        handle: &ExtWorkspaceHandleV1,
+        event: ext_workspace_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        use ext_workspace_handle_v1::Event;
+
+        let mut model = state.model();
+
+        if let Event::Removed = event {
+            if let Some(workspace) = model.workspaces.remove(&handle.id()) {
+                handle.destroy();
+                // Toplevels get re-attributed the next time they report state
+                for toplevel in model.toplevels.values_mut() {
+                    if toplevel.workspace_id == Some(workspace.id) {
+                        toplevel.workspace_id = None;
+                    }
+                }
+            }
+            return;
+        }
+
+        let Some(workspace) = model.workspaces.get_mut(&handle.id()) else { return };
+
+        match event {
+            Event::Name { name } => workspace.name = name,
+            Event::Coordinates { coordinates } => workspace.coordinates = coordinates,
+            Event::State { state } => {
+                workspace.active = matches!(
+                    state,
+                    WEnum::Value(flags) if flags.contains(ext_workspace_handle_v1::State::Active)
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+    use std::thread::{self, JoinHandle};
+
+    /// Objects the server creates get IDs from this range
+    const FIRST_SERVER_ID: u32 = 0xff00_0000;
+
+    const OUTPUT_GLOBAL: u32 = 1;
+    const TOPLEVEL_MANAGER_GLOBAL: u32 = 100;
+    const WORKSPACE_MANAGER_GLOBAL: u32 = 101;
+    const SEAT_GLOBAL: u32 = 102;
+
+    struct MockWorkspace {
+        name: &'static str,
+        /// Index into `Scene::outputs` of the output whose group holds it
+        output: usize,
+        coordinates: Vec<u32>,
+        active: bool,
+    }
+
+    struct MockToplevel {
+        app_id: &'static str,
+        title: &'static str,
+        output: usize,
+        activated: bool,
+        fullscreen: bool,
+    }
+
+    /// Changes the mock sends on a later roundtrip
+    enum Change {
+        CloseToplevel(usize),
+        SetActive { workspace: usize, active: bool },
+        RemoveOutput(usize),
+    }
+
+    /// What the mock compositor has. Without `workspaces`, ext-workspace isn't advertised.
+    struct Scene {
+        outputs: Vec<&'static str>,
+        workspaces: Option<Vec<MockWorkspace>>,
+        toplevels: Vec<MockToplevel>,
+        /// One batch of changes per roundtrip after the initial state was sent
+        later: Vec<Vec<Change>>,
+    }
+
+    enum Arg<'a> {
+        Uint(u32),
+        Str(&'a str),
+        Array(Vec<u8>),
+    }
+
+    fn message(object: u32, opcode: u16, args: &[Arg]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for arg in args {
+            match arg {
+                Arg::Uint(value) => body.extend(value.to_ne_bytes()),
+                Arg::Str(text) => {
+                    let mut bytes = text.as_bytes().to_vec();
+                    bytes.push(0);
+                    body.extend((bytes.len() as u32).to_ne_bytes());
+                    bytes.resize(bytes.len().next_multiple_of(4), 0);
+                    body.extend(bytes);
+                }
+                Arg::Array(bytes) => {
+                    body.extend((bytes.len() as u32).to_ne_bytes());
+                    let mut bytes = bytes.clone();
+                    bytes.resize(bytes.len().next_multiple_of(4), 0);
+                    body.extend(bytes);
+                }
+            }
+        }
+
+        let mut wire = object.to_ne_bytes().to_vec();
+        wire.extend(((((body.len() + 8) as u32) << 16) | opcode as u32).to_ne_bytes());
+        wire.extend(body);
+        wire
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_ne_bytes()).collect()
+    }
+
+    /// A compositor that speaks just enough of the wire protocol to advertise the globals
+    /// and describe `Scene`. Requests it has no use for are skipped.
+    struct MockCompositor {
+        stream: UnixStream,
+        scene: Scene,
+        registry: Option<u32>,
+        /// Client IDs of each bound output, by index into `scene.outputs`
+        outputs: Vec<Option<u32>>,
+        toplevel_manager: Option<u32>,
+        workspace_manager: Option<u32>,
+        groups: Vec<u32>,
+        workspaces: Vec<u32>,
+        /// Index into `scene.outputs` of the output each workspace is on
+        workspace_outputs: Vec<usize>,
+        toplevels: Vec<u32>,
+        next_id: u32,
+        announced: bool,
+        /// Roundtrips since the scene was described
+        roundtrips: usize,
+    }
+
+    impl MockCompositor {
+        fn spawn(scene: Scene) -> (UnixStream, JoinHandle<()>) {
+            let (client, server) = UnixStream::pair().unwrap();
+            let outputs = vec![None; scene.outputs.len()];
+            let mut mock = Self {
+                stream: server,
+                scene,
+                registry: None,
+                outputs,
+                toplevel_manager: None,
+                workspace_manager: None,
+                groups: Vec::new(),
+                workspaces: Vec::new(),
+                workspace_outputs: Vec::new(),
+                toplevels: Vec::new(),
+                next_id: FIRST_SERVER_ID,
+                announced: false,
+                roundtrips: 0,
+            };
+            (client, thread::spawn(move || mock.run()))
+        }
+
+        fn send(&mut self, object: u32, opcode: u16, args: &[Arg]) {
+            self.stream.write_all(&message(object, opcode, args)).unwrap();
+        }
+
+        fn new_id(&mut self) -> u32 {
+            self.next_id += 1;
+            self.next_id - 1
+        }
+
+        /// Serve requests until the client hangs up
+        fn run(&mut self) {
+            let mut buffer = Vec::new();
+            let mut chunk = [0u8; 4096];
+            loop {
+                let read = match self.stream.read(&mut chunk) {
+                    Ok(0) | Err(_) => return,
+                    Ok(read) => read,
+                };
+                buffer.extend_from_slice(&chunk[..read]);
+
+                while buffer.len() >= 8 {
+                    let word = |at: usize| u32::from_ne_bytes(buffer[at..at + 4].try_into().unwrap());
+                    let size = (word(4) >> 16) as usize;
+                    if buffer.len() < size {
+                        break;
+                    }
+                    let (object, opcode) = (word(0), word(4) & 0xffff);
+                    let args: Vec<u8> = buffer[8..size].to_vec();
+                    buffer.drain(..size);
+                    self.handle(object, opcode, &args);
+                }
+            }
+        }
+
+        fn handle(&mut self, object: u32, opcode: u32, args: &[u8]) {
+            let word = |at: usize| u32::from_ne_bytes(args[at..at + 4].try_into().unwrap());
+
+            match (object, opcode) {
+                // wl_display.sync
+                (1, 0) => {
+                    self.announce();
+                    let callback = word(0);
+                    self.send(callback, 0, &[Arg::Uint(0)]);
+                    self.send(1, 1, &[Arg::Uint(callback)]);
+                }
+                // wl_display.get_registry
+                (1, 1) => {
+                    let registry = word(0);
+                    self.registry = Some(registry);
+                    let mut globals = vec![
+                        (TOPLEVEL_MANAGER_GLOBAL, "zwlr_foreign_toplevel_manager_v1", 3),
+                        (SEAT_GLOBAL, "wl_seat", 7),
+                    ];
+                    if self.scene.workspaces.is_some() {
+                        globals.push((WORKSPACE_MANAGER_GLOBAL, "ext_workspace_manager_v1", 1));
+                    }
+                    for index in 0..self.scene.outputs.len() as u32 {
+                        globals.push((OUTPUT_GLOBAL + index, "wl_output", 4));
+                    }
+                    for (name, interface, version) in globals {
+                        self.send(registry, 0, &[Arg::Uint(name), Arg::Str(interface), Arg::Uint(version)]);
+                    }
+                }
+                // wl_registry.bind, whose new_id comes with its interface and version
+                (registry, 0) if Some(registry) == self.registry => {
+                    let name = word(0);
+                    let interface_len = (word(4) as usize).next_multiple_of(4);
+                    let id = word(8 + interface_len + 4);
+                    match name {
+                        TOPLEVEL_MANAGER_GLOBAL => self.toplevel_manager = Some(id),
+                        WORKSPACE_MANAGER_GLOBAL => self.workspace_manager = Some(id),
+                        SEAT_GLOBAL => {}
+                        _ => {
+                            let index = (name - OUTPUT_GLOBAL) as usize;
+                            self.outputs[index] = Some(id);
+                            let output_name = self.scene.outputs[index];
+                            self.send(id, 4, &[Arg::Str(output_name)]);
+                            self.send(id, 2, &[]);
+                        }
+                    }
+                }
+                // ext_workspace_group_handle_v1.create_workspace, announced right away
+                (group, 0) if self.groups.contains(&group) => {
+                    let output = self.groups.iter().position(|g| *g == group).unwrap();
+                    let name_len = word(0) as usize;
+                    let name = String::from_utf8(args[4..4 + name_len - 1].to_vec()).unwrap();
+                    let workspace = self.new_id();
+                    self.workspaces.push(workspace);
+                    self.workspace_outputs.push(output);
+
+                    let manager = self.workspace_manager.unwrap();
+                    self.send(manager, 1, &[Arg::Uint(workspace)]);
+                    self.send(group, 3, &[Arg::Uint(workspace)]);
+                    self.send(workspace, 0, &[Arg::Str(&name)]);
+                    self.send(workspace, 1, &[Arg::Str(&name)]);
+                    self.send(workspace, 3, &[Arg::Uint(0)]);
+                    self.send(manager, 2, &[]);
+                }
+                // ext_workspace_handle_v1.activate, which deactivates the rest of its group
+                (workspace, 1) if self.workspaces.contains(&workspace) => {
+                    let index = self.workspaces.iter().position(|w| *w == workspace).unwrap();
+                    let output = self.workspace_outputs[index];
+                    for (other, other_output) in self.workspaces.clone().into_iter().zip(self.workspace_outputs.clone()) {
+                        if other_output == output {
+                            self.send(other, 3, &[Arg::Uint((other == workspace) as u32)]);
+                        }
+                    }
+                    self.send(self.workspace_manager.unwrap(), 2, &[]);
+                }
+                _ => {}
+            }
+        }
+
+        /// Describe the scene once everything is bound, then one batch of changes per
+        /// roundtrip after setup
+        fn announce(&mut self) {
+            let Some(toplevel_manager) = self.toplevel_manager else { return };
+            if self.announced {
+                self.roundtrips += 1;
+                // The second of `from_connection`'s roundtrips is still part of setup
+                if self.roundtrips > 1 && !self.scene.later.is_empty() {
+                    let changes = self.scene.later.remove(0);
+                    self.apply(changes);
+                }
+                return;
+            }
+            self.announced = true;
+
+            if let (Some(manager), Some(workspaces)) = (self.workspace_manager, self.scene.workspaces.take()) {
+                // One group per output
+                for _ in 0..self.scene.outputs.len() {
+                    let group = self.new_id();
+                    self.groups.push(group);
+                    self.send(manager, 0, &[Arg::Uint(group)]);
+                }
+                for mock in &workspaces {
+                    let workspace = self.new_id();
+                    self.workspaces.push(workspace);
+                    self.workspace_outputs.push(mock.output);
+                    self.send(manager, 1, &[Arg::Uint(workspace)]);
+                }
+                for (index, group) in self.groups.clone().into_iter().enumerate() {
+                    self.send(group, 0, &[Arg::Uint(GroupCapabilities::CreateWorkspace.bits())]);
+                    self.send(group, 1, &[Arg::Uint(self.outputs[index].unwrap())]);
+                    for (workspace, mock) in self.workspaces.clone().into_iter().zip(&workspaces) {
+                        if mock.output == index {
+                            self.send(group, 3, &[Arg::Uint(workspace)]);
+                        }
+                    }
+                }
+                for (workspace, mock) in self.workspaces.clone().into_iter().zip(&workspaces) {
+                    self.send(workspace, 0, &[Arg::Str(mock.name)]);
+                    self.send(workspace, 1, &[Arg::Str(mock.name)]);
+                    self.send(workspace, 2, &[Arg::Array(words(&mock.coordinates))]);
+                    self.send(workspace, 3, &[Arg::Uint(mock.active as u32)]);
+                }
+                self.send(manager, 2, &[]);
+                self.scene.workspaces = Some(workspaces);
+            }
+
+            let toplevels = std::mem::take(&mut self.scene.toplevels);
+            for mock in &toplevels {
+                let toplevel = self.new_id();
+                self.toplevels.push(toplevel);
+                self.send(toplevel_manager, 0, &[Arg::Uint(toplevel)]);
+                self.send(toplevel, 0, &[Arg::Str(mock.title)]);
+                self.send(toplevel, 1, &[Arg::Str(mock.app_id)]);
+                self.send(toplevel, 2, &[Arg::Uint(self.outputs[mock.output].unwrap())]);
+                let mut states = Vec::new();
+                if mock.activated {
+                    states.push(zwlr_foreign_toplevel_handle_v1::State::Activated as u32);
+                }
+                if mock.fullscreen {
+                    states.push(zwlr_foreign_toplevel_handle_v1::State::Fullscreen as u32);
+                }
+                self.send(toplevel, 4, &[Arg::Array(words(&states))]);
+                self.send(toplevel, 5, &[]);
+            }
+            self.scene.toplevels = toplevels;
+        }
+
+        fn apply(&mut self, changes: Vec<Change>) {
+            for change in changes {
+                match change {
+                    Change::CloseToplevel(index) => self.send(self.toplevels[index], 6, &[]),
+                    Change::SetActive { workspace, active } => {
+                        self.send(self.workspaces[workspace], 3, &[Arg::Uint(active as u32)]);
+                        self.send(self.workspace_manager.unwrap(), 2, &[]);
+                    }
+                    Change::RemoveOutput(index) => {
+                        self.send(self.registry.unwrap(), 1, &[Arg::Uint(OUTPUT_GLOBAL + index as u32)]);
+                    }
+                }
+            }
+        }
+    }
+
+    fn connect(scene: Scene) -> (WaylandToplevelService, JoinHandle<()>) {
+        let (client, server) = MockCompositor::spawn(scene);
+        let service = WaylandToplevelService::from_connection(Connection::from_socket(client).unwrap()).unwrap();
+        // `listen` reports the initial state with a Changed of its own
+        service.model().take_events();
+        (service, server)
+    }
+
+    /// Let the mock send its next batch of changes, returning the events they produced
+    fn roundtrip(service: &WaylandToplevelService) -> Vec<WorkspaceEvent> {
+        let mut queue = service.event_queue.lock().unwrap();
+        let (event_queue, state) = queue.as_mut().unwrap();
+        event_queue.roundtrip(state).unwrap();
+        state.model().take_events()
+    }
+
+    fn disconnect(service: WaylandToplevelService, server: JoinHandle<()>) {
+        drop(service);
+        server.join().unwrap();
+    }
+
+    fn workspace(name: &'static str, output: usize, coordinate: u32, active: bool) -> MockWorkspace {
+        MockWorkspace { name, output, coordinates: vec![coordinate], active }
+    }
+
+    fn toplevel(app_id: &'static str, output: usize, activated: bool, fullscreen: bool) -> MockToplevel {
+        MockToplevel { app_id, title: app_id, output, activated, fullscreen }
+    }
+
+    /// Two outputs with a workspace group each: "1" and "2" on DP-1 with "2" active, and
+    /// "web" on HDMI-A-1. kitty has focus on DP-1; mpv is fullscreen on HDMI-A-1.
+    fn workspace_scene() -> Scene {
+        Scene {
+            outputs: vec!["HDMI-A-1", "DP-1"],
+            workspaces: Some(vec![
+                workspace("1", 1, 0, false),
+                workspace("2", 1, 1, true),
+                workspace("web", 0, 0, true),
+            ]),
+            toplevels: vec![toplevel("kitty", 1, true, false), toplevel("mpv", 0, false, true)],
+            later: Vec::new(),
+        }
+    }
+
+    fn sorted_monitors(service: &WaylandToplevelService) -> Vec<(String, Option<i32>)> {
+        let mut monitors: Vec<(String, Option<i32>)> = service
+            .query_monitors()
+            .unwrap()
+            .into_iter()
+            .map(|monitor| (monitor.name, monitor.active_workspace_id))
+            .collect();
+        monitors.sort();
+        monitors
+    }
+
+    fn sorted_windows(service: &WaylandToplevelService) -> Vec<WindowInfo> {
+        let mut windows = service.query_windows().unwrap();
+        windows.sort_by_key(|window| window.position);
+        windows
+    }
+
+    #[test]
+    fn ext_workspace_groups_become_monitors() {
+        let (service, server) = connect(workspace_scene());
+
+        let mut workspaces = service.query_workspaces().unwrap();
+        workspaces.sort_by_key(|ws| ws.id);
+        let summary: Vec<(i32, i32, &str, &str, i32, bool)> = workspaces
+            .iter()
+            .map(|ws| (ws.id, ws.index, ws.name.as_str(), ws.monitor.as_str(), ws.windows, ws.has_fullscreen))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, 1, "1", "DP-1", 0, false),
+                (2, 2, "2", "DP-1", 1, false),
+                (3, 1, "web", "HDMI-A-1", 1, true),
+            ],
+        );
+
+        assert_eq!(
+            sorted_monitors(&service),
+            vec![("DP-1".to_string(), Some(2)), ("HDMI-A-1".to_string(), Some(3))],
+        );
+
+        let windows = sorted_windows(&service);
+        let summary: Vec<(&str, i32, i32)> = windows
+            .iter()
+            .map(|window| (window.class.as_str(), window.workspace_id, window.monitor))
+            .collect();
+        // Monitors are numbered in name order
+        assert_eq!(summary, vec![("kitty", 2, 0), ("mpv", 3, 1)]);
+
+        assert_eq!(service.query_active_workspace(), Ok(2));
+        assert_eq!(service.query_active_window(), Ok(Some(windows[0].address.clone())));
+
+        disconnect(service, server);
+    }
+
+    #[test]
+    fn ext_workspace_changes_are_reported() {
+        let mut scene = workspace_scene();
+        scene.later = vec![
+            vec![Change::SetActive { workspace: 1, active: false }, Change::SetActive { workspace: 0, active: true }],
+            vec![Change::CloseToplevel(1)],
+        ];
+        let (service, server) = connect(scene);
+
+        assert_eq!(roundtrip(&service), vec![WorkspaceEvent::Changed]);
+        assert_eq!(
+            sorted_monitors(&service),
+            vec![("DP-1".to_string(), Some(1)), ("HDMI-A-1".to_string(), Some(3))],
+        );
+        // kitty stays on the workspace it was seen on
+        assert_eq!(sorted_windows(&service)[0].workspace_id, 2);
+
+        assert_eq!(roundtrip(&service), vec![WorkspaceEvent::Changed]);
+        let windows = sorted_windows(&service);
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].class, "kitty");
+        assert!(service.query_workspaces().unwrap().iter().all(|ws| !ws.has_fullscreen));
+
+        disconnect(service, server);
+    }
+
+    #[test]
+    fn created_workspace_is_activated_once_announced() {
+        let (service, server) = connect(workspace_scene());
+
+        service.create_workspace_on_monitor("HDMI-A-1", 4).unwrap();
+        // The first roundtrip brings the new workspace, which is activated as it arrives; the
+        // second brings the activation
+        roundtrip(&service);
+        roundtrip(&service);
+
+        let created = service.query_workspaces().unwrap().into_iter().find(|ws| ws.name == "4").unwrap();
+        assert_eq!(created.monitor, "HDMI-A-1");
+        assert_eq!(
+            sorted_monitors(&service),
+            vec![("DP-1".to_string(), Some(2)), ("HDMI-A-1".to_string(), Some(created.id))],
+        );
+        assert_eq!(service.model().pending_activation, None);
+
+        disconnect(service, server);
+    }
+
+    #[test]
+    fn failed_workspace_creation_activates_nothing() {
+        let (service, server) = connect(workspace_scene());
+
+        assert!(service.create_workspace_on_monitor("eDP-1", 4).is_err());
+        assert_eq!(service.model().pending_activation, None);
+
+        disconnect(service, server);
+    }
+
+    /// eDP-1 and DP-1 with no ext-workspace. kitty has focus on eDP-1, while firefox and a
+    /// fullscreen mpv are on DP-1.
+    fn output_scene() -> Scene {
+        Scene {
+            outputs: vec!["eDP-1", "DP-1"],
+            workspaces: None,
+            toplevels: vec![
+                toplevel("kitty", 0, true, false),
+                toplevel("firefox", 1, false, false),
+                toplevel("mpv", 1, false, true),
+            ],
+            later: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn without_ext_workspace_each_output_is_a_workspace() {
+        let (service, server) = connect(output_scene());
+
+        let mut workspaces = service.query_workspaces().unwrap();
+        workspaces.sort_by_key(|ws| ws.id);
+        let summary: Vec<(i32, i32, &str, &str, i32, bool)> = workspaces
+            .iter()
+            .map(|ws| (ws.id, ws.index, ws.name.as_str(), ws.monitor.as_str(), ws.windows, ws.has_fullscreen))
+            .collect();
+        assert_eq!(summary, vec![(1, 1, "1", "DP-1", 2, true), (2, 2, "2", "eDP-1", 1, false)]);
+
+        assert_eq!(
+            sorted_monitors(&service),
+            vec![("DP-1".to_string(), Some(1)), ("eDP-1".to_string(), Some(2))],
+        );
+
+        let windows = sorted_windows(&service);
+        let summary: Vec<(&str, i32, i32)> = windows
+            .iter()
+            .map(|window| (window.class.as_str(), window.workspace_id, window.monitor))
+            .collect();
+        assert_eq!(summary, vec![("kitty", 2, 1), ("firefox", 1, 0), ("mpv", 1, 0)]);
+
+        assert_eq!(service.query_active_workspace(), Ok(2));
+        assert_eq!(service.query_active_window(), Ok(Some(windows[0].address.clone())));
+
+        disconnect(service, server);
+    }
+
+    #[test]
+    fn without_ext_workspace_removed_output_drops_its_workspace() {
+        let mut scene = output_scene();
+        scene.later = vec![vec![Change::RemoveOutput(1)]];
+        let (service, server) = connect(scene);
+
+        assert_eq!(roundtrip(&service), vec![WorkspaceEvent::MonitorsChanged, WorkspaceEvent::Changed]);
+        assert_eq!(sorted_monitors(&service), vec![("eDP-1".to_string(), Some(1))]);
+
+        let workspaces = service.query_workspaces().unwrap();
+        assert_eq!(workspaces.len(), 1);
+        assert_eq!((workspaces[0].monitor.as_str(), workspaces[0].windows), ("eDP-1", 1));
+
+        disconnect(service, server);
+    }
+}
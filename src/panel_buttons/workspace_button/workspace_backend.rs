@@ -0,0 +1,213 @@
+use std::env;
+use std::sync::Arc;
+
+use super::hyprland_service::HyprlandService;
+use super::niri_service::NiriService;
+use super::sway_service::SwayService;
+use super::wayland_toplevel_service::WaylandToplevelService;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceInfo {
+    pub id: i32,
+    /// Position among its monitor's workspaces, used for ordering and as the button label
+    pub index: i32,
+    pub name: String,
+    pub monitor: String,
+    pub windows: i32,
+    pub has_fullscreen: bool,
+    /// One of its windows is asking for attention
+    pub urgent: bool,
+    /// A scratchpad workspace that's toggled over the current one rather than switched to
+    pub special: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowInfo {
+    pub address: String,
+    pub workspace_id: i32,
+    pub class: String,
+    pub title: String,
+    pub monitor: i32,
+    pub pid: i32,
+    /// Order within its workspace (niri column, sway tree order), or 0 if the compositor has none
+    pub position: i32,
+    /// Asking for attention; cleared once the window is focused
+    pub urgent: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    pub name: String,
+    pub active_workspace_id: Option<i32>,
+    /// The special workspace currently shown over the active one, if any
+    pub special_workspace_id: Option<i32>,
+    pub dpms_on: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkspaceState {
+    pub workspaces: Vec<WorkspaceInfo>,
+    pub windows: Vec<WindowInfo>,
+    pub active_workspace_id: i32,
+    pub active_window_address: Option<String>,
+    /// The special workspace open on this monitor, if any
+    pub special_workspace_id: Option<i32>,
+    pub _current_monitor: String,
+    /// False while the compositor's IPC can't be reached
+    pub connected: bool,
+}
+
+/// Events a backend reports from its compositor's event stream
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkspaceEvent {
+    /// Workspaces or windows changed; the full state should be re-queried
+    Changed,
+    /// A monitor was added or removed
+    MonitorsChanged,
+    /// A single change that can be applied to the last known state without re-querying
+    Update(WorkspaceUpdate),
+}
+
+/// Incremental changes for backends whose event stream carries enough detail
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkspaceUpdate {
+    /// A workspace was created; an empty `monitor` means the focused monitor
+    WorkspaceCreated(WorkspaceInfo),
+    WorkspaceDestroyed { id: i32 },
+    WorkspaceMoved { id: i32, monitor: String },
+    WorkspaceRenamed { id: i32, name: String },
+    /// The focused workspace changed, on the focused monitor unless it lives elsewhere
+    WorkspaceFocused { id: i32 },
+    MonitorFocused { monitor: String, workspace_id: i32 },
+    /// A special workspace was shown on or hidden from a monitor
+    SpecialWorkspaceToggled { monitor: String, id: Option<i32> },
+    /// A window was opened on the workspace with the given name
+    WindowOpened { address: String, workspace_name: String, class: String, title: String },
+    WindowClosed { address: String },
+    WindowMoved { address: String, workspace_id: i32 },
+    WindowTitleChanged { address: String, title: String },
+    ActiveWindowChanged { address: Option<String> },
+    /// A window asked for attention
+    WindowUrgent { address: String },
+    /// The focused window entered or left fullscreen
+    FullscreenChanged { fullscreen: bool },
+}
+
+/// How a backend learns that a monitor was powered off
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DpmsSupport {
+    /// The compositor doesn't report power state; `dpms_on` is always true
+    Unsupported,
+    /// Power changes arrive as `MonitorsChanged` events
+    Events,
+    /// Power state is reported but has to be polled with `query_monitors`
+    Polled,
+}
+
+/// A compositor IPC implementation the workspace bar can run on.
+/// Calls are made from both the GTK main thread and background threads.
+pub trait WorkspaceBackend: Send + Sync {
+    /// Name used in log messages
+    fn name(&self) -> &'static str;
+
+    fn query_workspaces(&self) -> Result<Vec<WorkspaceInfo>, String>;
+
+    fn query_windows(&self) -> Result<Vec<WindowInfo>, String>;
+
+    fn query_monitors(&self) -> Result<Vec<MonitorInfo>, String>;
+
+    fn dpms_support(&self) -> DpmsSupport {
+        DpmsSupport::Unsupported
+    }
+
+    /// Whether queried windows and workspaces carry the compositor's current urgency, so
+    /// urgency it has cleared can be dropped. Otherwise it's only known from events.
+    fn queries_urgency(&self) -> bool {
+        false
+    }
+
+    /// Get the globally focused workspace ID
+    fn query_active_workspace(&self) -> Result<i32, String>;
+
+    /// Get the focused window address, if any window has focus
+    fn query_active_window(&self) -> Result<Option<String>, String>;
+
+    fn switch_workspace(&self, workspace_id: i32) -> Result<(), String>;
+
+    /// Show or hide a special workspace on the focused monitor
+    fn toggle_special_workspace(&self, _workspace_name: &str) -> Result<(), String> {
+        Err(format!("{} doesn't support special workspaces", self.name()))
+    }
+
+    fn focus_window(&self, window_address: &str) -> Result<(), String>;
+
+    /// Whether the compositor lets clients minimize windows
+    fn can_minimize(&self) -> bool {
+        false
+    }
+
+    fn minimize_window(&self, _window_address: &str) -> Result<(), String> {
+        Err(format!("{} doesn't support minimizing windows", self.name()))
+    }
+
+    /// Ask a window to close, as if its close button was pressed
+    fn close_window(&self, window_address: &str) -> Result<(), String>;
+
+    /// Whether the compositor lets clients float, fullscreen and move windows between workspaces
+    fn can_arrange_windows(&self) -> bool {
+        false
+    }
+
+    fn toggle_floating(&self, _window_address: &str) -> Result<(), String> {
+        Err(format!("{} doesn't support floating windows", self.name()))
+    }
+
+    fn toggle_fullscreen(&self, _window_address: &str) -> Result<(), String> {
+        Err(format!("{} doesn't support fullscreening windows", self.name()))
+    }
+
+    /// Move a window to another workspace without following it there
+    fn move_window_to_workspace(&self, _window_address: &str, _workspace_id: i32) -> Result<(), String> {
+        Err(format!("{} doesn't support moving windows", self.name()))
+    }
+
+    /// Create workspace `workspace_id` on `monitor_name` and switch to it
+    fn create_workspace_on_monitor(&self, monitor_name: &str, workspace_id: i32) -> Result<(), String>;
+
+    fn move_workspace_to_monitor(&self, workspace_id: i32, monitor_name: &str) -> Result<(), String>;
+
+    /// Re-establish contact with the compositor after `listen` returned, e.g. by finding a
+    /// restarted instance. An error means it still can't be reached.
+    fn reconnect(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Block reading the compositor's event stream, calling `on_event` for each relevant event.
+    /// Returns when the stream ends or fails.
+    fn listen(&self, on_event: &dyn Fn(WorkspaceEvent)) -> Result<(), String>;
+}
+
+/// Pick a backend from the environment the compositor exports to its clients
+pub fn detect_backend() -> Option<Arc<dyn WorkspaceBackend>> {
+    if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        return Some(Arc::new(HyprlandService::new()));
+    }
+
+    if let Some(socket_path) = env::var_os("NIRI_SOCKET") {
+        return Some(Arc::new(NiriService::new(socket_path.into())));
+    }
+
+    if let Some(socket_path) = env::var_os("SWAYSOCK").or_else(|| env::var_os("I3SOCK")) {
+        return Some(Arc::new(SwayService::new(socket_path.into())));
+    }
+
+    // Any other compositor, as long as it speaks the standard toplevel protocols
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        match WaylandToplevelService::connect() {
+            Ok(service) => return Some(Arc::new(service)),
+            Err(e) => eprintln!("Wayland workspace backend unavailable: {}", e),
+        }
+    }
+
+    None
+}
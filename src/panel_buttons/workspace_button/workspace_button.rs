@@ -1,26 +1,60 @@
+use gtk::gdk::{ContentProvider, DragAction};
 use gtk::prelude::*;
-use gtk::{IconTheme, Widget};
+use gtk::{glib, DragSource, DropTarget, GestureClick, IconTheme, Widget, WidgetPaintable};
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 
+use crate::config::{WindowDisplayMode, WorkspaceButtonConfig};
+use crate::models::MenuItemModel;
 use crate::traits::CompositeWidget;
+use crate::types::TypedListStore;
+use crate::util::desktop_entries::DesktopEntryIndex;
 use crate::widgets::{PanelButton, PanelButtonGroup};
 
-use super::hyprland_service::{HyprlandService, WorkspaceState};
+use super::workspace_backend::{WindowInfo, WorkspaceInfo, WorkspaceState};
+use super::workspace_preview::{PreviewContent, WorkspacePreview};
+use super::workspace_service::WorkspaceService;
+
+/// Shown on special workspace toggles that have no configured label
+const SPECIAL_WORKSPACE_ICON: &str = "focus-windows-symbolic";
+
+/// Drag payload prefixes. Drags are plain strings so they can be dropped on the bar of
+/// another monitor's panel.
+const WINDOW_DRAG_PREFIX: &str = "window:";
+const WORKSPACE_DRAG_PREFIX: &str = "workspace:";
+
+/// Longest window title shown in `IconsAndTitles` mode before it's cut off
+const MAX_TITLE_CHARS: usize = 24;
 
 #[derive(Clone, Debug)]
 pub struct WorkspaceButton {
     button_group: PanelButtonGroup,
     state: Rc<RefCell<WorkspaceButtonState>>,
+    preview: WorkspacePreview,
 }
 
 #[derive(Debug)]
 struct WorkspaceButtonState {
     workspace_buttons: Vec<(i32, PanelButton)>, // (workspace_id, button)
-    app_icon_buttons: Vec<(String, PanelButton)>, // (window_address, button)
+    special_buttons: Vec<(i32, PanelButton)>, // (workspace_id, button)
+    window_buttons: Vec<(String, WindowButton)>, // (window address, or group key, button)
     consolidate_button: PanelButton,
     plus_button: PanelButton,
     current_monitor: Option<String>,
+    config: WorkspaceButtonConfig,
+    /// The state the buttons currently show, read by click handlers of reused buttons
+    last_state: Option<WorkspaceState>,
+}
+
+/// A taskbar button for one window, or for several windows of the same app in `Grouped` mode
+#[derive(Debug)]
+struct WindowButton {
+    button: PanelButton,
+    /// The dropdown listing a group's windows
+    menu: Option<TypedListStore<MenuItemModel>>,
+    context_menu: TypedListStore<MenuItemModel>,
+    /// What the menus were last built from, so they're only rebuilt when that changes
+    menu_key: String,
 }
 
 impl WorkspaceButton {
@@ -30,27 +64,31 @@ impl WorkspaceButton {
         let consolidate_button = PanelButton::from_icon_name("video-display-symbolic");
 
         consolidate_button.connect_button_clicked(move |_| {
-            HyprlandService::move_all_to_laptop();
+            WorkspaceService::consolidate_workspaces();
         });
 
         let state = Rc::new(RefCell::new(WorkspaceButtonState {
             workspace_buttons: Vec::new(),
-            app_icon_buttons: Vec::new(),
+            special_buttons: Vec::new(),
+            window_buttons: Vec::new(),
             consolidate_button: consolidate_button.clone(),
             plus_button: plus_button.clone(),
             current_monitor: None,
+            config: WorkspaceButtonConfig::default(),
+            last_state: None,
         }));
 
         let obj = Self {
             button_group: button_group.clone(),
             state: state.clone(),
+            preview: WorkspacePreview::new(&button_group),
         };
 
         // Connect the + button
         let state_clone = state.clone();
         plus_button.connect_button_clicked(move |_| {
             if let Some(monitor_name) = state_clone.borrow().current_monitor.clone() {
-                HyprlandService::create_new_workspace_on_monitor(&monitor_name);
+                WorkspaceService::create_new_workspace_on_monitor(&monitor_name);
             } else {
                 eprintln!("Warning: + button clicked but monitor not yet initialized");
             }
@@ -73,32 +111,36 @@ impl WorkspaceButton {
         obj
     }
 
-    pub fn new_with_monitor(monitor_name: String) -> Self {
+    pub fn new_with_monitor(monitor_name: String, config: WorkspaceButtonConfig) -> Self {
         let button_group = PanelButtonGroup::new();
         let plus_button = PanelButton::from_text("+");
         let consolidate_button = PanelButton::from_icon_name("video-display-symbolic");
 
         consolidate_button.connect_button_clicked(move |_| {
-            HyprlandService::move_all_to_laptop();
+            WorkspaceService::consolidate_workspaces();
         });
 
         let state = Rc::new(RefCell::new(WorkspaceButtonState {
             workspace_buttons: Vec::new(),
-            app_icon_buttons: Vec::new(),
+            special_buttons: Vec::new(),
+            window_buttons: Vec::new(),
             consolidate_button: consolidate_button.clone(),
             plus_button: plus_button.clone(),
             current_monitor: Some(monitor_name.clone()),
+            config,
+            last_state: None,
         }));
 
         let obj = Self {
             button_group: button_group.clone(),
             state: state.clone(),
+            preview: WorkspacePreview::new(&button_group),
         };
 
         // Connect the + button
         let monitor_name_for_button = monitor_name.clone();
         plus_button.connect_button_clicked(move |_| {
-            HyprlandService::create_new_workspace_on_monitor(&monitor_name_for_button);
+            WorkspaceService::create_new_workspace_on_monitor(&monitor_name_for_button);
         });
 
         // Add the + button initially
@@ -117,15 +159,38 @@ impl WorkspaceButton {
             state.current_monitor = Some(monitor_name.clone());
         }
 
-        // Start the Hyprland service
-        let initial_state = HyprlandService::start(monitor_name.clone());
+        // Start the workspace service
+        let initial_state = WorkspaceService::start(monitor_name.clone());
 
         // Update UI with initial state
         self.update_ui(&initial_state);
 
+        // Workspace buttons dragged from another monitor's bar move that workspace here
+        let drop_target = DropTarget::new(glib::Type::STRING, DragAction::MOVE);
+        let state = Rc::downgrade(&self.state);
+        drop_target.connect_drop(move |_, value, _, _| {
+            let Some(state) = state.upgrade() else { return false };
+            let Some(workspace_id) = value.get::<String>().ok()
+                .and_then(|payload| payload.strip_prefix(WORKSPACE_DRAG_PREFIX)?.parse::<i32>().ok())
+            else {
+                return false;
+            };
+
+            let state = state.borrow();
+            let Some(monitor_name) = state.current_monitor.as_ref() else { return false };
+            // Already on this monitor
+            if state.workspace_buttons.iter().any(|(id, _)| *id == workspace_id) {
+                return false;
+            }
+
+            WorkspaceService::move_workspace_to_monitor(workspace_id, monitor_name);
+            true
+        });
+        self.button_group.add_controller(drop_target);
+
         // Subscribe to workspace changes for this monitor
         let obj_clone = self.clone();
-        HyprlandService::subscribe(monitor_name, move |workspace_state| {
+        WorkspaceService::subscribe(monitor_name, move |workspace_state| {
             obj_clone.update_ui(&workspace_state);
         });
     }
@@ -138,104 +203,321 @@ impl WorkspaceButton {
         let monitor = display.monitor_at_surface(&surface)?;
 
         // Try to get connector name (e.g., "DP-1", "HDMI-A-1")
-        // This should match Hyprland's monitor names
+        // This should match the compositor's monitor names
         monitor.connector().map(|s| s.to_string())
     }
 
-    /// Update the UI based on workspace state
+    /// Update the UI based on workspace state. Buttons are matched to workspaces by ID and to
+    /// windows by address, so only what actually changed is created, removed or restyled.
     fn update_ui(&self, workspace_state: &WorkspaceState) {
         let mut state = self.state.borrow_mut();
 
-        // Remove all existing buttons (workspaces, app icons, and + button)
-        for (_, button) in &state.workspace_buttons {
-            self.button_group.remove_button(button);
-        }
-        state.workspace_buttons.clear();
-
-        for (_, button) in &state.app_icon_buttons {
-            self.button_group.remove_button(button);
-        }
-        state.app_icon_buttons.clear();
+        // Buttons for each visible workspace; special workspaces get toggles after them
+        let (mut special_workspaces, mut workspaces): (Vec<WorkspaceInfo>, Vec<WorkspaceInfo>) = workspace_state
+            .workspaces
+            .iter()
+            .cloned()
+            .partition(|ws| ws.special);
+        workspaces.sort_by_key(|ws| (ws.index, ws.id));
+        special_workspaces.sort_by(|a, b| a.name.cmp(&b.name));
 
-        // Remove the consolidate and + buttons
-        self.button_group.remove_button(&state.consolidate_button);
-        self.button_group.remove_button(&state.plus_button);
-
-        // Create buttons for each visible workspace (exclude special workspaces with negative IDs)
-        let mut workspaces = workspace_state.workspaces.clone();
-        workspaces.retain(|ws| ws.id >= 0);
-        workspaces.sort_by_key(|ws| ws.id);
+        let mut previous_workspace_buttons = std::mem::take(&mut state.workspace_buttons);
 
         for workspace in workspaces {
-            let button = PanelButton::from_text(&workspace.id.to_string());
-
-            // Add CSS class for active workspace (for underline styling)
-            if workspace.id == workspace_state.active_workspace_id {
-                let button_widget: Widget = button.clone().upcast();
-                button_widget.add_css_class("workspace-active");
+            let button = match previous_workspace_buttons.iter().position(|(id, _)| *id == workspace.id) {
+                Some(position) => previous_workspace_buttons.swap_remove(position).1,
+                None => self.create_workspace_button(workspace.id),
+            };
+
+            // Only configured labels can be icons; a workspace named e.g. "firefox" keeps its name
+            let label = state.config.labels
+                .get(&workspace.name)
+                .or_else(|| state.config.labels.get(&workspace.index.to_string()));
+            match label {
+                Some(label) => set_label(&button, label),
+                None => set_text_label(&button, &default_workspace_label(&workspace)),
             }
+            // Underline the active workspace
+            set_css_class(&button, "workspace-active", workspace.id == workspace_state.active_workspace_id);
+            // Pulse while one of its windows wants attention
+            set_css_class(&button, "workspace-urgent", workspace.urgent);
 
-            // Connect click handler
-            let workspace_id = workspace.id;
-            button.connect_button_clicked(move |_| {
-                HyprlandService::switch_workspace(workspace_id);
-            });
-
-            // Add button to the group
-            self.button_group.add_button(&button);
             state.workspace_buttons.push((workspace.id, button));
         }
 
-        // Add consolidate button before the + button
-        self.button_group.add_button(&state.consolidate_button);
+        let mut previous_special_buttons = std::mem::take(&mut state.special_buttons);
+
+        for workspace in special_workspaces {
+            let button = match previous_special_buttons.iter().position(|(id, _)| *id == workspace.id) {
+                Some(position) => previous_special_buttons.swap_remove(position).1,
+                None => Self::create_special_button(&workspace.name),
+            };
+
+            let short_name = workspace.name.strip_prefix("special:").unwrap_or(&workspace.name);
+            let label = state.config.labels
+                .get(&workspace.name)
+                .or_else(|| state.config.labels.get(short_name))
+                .map(String::as_str)
+                .unwrap_or(SPECIAL_WORKSPACE_ICON);
+            set_label(&button, label);
+            // Highlight the toggle while the special workspace is shown on this monitor
+            set_css_class(&button, "workspace-active", workspace_state.special_workspace_id == Some(workspace.id));
+            set_css_class(&button, "workspace-urgent", workspace.urgent);
+
+            state.special_buttons.push((workspace.id, button));
+        }
 
-        // Add the + button after workspace buttons
         // Check if + button should be disabled
         let should_disable = workspace_state.workspaces.iter().any(|ws| {
             ws.id == workspace_state.active_workspace_id && ws.windows == 0
         });
+        set_css_class(&state.plus_button, "workspace-plus-disabled", should_disable);
 
-        let plus_button_widget: Widget = state.plus_button.clone().upcast();
-        if should_disable {
-            plus_button_widget.add_css_class("workspace-plus-disabled");
+        // App icon buttons for each window
+        // Windows are already filtered to this monitor's workspaces
+        let mut windows = workspace_state.windows.clone();
+        // Sort by workspace position first, then by layout position, then by address
+        // (as a proxy for creation time)
+        let workspace_index = |workspace_id: i32| {
+            workspace_state.workspaces.iter()
+                .find(|ws| ws.id == workspace_id)
+                .map(|ws| ws.index)
+                .unwrap_or(workspace_id)
+        };
+        windows.sort_by_key(|w| (workspace_index(w.workspace_id), w.workspace_id, w.position, w.address.clone()));
+
+        // In grouped mode an app's windows share one button, placed where its first window is
+        let display_mode = state.config.windows;
+        let entries: Vec<Vec<WindowInfo>> = if display_mode == WindowDisplayMode::Grouped {
+            let mut groups: Vec<Vec<WindowInfo>> = Vec::new();
+            for window in windows {
+                match groups.iter_mut().find(|group| group[0].class == window.class) {
+                    Some(group) => group.push(window),
+                    None => groups.push(vec![window]),
+                }
+            }
+            groups
+        } else {
+            windows.into_iter().map(|window| vec![window]).collect()
+        };
+
+        let mut previous_window_buttons = std::mem::take(&mut state.window_buttons);
+
+        for entry in entries {
+            // A group that shrinks to one window turns back into a plain window button
+            let key = match entry.as_slice() {
+                [window] => window.address.clone(),
+                _ => format!("group:{}", entry[0].class),
+            };
+
+            let mut window_button = match previous_window_buttons.iter().position(|(k, _)| *k == key) {
+                Some(position) => previous_window_buttons.swap_remove(position).1,
+                None if entry.len() == 1 => self.create_window_button(&key),
+                None => self.create_group_button(),
+            };
+            let button = &window_button.button;
+
+            let icon_name = get_icon_for_app(&entry[0].class);
+            if button.icon_name().as_deref() != Some(icon_name.as_str()) {
+                button.set_icon_name(&icon_name);
+            }
+
+            let text = match (display_mode, entry.as_slice()) {
+                (WindowDisplayMode::IconsAndTitles, [window]) => truncate_title(&window.title),
+                (WindowDisplayMode::Grouped, windows) if windows.len() > 1 => windows.len().to_string(),
+                _ => String::new(),
+            };
+            button.set_text(&text);
+
+            let is_active = entry.iter().any(|w| workspace_state.active_window_address.as_ref() == Some(&w.address));
+            set_css_class(button, "workspace-active", is_active);
+            set_css_class(button, "workspace-urgent", entry.iter().any(|w| w.urgent));
+
+            update_window_menus(&mut window_button, &entry, &workspace_state.workspaces);
+
+            state.window_buttons.push((key, window_button));
+        }
+
+        // Workspaces, then the consolidate and + buttons, then app icons
+        let buttons: Vec<PanelButton> = state.workspace_buttons.iter()
+            .map(|(_, button)| button.clone())
+            .chain(state.special_buttons.iter().map(|(_, button)| button.clone()))
+            .chain([state.consolidate_button.clone(), state.plus_button.clone()])
+            .chain(state.window_buttons.iter().map(|(_, window_button)| window_button.button.clone()))
+            .collect();
+        self.button_group.set_buttons(&buttons);
+
+        // Dim the bar while the compositor can't be reached
+        if workspace_state.connected {
+            self.button_group.remove_css_class("disconnected");
         } else {
-            plus_button_widget.remove_css_class("workspace-plus-disabled");
+            self.button_group.add_css_class("disconnected");
         }
 
-        self.button_group.add_button(&state.plus_button);
+        state.last_state = Some(workspace_state.clone());
+    }
 
-        // Create app icon buttons for each window
-        // Windows are already filtered to this monitor's workspaces
-        let mut windows = workspace_state.windows.clone();
-        // Sort by workspace_id first, then by address (as a proxy for creation time)
-        windows.sort_by_key(|w| (w.workspace_id, w.address.clone()));
-
-        for window in windows {
-            let icon_name = get_icon_for_app(&window.class);
-            let button = PanelButton::from_icon_name(&icon_name);
-
-            // Add CSS class for active window
-            if let Some(ref active_address) = workspace_state.active_window_address {
-                if &window.address == active_address {
-                    let button_widget: Widget = button.clone().upcast();
-                    button_widget.add_css_class("workspace-active");
+    fn create_workspace_button(&self, workspace_id: i32) -> PanelButton {
+        let button = PanelButton::new();
+        let preview = self.preview.clone();
+        button.connect_button_clicked(move |_| {
+            preview.hide();
+            WorkspaceService::switch_workspace(workspace_id);
+        });
+
+        // Hovering lists the workspace's windows
+        let state = Rc::downgrade(&self.state);
+        self.preview.attach(&button, move || {
+            let state = state.upgrade()?;
+            let state = state.borrow();
+            let ws_state = state.last_state.as_ref()?;
+            let workspace = ws_state.workspaces.iter().find(|ws| ws.id == workspace_id)?;
+
+            let mut windows: Vec<&WindowInfo> = ws_state.windows.iter().filter(|w| w.workspace_id == workspace_id).collect();
+            windows.sort_by_key(|w| (w.position, w.address.clone()));
+
+            Some(PreviewContent {
+                title: format!("Workspace {}", default_workspace_label(workspace)),
+                windows: windows.into_iter().map(|w| (get_icon_for_app(&w.class), get_app_name(&w.class), w.clone())).collect(),
+                // Only a workspace that's on screen can be captured
+                visible_on: (workspace_id == ws_state.active_workspace_id).then(|| workspace.monitor.clone()),
+            })
+        });
+
+        // Drag onto another monitor's bar to move the workspace there
+        add_drag_source(&button, format!("{}{}", WORKSPACE_DRAG_PREFIX, workspace_id));
+
+        // Drop an app icon here to send its window to this workspace
+        let drop_target = DropTarget::new(glib::Type::STRING, DragAction::MOVE);
+        // Workspace drags aren't taken, so they fall through to the bar's target
+        drop_target.connect_accept(|_, drop| {
+            drag_payload(drop).is_some_and(|payload| payload.starts_with(WINDOW_DRAG_PREFIX))
+        });
+        drop_target.connect_drop(move |_, value, _, _| {
+            let Some(address) = value.get::<String>().ok()
+                .and_then(|payload| payload.strip_prefix(WINDOW_DRAG_PREFIX).map(str::to_string))
+            else {
+                return false;
+            };
+
+            WorkspaceService::move_window_to_workspace(&address, workspace_id);
+            true
+        });
+        button.add_controller(drop_target);
+
+        button
+    }
+
+    fn create_special_button(workspace_name: &str) -> PanelButton {
+        let button = PanelButton::new();
+        let workspace_name = workspace_name.to_string();
+        button.connect_button_clicked(move |_| {
+            WorkspaceService::toggle_special_workspace(&workspace_name);
+        });
+        button
+    }
+
+    fn create_window_button(&self, window_address: &str) -> WindowButton {
+        let button = PanelButton::new();
+
+        let state = Rc::downgrade(&self.state);
+        let address = window_address.to_string();
+        button.connect_button_clicked(move |_| {
+            // Clicking the focused window minimizes it, like a traditional taskbar
+            Self::activate_window(&state, &address, true);
+        });
+
+        // Middle-click closes the window
+        let middle_click = GestureClick::new();
+        middle_click.set_button(gtk::gdk::BUTTON_MIDDLE);
+        let address = window_address.to_string();
+        middle_click.connect_released(move |_, _, _, _| {
+            WorkspaceService::close_window(&address);
+        });
+        button.add_controller(middle_click);
+
+        // Drag onto a workspace button to move the window there
+        if WorkspaceService::can_arrange_windows() {
+            add_drag_source(&button, format!("{}{}", WINDOW_DRAG_PREFIX, window_address));
+        }
+
+        let context_menu = TypedListStore::new();
+        button.set_context_menu(context_menu.clone());
+        self.connect_window_menu(&button);
+
+        WindowButton {
+            button,
+            menu: None,
+            context_menu,
+            menu_key: String::new(),
+        }
+    }
+
+    /// A button standing in for several windows of one app, listing them in a dropdown
+    fn create_group_button(&self) -> WindowButton {
+        let button = PanelButton::new();
+
+        let menu = TypedListStore::new();
+        button.set_menu(menu.clone());
+        let context_menu = TypedListStore::new();
+        button.set_context_menu(context_menu.clone());
+        self.connect_window_menu(&button);
+
+        WindowButton {
+            button,
+            menu: Some(menu),
+            context_menu,
+            menu_key: String::new(),
+        }
+    }
+
+    /// Handle the menu items built by `update_window_menus`, whose IDs are `action:address`
+    fn connect_window_menu(&self, button: &PanelButton) {
+        let state = Rc::downgrade(&self.state);
+        button.connect_menu_item_clicked(move |_, model| {
+            let id = model.id();
+            let Some((action, address)) = id.split_once(':') else { return };
+
+            match action {
+                "focus" => Self::activate_window(&state, address, false),
+                "minimize" => WorkspaceService::minimize_window(address),
+                "floating" => WorkspaceService::toggle_floating(address),
+                "fullscreen" => WorkspaceService::toggle_fullscreen(address),
+                "close" => WorkspaceService::close_window(address),
+                "move" => {
+                    // move:<workspace_id>:<address>
+                    if let Some((workspace_id, address)) = address.split_once(':')
+                        && let Ok(workspace_id) = workspace_id.parse()
+                    {
+                        WorkspaceService::move_window_to_workspace(address, workspace_id);
+                    }
                 }
+                _ => {}
             }
+        });
+    }
 
-            // Connect click handler
-            let window_address = window.address.clone();
-            let workspace_id = window.workspace_id;
-            button.connect_button_clicked(move |_| {
-                // First switch to the workspace
-                HyprlandService::switch_workspace(workspace_id);
-                // Then focus the window
-                HyprlandService::focus_window(&window_address);
-            });
+    /// Switch to a window's workspace and focus it, or minimize it if it's already focused
+    /// and `minimize_if_active` is set
+    fn activate_window(state: &Weak<RefCell<WorkspaceButtonState>>, address: &str, minimize_if_active: bool) {
+        let Some(state) = state.upgrade() else { return };
+        // The window can move between workspaces while the button lives on, so look its
+        // workspace up in the current state
+        let Some((workspace_id, is_active)) = state.borrow().last_state.as_ref().and_then(|ws_state| {
+            let window = ws_state.windows.iter().find(|w| w.address == address)?;
+            Some((window.workspace_id, ws_state.active_window_address.as_deref() == Some(address)))
+        }) else {
+            return;
+        };
 
-            // Add button to the group
-            self.button_group.add_button(&button);
-            state.app_icon_buttons.push((window.address.clone(), button));
+        if minimize_if_active && is_active && WorkspaceService::can_minimize() {
+            WorkspaceService::minimize_window(address);
+            return;
         }
+        // First switch to the workspace
+        WorkspaceService::switch_workspace(workspace_id);
+        // Then focus the window
+        WorkspaceService::focus_window(address);
     }
 }
 
@@ -245,6 +527,178 @@ impl CompositeWidget for WorkspaceButton {
     }
 }
 
+fn set_css_class(button: &PanelButton, class: &str, enabled: bool) {
+    if enabled {
+        button.add_css_class(class);
+    } else {
+        button.remove_css_class(class);
+    }
+}
+
+/// Make a button draggable, carrying `payload` and showing the button itself under the pointer
+fn add_drag_source(button: &PanelButton, payload: String) {
+    let drag_source = DragSource::new();
+    drag_source.set_actions(DragAction::MOVE);
+    drag_source.set_content(Some(&ContentProvider::for_value(&payload.to_value())));
+
+    let button_weak = button.downgrade();
+    drag_source.connect_drag_begin(move |source, _| {
+        if let Some(button) = button_weak.upgrade() {
+            source.set_icon(Some(&WidgetPaintable::new(Some(&button))), 0, 0);
+        }
+    });
+
+    button.add_controller(drag_source);
+}
+
+/// The payload of a drag started from one of our buttons, which is readable before the drop
+fn drag_payload(drop: &gtk::gdk::Drop) -> Option<String> {
+    drop.drag()?.content().value(glib::Type::STRING).ok()?.get::<String>().ok()
+}
+
+/// A workspace's name, unless it's just its number
+fn default_workspace_label(workspace: &WorkspaceInfo) -> String {
+    let number = workspace.index.to_string();
+    if workspace.name.is_empty() || workspace.name == number {
+        number
+    } else {
+        workspace.name.clone()
+    }
+}
+
+/// Show a label as an icon if the icon theme has one by that name, otherwise as text
+fn set_label(button: &PanelButton, label: &str) {
+    let is_icon = gtk::gdk::Display::default()
+        .is_some_and(|display| IconTheme::for_display(&display).has_icon(label));
+
+    if is_icon {
+        if button.icon_name().as_deref() != Some(label) {
+            button.set_icon_name(label);
+        }
+        button.set_text("");
+    } else {
+        set_text_label(button, label);
+    }
+}
+
+/// Show a label as text, even if it happens to name an icon
+fn set_text_label(button: &PanelButton, label: &str) {
+    if button.icon_name().is_some() {
+        button.clear_icon_name();
+    }
+    button.set_text(label);
+}
+
+fn truncate_title(title: &str) -> String {
+    if title.chars().count() <= MAX_TITLE_CHARS {
+        title.to_string()
+    } else {
+        format!("{}…", title.chars().take(MAX_TITLE_CHARS - 1).collect::<String>())
+    }
+}
+
+/// Rebuild a window button's dropdown and right-click menu if its windows or the workspaces
+/// they can be moved to have changed
+fn update_window_menus(window_button: &mut WindowButton, windows: &[WindowInfo], workspaces: &[WorkspaceInfo]) {
+    // Workspaces on this monitor as (id, label), in bar order
+    let mut targets: Vec<&WorkspaceInfo> = workspaces.iter().filter(|ws| !ws.special).collect();
+    targets.sort_by_key(|ws| (ws.index, ws.id));
+    let move_targets: Vec<(i32, String)> = targets
+        .into_iter()
+        .map(|ws| (ws.id, default_workspace_label(ws)))
+        .collect();
+
+    let menu_key = format!(
+        "{:?}",
+        (windows.iter().map(|w| (&w.address, &w.title, w.workspace_id)).collect::<Vec<_>>(), &move_targets)
+    );
+    if window_button.menu_key == menu_key {
+        return;
+    }
+    window_button.menu_key = menu_key;
+
+    if let Some(menu) = &window_button.menu {
+        let items = windows.iter().map(|window| {
+            let item = MenuItemModel::new(&format!("focus:{}", window.address), &window_label(window));
+            item.set_icon_name(Some(&get_icon_for_app(&window.class)));
+            item
+        });
+        replace_menu_items(menu, items);
+    }
+
+    // A single window gets its actions directly; a group gets a submenu of actions per window
+    match windows {
+        [window] => replace_menu_items(&window_button.context_menu, window_actions(window, &move_targets)),
+        _ => {
+            let items = windows.iter().map(|window| {
+                let item = MenuItemModel::new(&format!("window:{}", window.address), &window_label(window));
+                item.set_icon_name(Some(&get_icon_for_app(&window.class)));
+                for action in window_actions(window, &move_targets) {
+                    item.submenu().append(action);
+                }
+                item
+            });
+            replace_menu_items(&window_button.context_menu, items);
+        }
+    }
+}
+
+/// The right-click actions the compositor supports for a window
+fn window_actions(window: &WindowInfo, move_targets: &[(i32, String)]) -> Vec<MenuItemModel> {
+    let address = &window.address;
+    let mut actions = Vec::new();
+
+    if WorkspaceService::can_minimize() {
+        actions.push(MenuItemModel::new(&format!("minimize:{}", address), "Minimize"));
+    }
+
+    if WorkspaceService::can_arrange_windows() {
+        actions.push(MenuItemModel::new(&format!("floating:{}", address), "Toggle Floating"));
+        actions.push(MenuItemModel::new(&format!("fullscreen:{}", address), "Toggle Fullscreen"));
+
+        let move_to = MenuItemModel::new(&format!("move-list:{}", address), "Move to Workspace");
+        for (workspace_id, label) in move_targets.iter().filter(|(id, _)| *id != window.workspace_id) {
+            move_to.submenu().append(MenuItemModel::new(
+                &format!("move:{}:{}", workspace_id, address),
+                &format!("Workspace {}", label),
+            ));
+        }
+        if move_to.has_submenu() {
+            actions.push(move_to);
+        }
+    }
+
+    if let Some(last) = actions.last() {
+        last.set_separator_after(true);
+    }
+    actions.push(MenuItemModel::new(&format!("close:{}", address), "Close"));
+
+    actions
+}
+
+fn window_label(window: &WindowInfo) -> String {
+    if window.title.is_empty() {
+        window.class.clone()
+    } else {
+        truncate_title(&window.title)
+    }
+}
+
+fn replace_menu_items(menu: &TypedListStore<MenuItemModel>, items: impl IntoIterator<Item = MenuItemModel>) {
+    menu.as_list_store().remove_all();
+    for item in items {
+        menu.append(item);
+    }
+}
+
+/// The app's name from its desktop entry, or its class if it has none
+fn get_app_name(app_class: &str) -> String {
+    DesktopEntryIndex::shared()
+        .lookup(app_class)
+        .map(|entry| entry.name.clone())
+        .unwrap_or_else(|| app_class.to_string())
+}
+
 /// Helper function to get an icon name for an application class
 fn get_icon_for_app(app_class: &str) -> String {
     let display = match gtk::gdk::Display::default() {
@@ -257,19 +711,16 @@ fn get_icon_for_app(app_class: &str) -> String {
 
     let icon_theme = IconTheme::for_display(&display);
 
-    // Try the app class as-is (often works for common apps like "firefox", "kitty", etc.)
-    if icon_theme.has_icon(app_class) {
-        return app_class.to_string();
+    // The app's desktop entry names the icon it wants, so prefer that over guessing
+    if let Some(icon) = DesktopEntryIndex::shared().lookup(app_class).and_then(|entry| entry.icon.clone())
+        && icon_theme.has_icon(&icon)
+    {
+        return icon;
     }
 
-    // Try lowercase version
+    // Try the app class as-is (often works for common apps like "firefox", "kitty", etc.)
     let lowercase = app_class.to_lowercase();
-    if icon_theme.has_icon(&lowercase) {
-        return lowercase;
-    }
-
-    // Try common icon name patterns
-    let patterns = vec![
+    let patterns = [
         app_class.to_string(),
         lowercase.clone(),
         format!("{}-icon", lowercase),
@@ -282,62 +733,6 @@ fn get_icon_for_app(app_class: &str) -> String {
         }
     }
 
-    // Try to find icon from desktop files
-    if let Some(icon) = find_icon_from_desktop_files(app_class) {
-        if icon_theme.has_icon(&icon) {
-            return icon;
-        }
-    }
-
     // Fallback to generic application icon
     "application-x-executable".to_string()
 }
-
-/// Search desktop files for an application and extract its icon
-fn find_icon_from_desktop_files(app_class: &str) -> Option<String> {
-    use std::fs;
-    use std::path::Path;
-
-    let home_dir = std::env::var("HOME").unwrap_or_default();
-    let desktop_dirs = vec![
-        "/usr/share/applications".to_string(),
-        "/usr/local/share/applications".to_string(),
-        format!("{}/.local/share/applications", home_dir),
-    ];
-
-    let lowercase_class = app_class.to_lowercase();
-
-    for dir in desktop_dirs {
-        let dir_path = Path::new(&dir);
-        if !dir_path.exists() {
-            continue;
-        }
-
-        if let Ok(entries) = fs::read_dir(dir_path) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.extension().and_then(|s| s.to_str()) != Some("desktop") {
-                    continue;
-                }
-
-                let filename = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
-                let filename_lower = filename.to_lowercase();
-
-                // Check if filename matches the app class
-                if filename_lower.contains(&lowercase_class) || lowercase_class.contains(&filename_lower) {
-                    if let Ok(contents) = fs::read_to_string(&path) {
-                        // Parse the desktop file for Icon= line
-                        for line in contents.lines() {
-                            if line.starts_with("Icon=") {
-                                let icon = line.trim_start_matches("Icon=").trim().to_string();
-                                return Some(icon);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    None
-}
@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use super::workspace_backend::{MonitorInfo, WorkspaceInfo};
+
+/// Which monitor each workspace belongs on, so workspaces moved away when a monitor is
+/// unplugged or powered off can be put back when it returns. Persisted across restarts in
+/// `$XDG_STATE_HOME/waltopanel/workspace_homes.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WorkspaceHomes {
+    /// Home connector by workspace ID
+    #[serde(default)]
+    homes: HashMap<i32, String>,
+    /// The workspace each connector was last showing while available
+    #[serde(default)]
+    active: HashMap<String, i32>,
+    /// Workspaces we moved off their home monitor. Their home isn't updated from where
+    /// they currently are until they've been moved back.
+    #[serde(default)]
+    displaced: HashSet<i32>,
+}
+
+impl WorkspaceHomes {
+    fn path() -> Option<PathBuf> {
+        // Tests work on homes in memory and mustn't touch the user's state
+        if cfg!(test) {
+            return None;
+        }
+
+        let state_dir = env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+
+        Some(state_dir.join("waltopanel").join("workspace_homes.json"))
+    }
+
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let Some(path) = Self::path() else { return };
+
+        if let Some(dir) = path.parent()
+            && let Err(e) = fs::create_dir_all(dir)
+        {
+            eprintln!("[WorkspaceHomes] Failed to create {}: {}", dir.display(), e);
+            return;
+        }
+
+        match serde_json::to_string_pretty(self) {
+            Ok(content) => {
+                if let Err(e) = fs::write(&path, content) {
+                    eprintln!("[WorkspaceHomes] Failed to write {}: {}", path.display(), e);
+                }
+            }
+            Err(e) => eprintln!("[WorkspaceHomes] Failed to serialize workspace homes: {}", e),
+        }
+    }
+
+    /// Learn homes from where workspaces currently are. Workspaces on monitors that aren't
+    /// `available`, and workspaces we displaced, keep the home they had. A workspace found
+    /// away from a home that isn't available was moved off it, by us or by the compositor
+    /// when the monitor went away, so it's displaced rather than rehomed.
+    pub fn record(&mut self, workspaces: &[WorkspaceInfo], monitors: &[MonitorInfo], available: &[String]) {
+        let mut changed = false;
+
+        for ws in workspaces {
+            if ws.special || !available.contains(&ws.monitor) || self.homes.get(&ws.id) == Some(&ws.monitor) {
+                continue;
+            }
+            match self.homes.get(&ws.id) {
+                Some(home) if !available.contains(home) => changed |= self.displaced.insert(ws.id),
+                Some(_) if self.displaced.contains(&ws.id) => {}
+                _ => {
+                    self.homes.insert(ws.id, ws.monitor.clone());
+                    changed = true;
+                }
+            }
+        }
+
+        for monitor in monitors.iter().filter(|m| available.contains(&m.name)) {
+            if let Some(workspace_id) = monitor.active_workspace_id
+                && self.active.get(&monitor.name) != Some(&workspace_id)
+            {
+                self.active.insert(monitor.name.clone(), workspace_id);
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.save();
+        }
+    }
+
+    /// Remember that we moved a workspace off its home monitor. Returns false if it has no
+    /// known home or was already displaced.
+    pub fn mark_displaced(&mut self, workspace_id: i32) -> bool {
+        let marked = self.homes.contains_key(&workspace_id) && self.displaced.insert(workspace_id);
+        if marked {
+            self.save();
+        }
+        marked
+    }
+
+    /// Displaced workspaces whose home monitor is available again, with that monitor
+    pub fn returning(&self, workspaces: &[WorkspaceInfo], available: &[String]) -> Vec<(i32, String)> {
+        workspaces
+            .iter()
+            .filter(|ws| self.displaced.contains(&ws.id))
+            .filter_map(|ws| {
+                let home = self.homes.get(&ws.id)?;
+                (available.contains(home) && *home != ws.monitor).then(|| (ws.id, home.clone()))
+            })
+            .collect()
+    }
+
+    /// Forget that a workspace was displaced, once it's back home or gone
+    pub fn clear_displaced(&mut self, workspace_ids: &[i32]) {
+        let before = self.displaced.len();
+        self.displaced.retain(|id| !workspace_ids.contains(id));
+        if self.displaced.len() != before {
+            self.save();
+        }
+    }
+
+    /// Stop tracking displaced workspaces that were destroyed, or that are already back home
+    /// (e.g. moved there by hand)
+    pub fn prune_displaced(&mut self, workspaces: &[WorkspaceInfo]) {
+        let settled: Vec<i32> = self.displaced
+            .iter()
+            .filter(|id| {
+                workspaces
+                    .iter()
+                    .find(|ws| ws.id == **id)
+                    .is_none_or(|ws| self.homes.get(&ws.id) == Some(&ws.monitor))
+            })
+            .copied()
+            .collect();
+        self.clear_displaced(&settled);
+    }
+
+    /// The workspace a monitor was showing before it went away
+    pub fn active_workspace(&self, monitor_name: &str) -> Option<i32> {
+        self.active.get(monitor_name).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::workspace_backend::WorkspaceUpdate;
+    use super::super::workspace_model::WorkspaceModel;
+
+    fn workspace(id: i32, monitor: &str) -> WorkspaceInfo {
+        WorkspaceInfo {
+            id,
+            index: id,
+            name: id.to_string(),
+            monitor: monitor.to_string(),
+            windows: 1,
+            has_fullscreen: false,
+            urgent: false,
+            special: false,
+        }
+    }
+
+    fn monitor(name: &str, active_workspace_id: i32) -> MonitorInfo {
+        MonitorInfo {
+            name: name.to_string(),
+            active_workspace_id: Some(active_workspace_id),
+            special_workspace_id: None,
+            dpms_on: true,
+        }
+    }
+
+    /// What the service does with each model it publishes
+    fn publish(homes: &mut WorkspaceHomes, model: &WorkspaceModel) {
+        let available: Vec<String> = model.monitors.iter().filter(|m| m.dpms_on).map(|m| m.name.clone()).collect();
+        homes.record(&model.workspaces, &model.monitors, &available);
+    }
+
+    /// Workspaces 1 and 2 on DP-1 and 3 and 4 on HDMI-A-1, with 4 showing on HDMI-A-1
+    fn model() -> WorkspaceModel {
+        WorkspaceModel {
+            workspaces: vec![workspace(1, "DP-1"), workspace(2, "DP-1"), workspace(3, "HDMI-A-1"), workspace(4, "HDMI-A-1")],
+            monitors: vec![monitor("DP-1", 1), monitor("HDMI-A-1", 4)],
+            active_workspace_id: 1,
+            focused_monitor: Some("DP-1".to_string()),
+            connected: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn workspaces_moved_by_compositor_on_unplug_return_on_replug() {
+        let mut homes = WorkspaceHomes::default();
+        let mut model = model();
+        publish(&mut homes, &model);
+
+        // HDMI-A-1 is unplugged; the compositor drops it and then moves its workspaces itself
+        model.monitors.retain(|m| m.name != "HDMI-A-1");
+        publish(&mut homes, &model);
+        for id in [3, 4] {
+            model.apply(WorkspaceUpdate::WorkspaceMoved { id, monitor: "DP-1".to_string() });
+            publish(&mut homes, &model);
+        }
+        assert!(homes.returning(&model.workspaces, &["DP-1".to_string()]).is_empty());
+
+        // It's plugged back in, empty
+        model.monitors.push(MonitorInfo { active_workspace_id: None, ..monitor("HDMI-A-1", 0) });
+        let available = ["DP-1".to_string(), "HDMI-A-1".to_string()];
+        publish(&mut homes, &model);
+
+        let mut returning = homes.returning(&model.workspaces, &available);
+        returning.sort();
+        assert_eq!(returning, vec![(3, "HDMI-A-1".to_string()), (4, "HDMI-A-1".to_string())]);
+        assert_eq!(homes.active_workspace("HDMI-A-1"), Some(4));
+
+        // Once moved back they're home again and no longer displaced
+        for id in [3, 4] {
+            model.apply(WorkspaceUpdate::WorkspaceMoved { id, monitor: "HDMI-A-1".to_string() });
+        }
+        publish(&mut homes, &model);
+        homes.prune_displaced(&model.workspaces);
+        assert!(homes.displaced.is_empty());
+        assert_eq!(homes.homes.get(&3).map(String::as_str), Some("HDMI-A-1"));
+    }
+
+    #[test]
+    fn workspace_moved_between_available_monitors_is_rehomed() {
+        let mut homes = WorkspaceHomes::default();
+        let mut model = model();
+        publish(&mut homes, &model);
+
+        model.apply(WorkspaceUpdate::WorkspaceMoved { id: 2, monitor: "HDMI-A-1".to_string() });
+        publish(&mut homes, &model);
+
+        assert_eq!(homes.homes.get(&2).map(String::as_str), Some("HDMI-A-1"));
+        assert!(homes.displaced.is_empty());
+    }
+
+    #[test]
+    fn workspace_displaced_before_its_move_keeps_its_home() {
+        let mut homes = WorkspaceHomes::default();
+        let mut model = model();
+        publish(&mut homes, &model);
+
+        // We move it off a powered-off monitor; the move event may be published before or
+        // after the monitor shows as powered off
+        assert!(homes.mark_displaced(3));
+        model.apply(WorkspaceUpdate::WorkspaceMoved { id: 3, monitor: "DP-1".to_string() });
+        publish(&mut homes, &model);
+
+        assert_eq!(homes.homes.get(&3).map(String::as_str), Some("HDMI-A-1"));
+        assert_eq!(homes.returning(&model.workspaces, &["HDMI-A-1".to_string()]), vec![(3, "HDMI-A-1".to_string())]);
+    }
+}
@@ -0,0 +1,475 @@
+use super::workspace_backend::{MonitorInfo, WindowInfo, WorkspaceBackend, WorkspaceInfo, WorkspaceUpdate};
+
+/// The full compositor state across all monitors. Kept up to date on the event listener
+/// thread, either by re-querying or by applying `WorkspaceUpdate`s, and handed to the main
+/// thread as a finished snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct WorkspaceModel {
+    pub workspaces: Vec<WorkspaceInfo>,
+    pub windows: Vec<WindowInfo>,
+    pub monitors: Vec<MonitorInfo>,
+    pub active_workspace_id: i32,
+    pub active_window_address: Option<String>,
+    pub focused_monitor: Option<String>,
+    /// False while the compositor's IPC can't be reached
+    pub connected: bool,
+}
+
+impl WorkspaceModel {
+    /// Query everything from the backend. Failed queries leave that part empty, and the model
+    /// counts as disconnected if the workspace list can't be fetched.
+    pub fn query(backend: &dyn WorkspaceBackend) -> Self {
+        let workspaces = backend.query_workspaces();
+        let connected = workspaces.is_ok();
+        let workspaces = workspaces.unwrap_or_default();
+        let monitors = backend.query_monitors().unwrap_or_default();
+        let active_workspace_id = backend.query_active_workspace().unwrap_or(1);
+        let focused_monitor = workspaces
+            .iter()
+            .find(|ws| ws.id == active_workspace_id)
+            .map(|ws| ws.monitor.clone());
+
+        let mut model = Self {
+            workspaces,
+            windows: backend.query_windows().unwrap_or_default(),
+            monitors,
+            active_workspace_id,
+            active_window_address: backend.query_active_window().unwrap_or(None),
+            focused_monitor,
+            connected,
+        };
+        for workspace in &mut model.workspaces {
+            workspace.urgent |= model.windows.iter().any(|w| w.workspace_id == workspace.id && w.urgent);
+        }
+        model
+    }
+
+    /// Re-query everything. For backends whose queries don't include urgency, urgency from
+    /// before is kept for windows that haven't been focused since, as it's only reported as
+    /// an event.
+    pub fn refresh(&mut self, backend: &dyn WorkspaceBackend) {
+        let mut fresh = Self::query(backend);
+        if backend.queries_urgency() {
+            *self = fresh;
+            return;
+        }
+
+        for window in &mut fresh.windows {
+            let was_urgent = self.windows.iter().any(|w| w.address == window.address && w.urgent);
+            if was_urgent && fresh.active_window_address.as_ref() != Some(&window.address) {
+                window.urgent = true;
+            }
+        }
+        fresh.recount_windows();
+
+        *self = fresh;
+    }
+
+    /// The workspace shown on a monitor, falling back to the globally focused one
+    pub fn active_workspace_for_monitor(&self, monitor_name: &str) -> i32 {
+        self.monitors
+            .iter()
+            .find(|monitor| monitor.name == monitor_name)
+            .and_then(|monitor| monitor.active_workspace_id)
+            .unwrap_or(self.active_workspace_id)
+    }
+
+    /// Apply an incremental change from the backend's event stream
+    pub fn apply(&mut self, update: WorkspaceUpdate) {
+        match update {
+            WorkspaceUpdate::WorkspaceCreated(mut workspace) => {
+                if workspace.monitor.is_empty() {
+                    workspace.monitor = self.focused_monitor.clone().unwrap_or_default();
+                }
+                self.workspaces.retain(|ws| ws.id != workspace.id);
+                self.workspaces.push(workspace);
+            }
+            WorkspaceUpdate::WorkspaceDestroyed { id } => {
+                self.workspaces.retain(|ws| ws.id != id);
+                self.windows.retain(|w| w.workspace_id != id);
+                for monitor in self.monitors.iter_mut().filter(|m| m.special_workspace_id == Some(id)) {
+                    monitor.special_workspace_id = None;
+                }
+            }
+            WorkspaceUpdate::WorkspaceMoved { id, monitor } => {
+                let Some(workspace) = self.workspace_mut(id) else { return };
+                let source = std::mem::replace(&mut workspace.monitor, monitor.clone());
+                self.set_monitor_workspace(&monitor, id);
+
+                // The monitor it left shows one of its remaining workspaces instead
+                if source != monitor {
+                    let remaining = self.workspaces
+                        .iter()
+                        .filter(|ws| ws.monitor == source && !ws.special)
+                        .min_by_key(|ws| ws.index)
+                        .map(|ws| ws.id);
+                    if let Some(source) = self.monitors.iter_mut().find(|m| m.name == source)
+                        && source.active_workspace_id == Some(id)
+                    {
+                        source.active_workspace_id = remaining;
+                    }
+                }
+            }
+            WorkspaceUpdate::WorkspaceRenamed { id, name } => {
+                if let Some(workspace) = self.workspace_mut(id) {
+                    workspace.name = name;
+                }
+            }
+            WorkspaceUpdate::WorkspaceFocused { id } => {
+                self.active_workspace_id = id;
+                let monitor = self.workspaces
+                    .iter()
+                    .find(|ws| ws.id == id)
+                    .map(|ws| ws.monitor.clone())
+                    .or_else(|| self.focused_monitor.clone());
+                if let Some(monitor) = monitor {
+                    self.set_monitor_workspace(&monitor, id);
+                    self.focused_monitor = Some(monitor);
+                }
+            }
+            WorkspaceUpdate::MonitorFocused { monitor, workspace_id } => {
+                self.active_workspace_id = workspace_id;
+                self.set_monitor_workspace(&monitor, workspace_id);
+                self.focused_monitor = Some(monitor);
+            }
+            WorkspaceUpdate::SpecialWorkspaceToggled { monitor, id } => {
+                if let Some(monitor) = self.monitors.iter_mut().find(|m| m.name == monitor) {
+                    monitor.special_workspace_id = id;
+                }
+            }
+            WorkspaceUpdate::WindowOpened { address, workspace_name, class, title } => {
+                let Some(workspace_id) = self.workspaces
+                    .iter()
+                    .find(|ws| ws.name == workspace_name)
+                    .map(|ws| ws.id)
+                else {
+                    return;
+                };
+
+                self.windows.retain(|w| w.address != address);
+                self.windows.push(WindowInfo {
+                    address,
+                    workspace_id,
+                    class,
+                    title,
+                    monitor: self.monitor_index(workspace_id),
+                    pid: 0,
+                    position: 0,
+                    urgent: false,
+                });
+            }
+            WorkspaceUpdate::WindowClosed { address } => {
+                self.windows.retain(|w| w.address != address);
+                if self.active_window_address.as_ref() == Some(&address) {
+                    self.active_window_address = None;
+                }
+            }
+            WorkspaceUpdate::WindowMoved { address, workspace_id } => {
+                let monitor = self.monitor_index(workspace_id);
+                if let Some(window) = self.windows.iter_mut().find(|w| w.address == address) {
+                    window.workspace_id = workspace_id;
+                    window.monitor = monitor;
+                }
+            }
+            WorkspaceUpdate::WindowTitleChanged { address, title } => {
+                if let Some(window) = self.windows.iter_mut().find(|w| w.address == address) {
+                    window.title = title;
+                }
+            }
+            WorkspaceUpdate::ActiveWindowChanged { address } => {
+                if let Some(window) = self.windows.iter_mut().find(|w| Some(&w.address) == address.as_ref()) {
+                    window.urgent = false;
+                }
+                self.active_window_address = address;
+            }
+            WorkspaceUpdate::WindowUrgent { address } => {
+                // The focused window is already getting attention
+                if self.active_window_address.as_ref() != Some(&address)
+                    && let Some(window) = self.windows.iter_mut().find(|w| w.address == address)
+                {
+                    window.urgent = true;
+                }
+            }
+            WorkspaceUpdate::FullscreenChanged { fullscreen } => {
+                let workspace_id = self.active_window_address
+                    .as_ref()
+                    .and_then(|address| self.windows.iter().find(|w| &w.address == address))
+                    .map(|w| w.workspace_id)
+                    .unwrap_or(self.active_workspace_id);
+                if let Some(workspace) = self.workspace_mut(workspace_id) {
+                    workspace.has_fullscreen = fullscreen;
+                }
+            }
+        }
+
+        self.recount_windows();
+    }
+
+    fn workspace_mut(&mut self, id: i32) -> Option<&mut WorkspaceInfo> {
+        self.workspaces.iter_mut().find(|ws| ws.id == id)
+    }
+
+    fn set_monitor_workspace(&mut self, monitor_name: &str, workspace_id: i32) {
+        if let Some(monitor) = self.monitors.iter_mut().find(|m| m.name == monitor_name) {
+            monitor.active_workspace_id = Some(workspace_id);
+        }
+    }
+
+    /// Position of a workspace's monitor in the monitor list
+    fn monitor_index(&self, workspace_id: i32) -> i32 {
+        self.workspaces
+            .iter()
+            .find(|ws| ws.id == workspace_id)
+            .and_then(|ws| self.monitors.iter().position(|m| m.name == ws.monitor))
+            .unwrap_or(0) as i32
+    }
+
+    /// Bring each workspace's window count and urgency in line with its windows
+    fn recount_windows(&mut self) {
+        for workspace in &mut self.workspaces {
+            let windows = || self.windows.iter().filter(|w| w.workspace_id == workspace.id);
+            workspace.windows = windows().count() as i32;
+            workspace.urgent = windows().any(|w| w.urgent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(id: i32, name: &str, monitor: &str) -> WorkspaceInfo {
+        WorkspaceInfo {
+            id,
+            index: id.abs(),
+            name: name.to_string(),
+            monitor: monitor.to_string(),
+            windows: 0,
+            has_fullscreen: false,
+            urgent: false,
+            special: name.starts_with("special:"),
+        }
+    }
+
+    fn window(address: &str, workspace_id: i32) -> WindowInfo {
+        WindowInfo {
+            address: address.to_string(),
+            workspace_id,
+            class: "kitty".to_string(),
+            title: address.to_string(),
+            monitor: 0,
+            pid: 0,
+            position: 0,
+            urgent: false,
+        }
+    }
+
+    fn monitor(name: &str, active_workspace_id: i32) -> MonitorInfo {
+        MonitorInfo {
+            name: name.to_string(),
+            active_workspace_id: Some(active_workspace_id),
+            special_workspace_id: None,
+            dpms_on: true,
+        }
+    }
+
+    /// Workspaces 1 and 2 on DP-1 and 3 on HDMI-A-1, with windows a and b on 1 and c on 3
+    fn model() -> WorkspaceModel {
+        let mut model = WorkspaceModel {
+            workspaces: vec![workspace(1, "1", "DP-1"), workspace(2, "2", "DP-1"), workspace(3, "3", "HDMI-A-1")],
+            windows: vec![window("0xa", 1), window("0xb", 1), window("0xc", 3)],
+            monitors: vec![monitor("DP-1", 1), monitor("HDMI-A-1", 3)],
+            active_workspace_id: 1,
+            active_window_address: Some("0xa".to_string()),
+            focused_monitor: Some("DP-1".to_string()),
+            connected: true,
+        };
+        model.recount_windows();
+        model
+    }
+
+    fn find_workspace(model: &WorkspaceModel, id: i32) -> &WorkspaceInfo {
+        model.workspaces.iter().find(|ws| ws.id == id).unwrap()
+    }
+
+    fn find_window<'a>(model: &'a WorkspaceModel, address: &str) -> Option<&'a WindowInfo> {
+        model.windows.iter().find(|w| w.address == address)
+    }
+
+    fn monitor_workspace(model: &WorkspaceModel, name: &str) -> Option<i32> {
+        model.monitors.iter().find(|m| m.name == name).unwrap().active_workspace_id
+    }
+
+    #[test]
+    fn workspace_created_on_focused_monitor() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WorkspaceCreated(workspace(4, "4", "")));
+        assert_eq!(find_workspace(&model, 4).monitor, "DP-1");
+
+        model.apply(WorkspaceUpdate::WorkspaceCreated(workspace(5, "5", "HDMI-A-1")));
+        assert_eq!(find_workspace(&model, 5).monitor, "HDMI-A-1");
+    }
+
+    #[test]
+    fn workspace_created_again_replaces_it() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WorkspaceCreated(workspace(2, "two", "HDMI-A-1")));
+        assert_eq!(model.workspaces.iter().filter(|ws| ws.id == 2).count(), 1);
+        assert_eq!(find_workspace(&model, 2).name, "two");
+    }
+
+    #[test]
+    fn workspace_destroyed_takes_its_windows() {
+        let mut model = model();
+        model.monitors[1].special_workspace_id = Some(3);
+        model.apply(WorkspaceUpdate::WorkspaceDestroyed { id: 3 });
+
+        assert!(model.workspaces.iter().all(|ws| ws.id != 3));
+        assert!(find_window(&model, "0xc").is_none());
+        assert_eq!(model.monitors[1].special_workspace_id, None);
+    }
+
+    #[test]
+    fn workspace_moved_becomes_active_on_its_new_monitor() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WorkspaceMoved { id: 1, monitor: "HDMI-A-1".to_string() });
+
+        assert_eq!(find_workspace(&model, 1).monitor, "HDMI-A-1");
+        assert_eq!(monitor_workspace(&model, "HDMI-A-1"), Some(1));
+        // DP-1 no longer shows the workspace that left it
+        assert_eq!(monitor_workspace(&model, "DP-1"), Some(2));
+    }
+
+    #[test]
+    fn workspace_moved_from_inactive_position_leaves_source_alone() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WorkspaceMoved { id: 2, monitor: "HDMI-A-1".to_string() });
+
+        assert_eq!(monitor_workspace(&model, "DP-1"), Some(1));
+        assert_eq!(monitor_workspace(&model, "HDMI-A-1"), Some(2));
+    }
+
+    #[test]
+    fn last_workspace_moved_off_monitor_leaves_it_empty() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WorkspaceMoved { id: 3, monitor: "DP-1".to_string() });
+
+        assert_eq!(monitor_workspace(&model, "HDMI-A-1"), None);
+        assert_eq!(monitor_workspace(&model, "DP-1"), Some(3));
+    }
+
+    #[test]
+    fn workspace_renamed() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WorkspaceRenamed { id: 2, name: "mail, chat".to_string() });
+        assert_eq!(find_workspace(&model, 2).name, "mail, chat");
+    }
+
+    #[test]
+    fn workspace_focused_updates_its_monitor() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WorkspaceFocused { id: 3 });
+
+        assert_eq!(model.active_workspace_id, 3);
+        assert_eq!(model.focused_monitor.as_deref(), Some("HDMI-A-1"));
+        assert_eq!(monitor_workspace(&model, "DP-1"), Some(1));
+    }
+
+    #[test]
+    fn window_opened_on_named_workspace() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WindowOpened {
+            address: "0xd".to_string(),
+            workspace_name: "3".to_string(),
+            class: "firefox".to_string(),
+            title: "Inbox, 3 unread".to_string(),
+        });
+
+        let window = find_window(&model, "0xd").unwrap();
+        assert_eq!(window.workspace_id, 3);
+        assert_eq!(window.monitor, 1);
+        assert_eq!(window.title, "Inbox, 3 unread");
+        assert_eq!(find_workspace(&model, 3).windows, 2);
+    }
+
+    #[test]
+    fn window_opened_on_unknown_workspace_is_ignored() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WindowOpened {
+            address: "0xd".to_string(),
+            workspace_name: "nowhere".to_string(),
+            class: "firefox".to_string(),
+            title: String::new(),
+        });
+        assert!(find_window(&model, "0xd").is_none());
+    }
+
+    #[test]
+    fn window_closed() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WindowClosed { address: "0xa".to_string() });
+
+        assert!(find_window(&model, "0xa").is_none());
+        assert_eq!(model.active_window_address, None);
+        assert_eq!(find_workspace(&model, 1).windows, 1);
+    }
+
+    #[test]
+    fn window_moved_between_monitors() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WindowMoved { address: "0xb".to_string(), workspace_id: 3 });
+
+        let window = find_window(&model, "0xb").unwrap();
+        assert_eq!(window.workspace_id, 3);
+        assert_eq!(window.monitor, 1);
+        assert_eq!(find_workspace(&model, 1).windows, 1);
+        assert_eq!(find_workspace(&model, 3).windows, 2);
+    }
+
+    #[test]
+    fn urgent_window_marks_its_workspace_until_focused() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WindowUrgent { address: "0xc".to_string() });
+        assert!(find_window(&model, "0xc").unwrap().urgent);
+        assert!(find_workspace(&model, 3).urgent);
+
+        model.apply(WorkspaceUpdate::ActiveWindowChanged { address: Some("0xc".to_string()) });
+        assert!(!find_window(&model, "0xc").unwrap().urgent);
+        assert!(!find_workspace(&model, 3).urgent);
+    }
+
+    #[test]
+    fn urgent_focused_window_is_ignored() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WindowUrgent { address: "0xa".to_string() });
+        assert!(!find_workspace(&model, 1).urgent);
+    }
+
+    #[test]
+    fn urgency_follows_moved_window() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WindowUrgent { address: "0xb".to_string() });
+        model.apply(WorkspaceUpdate::WindowMoved { address: "0xb".to_string(), workspace_id: 2 });
+
+        assert!(!find_workspace(&model, 1).urgent);
+        assert!(find_workspace(&model, 2).urgent);
+    }
+
+    #[test]
+    fn urgency_goes_with_closed_window() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::WindowUrgent { address: "0xc".to_string() });
+        model.apply(WorkspaceUpdate::WindowClosed { address: "0xc".to_string() });
+        assert!(!find_workspace(&model, 3).urgent);
+    }
+
+    #[test]
+    fn fullscreen_applies_to_focused_window_workspace() {
+        let mut model = model();
+        model.apply(WorkspaceUpdate::ActiveWindowChanged { address: Some("0xc".to_string()) });
+        model.apply(WorkspaceUpdate::FullscreenChanged { fullscreen: true });
+
+        assert!(find_workspace(&model, 3).has_fullscreen);
+        assert!(!find_workspace(&model, 1).has_fullscreen);
+    }
+}
@@ -0,0 +1,190 @@
+use gtk::gdk::{MemoryFormat, MemoryTexture, Rectangle};
+use gtk::prelude::*;
+use gtk::{gio, glib, Box as GtkBox, Image, Label, Orientation, Picture, Popover, PositionType};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::widgets::{PanelButton, PanelButtonGroup};
+
+use super::screencopy;
+use super::workspace_backend::WindowInfo;
+
+/// How long the pointer has to rest on a workspace button before its preview opens
+const HOVER_DELAY: Duration = Duration::from_millis(400);
+const THUMBNAIL_WIDTH: i32 = 240;
+
+/// What a preview shows for one workspace
+pub struct PreviewContent {
+    pub title: String,
+    /// (icon name, app name, window) for each window on the workspace
+    pub windows: Vec<(String, String, WindowInfo)>,
+    /// The monitor the workspace is currently shown on, which can be captured for a thumbnail
+    pub visible_on: Option<String>,
+}
+
+/// A popover listing a workspace's windows while its button is hovered, with a thumbnail of
+/// the workspace when it's on screen and the compositor supports wlr-screencopy. One popover
+/// is shared by all of a bar's workspace buttons.
+#[derive(Clone, Debug)]
+pub struct WorkspacePreview {
+    popover: Popover,
+    group: PanelButtonGroup,
+    pending: Rc<RefCell<Option<glib::SourceId>>>,
+    /// Bumped on every show and hide, so a thumbnail that arrives late is dropped
+    generation: Rc<Cell<u64>>,
+}
+
+impl WorkspacePreview {
+    pub fn new(group: &PanelButtonGroup) -> Self {
+        let popover = Popover::builder()
+            .autohide(false)
+            .has_arrow(false)
+            .position(PositionType::Bottom)
+            .can_focus(false)
+            .css_classes(vec!["workspace-preview"])
+            .build();
+        popover.set_parent(group);
+
+        let popover_clone = popover.clone();
+        group.connect_destroy(move |_| popover_clone.unparent());
+
+        Self {
+            popover,
+            group: group.clone(),
+            pending: Rc::new(RefCell::new(None)),
+            generation: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// Show hover previews on `button`, asking `content` what to show each time it opens
+    pub fn attach(&self, button: &PanelButton, content: impl Fn() -> Option<PreviewContent> + 'static) {
+        let motion = gtk::EventControllerMotion::new();
+        let content = Rc::new(content);
+
+        let preview = self.clone();
+        let button_weak = button.downgrade();
+        motion.connect_enter(move |_, _, _| {
+            preview.cancel_pending();
+
+            let preview_clone = preview.clone();
+            let button_weak = button_weak.clone();
+            let content = content.clone();
+            let source = glib::timeout_add_local_once(HOVER_DELAY, move || {
+                preview_clone.pending.borrow_mut().take();
+                if let (Some(button), Some(content)) = (button_weak.upgrade(), content()) {
+                    preview_clone.show(&button, content);
+                }
+            });
+            *preview.pending.borrow_mut() = Some(source);
+        });
+
+        let preview = self.clone();
+        motion.connect_leave(move |_| preview.hide());
+
+        button.add_controller(motion);
+    }
+
+    pub fn hide(&self) {
+        self.cancel_pending();
+        self.generation.set(self.generation.get() + 1);
+        if self.popover.is_visible() {
+            self.popover.popdown();
+        }
+    }
+
+    fn cancel_pending(&self) {
+        if let Some(source) = self.pending.borrow_mut().take() {
+            source.remove();
+        }
+    }
+
+    fn show(&self, button: &PanelButton, content: PreviewContent) {
+        let generation = self.generation.get() + 1;
+        self.generation.set(generation);
+
+        let container = GtkBox::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(6)
+            .build();
+
+        let title = Label::builder()
+            .label(&content.title)
+            .xalign(0.0)
+            .css_classes(vec!["workspace-preview-title"])
+            .build();
+        container.append(&title);
+
+        let thumbnail = Picture::builder()
+            .can_shrink(true)
+            .visible(false)
+            .css_classes(vec!["workspace-preview-thumbnail"])
+            .build();
+        container.append(&thumbnail);
+
+        if content.windows.is_empty() {
+            container.append(&Label::builder().label("No windows").xalign(0.0).css_classes(vec!["dim-label"]).build());
+        }
+
+        for (icon_name, app_name, window) in &content.windows {
+            let row = GtkBox::builder()
+                .orientation(Orientation::Horizontal)
+                .spacing(8)
+                .build();
+            row.append(&Image::from_icon_name(icon_name));
+            row.append(&Label::builder().label(app_name).css_classes(vec!["workspace-preview-class"]).build());
+            row.append(
+                &Label::builder()
+                    .label(&window.title)
+                    .ellipsize(gtk::pango::EllipsizeMode::End)
+                    .max_width_chars(40)
+                    .css_classes(vec!["dim-label"])
+                    .build(),
+            );
+            container.append(&row);
+        }
+
+        self.popover.set_child(Some(&container));
+
+        if let Some(bounds) = button.compute_bounds(&self.group) {
+            self.popover.set_pointing_to(Some(&Rectangle::new(
+                bounds.x() as i32,
+                bounds.y() as i32,
+                bounds.width() as i32,
+                bounds.height() as i32,
+            )));
+        }
+        self.popover.popup();
+
+        if let Some(monitor_name) = content.visible_on {
+            self.load_thumbnail(&thumbnail, monitor_name, generation);
+        }
+    }
+
+    /// Capture the monitor off the main thread and show it, unless the preview has moved on.
+    /// If the capture fails, the preview just keeps its window list.
+    fn load_thumbnail(&self, thumbnail: &Picture, monitor_name: String, generation: u64) {
+        let thumbnail = thumbnail.clone();
+        let generation_cell = self.generation.clone();
+
+        glib::MainContext::default().spawn_local(async move {
+            let Ok(Ok(capture)) = gio::spawn_blocking(move || screencopy::capture_output(&monitor_name)).await else {
+                return;
+            };
+            if generation_cell.get() != generation {
+                return;
+            }
+
+            let texture = MemoryTexture::new(
+                capture.width,
+                capture.height,
+                MemoryFormat::B8g8r8a8,
+                &glib::Bytes::from_owned(capture.data),
+                capture.stride,
+            );
+            thumbnail.set_paintable(Some(&texture));
+            thumbnail.set_size_request(THUMBNAIL_WIDTH, THUMBNAIL_WIDTH * capture.height / capture.width.max(1));
+            thumbnail.set_visible(true);
+        });
+    }
+}
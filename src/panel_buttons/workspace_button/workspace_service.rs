@@ -0,0 +1,592 @@
+use gtk::glib;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::OrphanPolicy;
+
+use super::workspace_backend::{
+    detect_backend, DpmsSupport, MonitorInfo, WindowInfo, WorkspaceBackend, WorkspaceEvent, WorkspaceInfo, WorkspaceState,
+};
+use super::workspace_homes::WorkspaceHomes;
+use super::workspace_model::WorkspaceModel;
+
+type WorkspaceCallback = Box<dyn Fn(WorkspaceState)>;
+
+struct MonitorSubscription {
+    monitor_name: String,
+    callback: WorkspaceCallback,
+}
+
+struct WorkspaceServiceState {
+    model: WorkspaceModel,
+    subscribers: Vec<MonitorSubscription>,
+    _running: bool,
+}
+
+thread_local! {
+    static WORKSPACE_SERVICE: RefCell<Option<Rc<RefCell<WorkspaceServiceState>>>> = RefCell::new(None);
+}
+
+static BACKEND: OnceLock<Option<Arc<dyn WorkspaceBackend>>> = OnceLock::new();
+
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+static ORPHAN_POLICY: OnceLock<OrphanPolicy> = OnceLock::new();
+
+/// Monitors in most-recently-focused order, for `OrphanPolicy::LastFocused`
+static FOCUS_HISTORY: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Loaded from disk on first use
+static HOMES: Mutex<Option<WorkspaceHomes>> = Mutex::new(None);
+
+/// The newest model from the event listener thread that the main thread hasn't picked up yet.
+/// Bursts of events overwrite it, so the UI is only updated once per main loop iteration.
+static PENDING_MODEL: Mutex<Option<WorkspaceModel>> = Mutex::new(None);
+
+/// Compositor-independent workspace state shared by every `WorkspaceButton`
+pub struct WorkspaceService;
+
+impl WorkspaceService {
+    /// The autodetected compositor backend, if we're running under a supported compositor
+    fn backend() -> Option<Arc<dyn WorkspaceBackend>> {
+        BACKEND
+            .get_or_init(|| {
+                let backend = detect_backend();
+                match &backend {
+                    Some(backend) => eprintln!("[WorkspaceService] Using {} backend", backend.name()),
+                    None => eprintln!("[WorkspaceService] No supported compositor detected"),
+                }
+                backend
+            })
+            .clone()
+    }
+
+    /// Start the workspace service and return initial workspace state for a monitor
+    pub fn start(monitor_name: String) -> WorkspaceState {
+        WORKSPACE_SERVICE.with(|service| {
+            // If service already running, just return current state for this monitor
+            if let Some(state_ref) = service.borrow().as_ref() {
+                return Self::filter_workspace_state_for_monitor(&state_ref.borrow().model, &monitor_name);
+            }
+
+            // First time initialization
+            let model = Self::query_state();
+
+            let service_state = Rc::new(RefCell::new(WorkspaceServiceState {
+                model: model.clone(),
+                subscribers: Vec::new(),
+                _running: true,
+            }));
+
+            *service.borrow_mut() = Some(service_state.clone());
+
+            // Start event listener in a separate thread
+            Self::start_event_listener();
+            Self::start_dpms_watcher();
+
+            // Return filtered state for this monitor
+            Self::filter_workspace_state_for_monitor(&model, &monitor_name)
+        })
+    }
+
+    pub fn _stop() {
+        WORKSPACE_SERVICE.with(|service| {
+            if let Some(state) = service.borrow().as_ref() {
+                state.borrow_mut()._running = false;
+            }
+            *service.borrow_mut() = None;
+        });
+    }
+
+    pub fn subscribe<F>(monitor_name: String, callback: F)
+    where
+        F: Fn(WorkspaceState) + 'static,
+    {
+        WORKSPACE_SERVICE.with(|service| {
+            if let Some(state) = service.borrow().as_ref() {
+                state.borrow_mut().subscribers.push(MonitorSubscription {
+                    monitor_name,
+                    callback: Box::new(callback),
+                });
+            }
+        });
+    }
+
+    /// Switch to a workspace by ID
+    pub fn switch_workspace(workspace_id: i32) {
+        let Some(backend) = Self::backend() else { return };
+        if let Err(e) = backend.switch_workspace(workspace_id) {
+            eprintln!("Failed to switch workspace: {}", e);
+        }
+    }
+
+    /// Show or hide a special workspace on the focused monitor
+    pub fn toggle_special_workspace(workspace_name: &str) {
+        let Some(backend) = Self::backend() else { return };
+        if let Err(e) = backend.toggle_special_workspace(workspace_name) {
+            eprintln!("Failed to toggle special workspace: {}", e);
+        }
+    }
+
+    /// Focus a window by its address
+    pub fn focus_window(window_address: &str) {
+        let Some(backend) = Self::backend() else { return };
+        if let Err(e) = backend.focus_window(window_address) {
+            eprintln!("Failed to focus window: {}", e);
+        }
+    }
+
+    /// Whether windows can be minimized on the current compositor
+    pub fn can_minimize() -> bool {
+        Self::backend().is_some_and(|backend| backend.can_minimize())
+    }
+
+    /// Minimize a window by its address
+    pub fn minimize_window(window_address: &str) {
+        let Some(backend) = Self::backend() else { return };
+        if let Err(e) = backend.minimize_window(window_address) {
+            eprintln!("Failed to minimize window: {}", e);
+        }
+    }
+
+    /// Ask a window to close by its address
+    pub fn close_window(window_address: &str) {
+        let Some(backend) = Self::backend() else { return };
+        if let Err(e) = backend.close_window(window_address) {
+            eprintln!("Failed to close window: {}", e);
+        }
+    }
+
+    /// Whether windows can be floated, fullscreened and moved on the current compositor
+    pub fn can_arrange_windows() -> bool {
+        Self::backend().is_some_and(|backend| backend.can_arrange_windows())
+    }
+
+    /// Toggle whether a window floats above the tiled layout
+    pub fn toggle_floating(window_address: &str) {
+        let Some(backend) = Self::backend() else { return };
+        if let Err(e) = backend.toggle_floating(window_address) {
+            eprintln!("Failed to toggle floating: {}", e);
+        }
+    }
+
+    /// Toggle fullscreen for a window by its address
+    pub fn toggle_fullscreen(window_address: &str) {
+        let Some(backend) = Self::backend() else { return };
+        if let Err(e) = backend.toggle_fullscreen(window_address) {
+            eprintln!("Failed to toggle fullscreen: {}", e);
+        }
+    }
+
+    /// Move a window to another workspace, staying on the current one
+    pub fn move_window_to_workspace(window_address: &str, workspace_id: i32) {
+        let Some(backend) = Self::backend() else { return };
+        if let Err(e) = backend.move_window_to_workspace(window_address, workspace_id) {
+            eprintln!("Failed to move window: {}", e);
+        }
+    }
+
+    /// Move a workspace to another monitor at the user's request. It's no longer considered
+    /// displaced, so it isn't moved back when its previous home returns.
+    pub fn move_workspace_to_monitor(workspace_id: i32, monitor_name: &str) {
+        let Some(backend) = Self::backend() else { return };
+        match backend.move_workspace_to_monitor(workspace_id, monitor_name) {
+            Ok(()) => Self::with_homes(|homes| homes.clear_displaced(&[workspace_id])),
+            Err(e) => eprintln!("Failed to move workspace: {}", e),
+        }
+    }
+
+    /// Create a new workspace and switch to it, or switch to existing empty workspace
+    pub fn create_new_workspace_on_monitor(monitor_name: &str) {
+        let Some(backend) = Self::backend() else { return };
+        let workspaces = backend.query_workspaces().unwrap_or_default();
+
+        // Check if this monitor already has an empty workspace
+        let empty_workspace = workspaces
+            .iter()
+            .find(|ws| ws.monitor == monitor_name && ws.windows == 0 && !ws.special);
+
+        if let Some(empty_ws) = empty_workspace {
+            Self::switch_workspace(empty_ws.id);
+            return;
+        }
+
+        // No empty workspace on this monitor, create a new one
+        // Find the next available workspace ID
+        let max_id = workspaces.iter().map(|ws| ws.id).max().unwrap_or(0);
+        let new_workspace_id = max_id + 1;
+
+        if let Err(e) = backend.create_workspace_on_monitor(monitor_name, new_workspace_id) {
+            eprintln!("Failed to create new workspace: {}", e);
+        }
+    }
+
+    /// Set where orphaned workspaces go; see `OrphanPolicy`
+    pub fn set_orphan_policy(policy: OrphanPolicy) {
+        let _ = ORPHAN_POLICY.set(policy);
+    }
+
+    fn orphan_policy() -> OrphanPolicy {
+        ORPHAN_POLICY.get().cloned().unwrap_or_default()
+    }
+
+    fn focus_history() -> Vec<String> {
+        FOCUS_HISTORY.lock().unwrap().clone()
+    }
+
+    fn record_focus(monitor_name: &str) {
+        let mut history = FOCUS_HISTORY.lock().unwrap();
+        if history.first().map(String::as_str) != Some(monitor_name) {
+            history.retain(|m| m != monitor_name);
+            history.insert(0, monitor_name.to_string());
+        }
+    }
+
+    fn with_homes<T>(f: impl FnOnce(&mut WorkspaceHomes) -> T) -> T {
+        let mut homes = HOMES.lock().unwrap();
+        f(homes.get_or_insert_with(WorkspaceHomes::load))
+    }
+
+    /// Gather every workspace onto one monitor, e.g. before switching a KVM away from this
+    /// machine. Goes to the orphan policy's target, or the last focused monitor if the
+    /// policy is not to move anything automatically. Like moving a workspace by hand, the
+    /// moved workspaces aren't displaced, so they stay put when other monitors come back.
+    pub fn consolidate_workspaces() {
+        let Some(backend) = Self::backend() else { return };
+        let monitors = match backend.query_monitors() {
+            Ok(m) => m,
+            Err(e) => { eprintln!("[WorkspaceService] consolidate_workspaces: failed to query monitors: {}", e); return; }
+        };
+
+        let powered: Vec<String> = monitors.into_iter().filter(|m| m.dpms_on).map(|m| m.name).collect();
+        let history = Self::focus_history();
+        let target = Self::orphan_policy()
+            .target(&powered, &history)
+            .or_else(|| OrphanPolicy::LastFocused.target(&powered, &history));
+
+        match target {
+            Some(target) => Self::move_orphaned_workspaces_to(&target, std::slice::from_ref(&target), false),
+            None => eprintln!("[WorkspaceService] consolidate_workspaces: no powered monitor found"),
+        }
+    }
+
+    /// Move workspaces off any monitor not in `available_monitors`, following the orphan
+    /// policy. Callers pass the GDK connector list or the compositor's powered-on monitors
+    /// rather than this querying the compositor's monitor list, because compositor IPC can
+    /// lag behind GDK when multiple monitors disconnect simultaneously.
+    pub fn rehome_orphaned_workspaces(available_monitors: &[String]) {
+        let Some(target) = Self::orphan_policy().target(available_monitors, &Self::focus_history()) else {
+            return;
+        };
+        Self::move_orphaned_workspaces_to(&target, available_monitors, true);
+    }
+
+    /// Move any workspaces assigned to a connector not in `active_connectors`
+    /// to `target_monitor`. With `displace` they're moved back when their home returns.
+    fn move_orphaned_workspaces_to(target_monitor: &str, active_connectors: &[String], displace: bool) {
+        let Some(backend) = Self::backend() else { return };
+        let workspaces = match backend.query_workspaces() {
+            Ok(w) => w,
+            Err(e) => { eprintln!("[WorkspaceService] Failed to query workspaces: {}", e); return; }
+        };
+
+        // Special workspaces open on whichever monitor is focused, so they're never orphaned
+        for ws in workspaces.iter().filter(|ws| !ws.special && !active_connectors.contains(&ws.monitor)) {
+            // Marked before moving, so the listener thread can't record the target as its
+            // new home when the move event arrives first
+            let newly_displaced = displace && Self::with_homes(|homes| homes.mark_displaced(ws.id));
+            match backend.move_workspace_to_monitor(ws.id, target_monitor) {
+                Ok(()) if displace => {}
+                Ok(()) => Self::with_homes(|homes| homes.clear_displaced(&[ws.id])),
+                Err(e) => {
+                    if newly_displaced {
+                        Self::with_homes(|homes| homes.clear_displaced(&[ws.id]));
+                    }
+                    eprintln!("[WorkspaceService] Failed to move workspace {}: {}", ws.id, e);
+                }
+            }
+        }
+    }
+
+    /// Query the full workspace and window state from the backend
+    fn query_state() -> WorkspaceModel {
+        match Self::backend() {
+            Some(backend) => WorkspaceModel::query(backend.as_ref()),
+            None => WorkspaceModel { active_workspace_id: 1, ..Default::default() },
+        }
+    }
+
+    /// Filter workspace state for a specific monitor
+    fn filter_workspace_state_for_monitor(model: &WorkspaceModel, monitor_name: &str) -> WorkspaceState {
+        // Filter workspaces for this monitor and apply visibility rules
+        let monitor_workspaces = Self::filter_visible_workspaces(&model.workspaces, monitor_name);
+
+        // Get workspace IDs for this monitor. Windows on special workspaces are left out, since
+        // they're reached through the special workspace's toggle rather than the taskbar.
+        let monitor_workspace_ids: Vec<i32> = monitor_workspaces
+            .iter()
+            .filter(|ws| !ws.special)
+            .map(|ws| ws.id)
+            .collect();
+
+        // Filter windows to only those on this monitor's workspaces
+        let monitor_windows: Vec<WindowInfo> = model.windows
+            .iter()
+            .filter(|w| monitor_workspace_ids.contains(&w.workspace_id))
+            .cloned()
+            .collect();
+
+        WorkspaceState {
+            workspaces: monitor_workspaces,
+            windows: monitor_windows,
+            // The active workspace for THIS monitor specifically
+            active_workspace_id: model.active_workspace_for_monitor(monitor_name),
+            active_window_address: model.active_window_address.clone(),
+            special_workspace_id: model.monitors
+                .iter()
+                .find(|monitor| monitor.name == monitor_name)
+                .and_then(|monitor| monitor.special_workspace_id),
+            connected: model.connected,
+            _current_monitor: monitor_name.to_string(),
+        }
+    }
+
+    /// Filter workspaces according to the visibility rules:
+    /// - Show workspaces with windows
+    /// - Keep only one empty workspace per monitor
+    /// - Show special workspaces on every monitor, since they open on whichever is focused
+    fn filter_visible_workspaces(
+        workspaces: &[WorkspaceInfo],
+        monitor_name: &str,
+    ) -> Vec<WorkspaceInfo> {
+        let mut monitor_workspaces: Vec<WorkspaceInfo> = workspaces
+            .iter()
+            .filter(|ws| ws.monitor == monitor_name || ws.special)
+            .cloned()
+            .collect();
+
+        // Sort by position on the monitor
+        monitor_workspaces.sort_by_key(|ws| (ws.index, ws.id));
+
+        // Count empty workspaces
+        let empty_workspaces: Vec<&WorkspaceInfo> = monitor_workspaces
+            .iter()
+            .filter(|ws| ws.windows == 0 && !ws.special)
+            .collect();
+
+        // If there are multiple empty workspaces, keep only the first one
+        if empty_workspaces.len() > 1 {
+            let first_empty_id = empty_workspaces[0].id;
+            monitor_workspaces.retain(|ws| ws.windows > 0 || ws.id == first_empty_id);
+        }
+
+        monitor_workspaces
+    }
+
+    /// Notify all subscribers with updated workspace states
+    fn notify_subscribers() {
+        WORKSPACE_SERVICE.with(|service| {
+            if let Some(state_ref) = service.borrow().as_ref() {
+                let state = state_ref.borrow();
+
+                // Notify each subscriber with their monitor-specific state
+                for sub in &state.subscribers {
+                    let monitor_state = Self::filter_workspace_state_for_monitor(&state.model, &sub.monitor_name);
+                    (sub.callback)(monitor_state);
+                }
+            }
+        });
+    }
+
+    /// Hand a model built on the listener thread to the main thread and notify subscribers
+    fn publish_model(model: WorkspaceModel) {
+        if let Some(monitor) = &model.focused_monitor {
+            Self::record_focus(monitor);
+        }
+
+        if model.connected {
+            let powered: Vec<String> = model.monitors.iter().filter(|m| m.dpms_on).map(|m| m.name.clone()).collect();
+            Self::with_homes(|homes| homes.record(&model.workspaces, &model.monitors, &powered));
+        }
+
+        // Only schedule the main loop callback if one isn't already waiting
+        if PENDING_MODEL.lock().unwrap().replace(model).is_some() {
+            return;
+        }
+
+        glib::idle_add_once(move || {
+            let Some(model) = PENDING_MODEL.lock().unwrap().take() else { return };
+
+            WORKSPACE_SERVICE.with(|service| {
+                if let Some(state_ref) = service.borrow().as_ref() {
+                    state_ref.borrow_mut().model = model;
+                }
+            });
+
+            // Notify all subscribers
+            Self::notify_subscribers();
+        });
+    }
+
+    /// Bring displaced workspaces back to monitors that returned, then move workspaces off
+    /// monitors the compositor no longer reports or that are powered off
+    fn rehome_from_monitors(monitors: &[MonitorInfo]) {
+        // At this point the compositor has already updated its internal list,
+        // so its monitor list is the authoritative set.
+        let powered: Vec<String> = monitors.iter().filter(|m| m.dpms_on).map(|m| m.name.clone()).collect();
+        Self::restore_returning_workspaces(&powered);
+        Self::rehome_orphaned_workspaces(&powered);
+    }
+
+    /// Move workspaces we displaced back to their home monitor if it's in `available_monitors`,
+    /// and show the workspace each returning monitor had active before it went away
+    fn restore_returning_workspaces(available_monitors: &[String]) {
+        let Some(backend) = Self::backend() else { return };
+        let Ok(workspaces) = backend.query_workspaces() else { return };
+
+        let returning = Self::with_homes(|homes| {
+            homes.prune_displaced(&workspaces);
+            homes.returning(&workspaces, available_monitors)
+        });
+        if returning.is_empty() {
+            return;
+        }
+
+        let focused_workspace = backend.query_active_workspace().ok();
+        let mut restored = Vec::new();
+        let mut returned_monitors: Vec<String> = Vec::new();
+
+        for (workspace_id, home) in returning {
+            match backend.move_workspace_to_monitor(workspace_id, &home) {
+                Ok(()) => {
+                    eprintln!("[WorkspaceService] Restored workspace {} to {}", workspace_id, home);
+                    restored.push(workspace_id);
+                    if !returned_monitors.contains(&home) {
+                        returned_monitors.push(home);
+                    }
+                }
+                Err(e) => eprintln!("[WorkspaceService] Failed to restore workspace {} to {}: {}", workspace_id, home, e),
+            }
+        }
+
+        Self::with_homes(|homes| homes.clear_displaced(&restored));
+
+        // Switching to a workspace shows it on its monitor; do that for each returned monitor,
+        // then put focus back where it was
+        for monitor in &returned_monitors {
+            if let Some(workspace_id) = Self::with_homes(|homes| homes.active_workspace(monitor))
+                && restored.contains(&workspace_id)
+            {
+                let _ = backend.switch_workspace(workspace_id);
+            }
+        }
+        if let Some(workspace_id) = focused_workspace {
+            let _ = backend.switch_workspace(workspace_id);
+        }
+    }
+
+    /// Start listening to compositor events. The listener is supervised: whenever the event
+    /// stream fails or ends, subscribers are told we're disconnected and the backend is asked
+    /// to reconnect with exponential backoff, then the full state is re-queried.
+    fn start_event_listener() {
+        let Some(backend) = Self::backend() else { return };
+
+        thread::spawn(move || {
+            let mut backoff = RECONNECT_BACKOFF_MIN;
+
+            loop {
+                // The listener thread keeps its own copy of the state so events can be applied
+                // without touching the main thread until the result is ready
+                let model = RefCell::new(WorkspaceModel::query(backend.as_ref()));
+                Self::publish_model(model.borrow().clone());
+
+                let started = Instant::now();
+                let result = backend.listen(&|event| {
+                    match event {
+                        WorkspaceEvent::Update(update) => model.borrow_mut().apply(update),
+                        WorkspaceEvent::Changed => model.borrow_mut().refresh(backend.as_ref()),
+                        WorkspaceEvent::MonitorsChanged => {
+                            // When the compositor removes or powers off a monitor, immediately move
+                            // orphaned workspaces in the background thread. GDK doesn't always fire
+                            // items_changed for all monitor disconnections (e.g. DDC/CI keeps DP-2
+                            // "connected" in GDK).
+                            model.borrow_mut().refresh(backend.as_ref());
+                            Self::rehome_from_monitors(&model.borrow().monitors);
+                        }
+                    }
+
+                    Self::publish_model(model.borrow().clone());
+                });
+
+                match result {
+                    Ok(()) => eprintln!("[WorkspaceService] {} event stream ended", backend.name()),
+                    Err(e) => eprintln!("[WorkspaceService] {} event listener stopped: {}", backend.name(), e),
+                }
+
+                // Keep showing the last known state, marked as disconnected
+                let mut model = model.into_inner();
+                model.connected = false;
+                Self::publish_model(model);
+
+                // A listener that ran for a while was a healthy connection, so start over
+                if started.elapsed() >= RECONNECT_BACKOFF_MAX {
+                    backoff = RECONNECT_BACKOFF_MIN;
+                }
+
+                loop {
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+
+                    match backend.reconnect() {
+                        Ok(()) => break,
+                        Err(e) => eprintln!("[WorkspaceService] {} reconnect failed: {}", backend.name(), e),
+                    }
+                }
+            }
+        });
+    }
+
+    /// For backends that can't report DPMS changes as events, poll monitor power state every
+    /// 2 seconds. When a monitor goes from DPMS-on to DPMS-off (e.g. KVM switch), move its
+    /// workspaces according to the orphan policy, and move them back when it comes on again.
+    fn start_dpms_watcher() {
+        let Some(backend) = Self::backend() else { return };
+        if backend.dpms_support() != DpmsSupport::Polled {
+            return;
+        }
+
+        thread::spawn(move || {
+            // Give the compositor a moment to settle before we start polling.
+            thread::sleep(Duration::from_secs(3));
+
+            let mut prev_dpms: HashMap<String, bool> = HashMap::new();
+
+            loop {
+                thread::sleep(Duration::from_secs(2));
+
+                let monitors = match backend.query_monitors() {
+                    Ok(m) => m,
+                    Err(_) => continue,
+                };
+
+                let changed = monitors.iter()
+                    .filter(|m| prev_dpms.get(&m.name).is_some_and(|was_on| *was_on != m.dpms_on))
+                    .map(|m| format!("{} {}", m.name, if m.dpms_on { "on" } else { "off" }))
+                    .collect::<Vec<_>>();
+
+                if !changed.is_empty() {
+                    // Off means a KVM likely switched away; on means it switched back
+                    eprintln!("[WorkspaceService] DPMS changed: {}", changed.join(", "));
+                    Self::rehome_from_monitors(&monitors);
+                }
+
+                prev_dpms = monitors.into_iter().map(|m| (m.name, m.dpms_on)).collect();
+            }
+        });
+    }
+}
@@ -102,22 +102,22 @@ impl SystemPanel {
           let btn = crate::panel_buttons::LaunchButton::from_icon_name(icon, command.clone());
           container.append(btn.widget());
         }
-        PanelButtonConfig::Clock => {
-          let btn = crate::panel_buttons::ClockButton::new();
+        PanelButtonConfig::Clock(config) => {
+          let btn = crate::panel_buttons::ClockButton::new(config);
           container.append(btn.widget());
         }
-        PanelButtonConfig::Weather { location } => {
-          let btn = crate::panel_buttons::WeatherButton::new(&location);
+        PanelButtonConfig::Weather(config) => {
+          let btn = crate::panel_buttons::WeatherButton::new(config);
           container.append(btn.widget());
         }
-        PanelButtonConfig::Workspace => {
+        PanelButtonConfig::Workspace(config) => {
           if let Some(name) = monitor_name {
-            let btn = crate::panel_buttons::WorkspaceButton::new_with_monitor(name.to_string());
+            let btn = crate::panel_buttons::WorkspaceButton::new_with_monitor(name.to_string(), config.clone());
             container.append(btn.widget());
           }
         }
-        PanelButtonConfig::Network => {
-          let btn = crate::panel_buttons::NetworkButton::new();
+        PanelButtonConfig::Network { hotspot } => {
+          let btn = crate::panel_buttons::NetworkButton::new(hotspot.clone());
           container.append(btn.widget());
         }
         PanelButtonConfig::Brightness => {
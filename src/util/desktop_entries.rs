@@ -0,0 +1,293 @@
+use gtk::gio::{self, prelude::*};
+use gtk::glib;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+static INDEX: RwLock<Option<Arc<DesktopEntryIndex>>> = RwLock::new(None);
+
+thread_local! {
+  static WATCHERS: RefCell<Vec<gio::FileMonitor>> = const { RefCell::new(Vec::new()) };
+  static REFRESH_PENDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Coalesce bursts of changes, e.g. a package manager installing many entries at once
+const REFRESH_DELAY: Duration = Duration::from_millis(500);
+
+/// The parts of an application's `.desktop` file the panel uses
+#[derive(Clone, Debug)]
+pub struct DesktopEntry {
+  /// Name in the user's locale
+  pub name: String,
+  pub icon: Option<String>,
+  pub exec: Option<String>,
+  pub startup_wm_class: Option<String>,
+}
+
+/// Every application desktop entry in the XDG data directories, indexed by the names
+/// compositors report for windows. Shared by everything in the panel that needs to map a
+/// window or app to its entry; use `DesktopEntryIndex::shared()`.
+#[derive(Debug, Default)]
+pub struct DesktopEntryIndex {
+  /// Entries by lowercased desktop ID
+  entries: HashMap<String, DesktopEntry>,
+  /// Lowercased desktop IDs by lowercased `StartupWMClass`
+  by_wm_class: HashMap<String, String>,
+  /// Lowercased desktop IDs by lowercased executable name
+  by_exec: HashMap<String, String>,
+  /// Lowercased desktop IDs by the last part of a reverse-DNS ID, e.g. `nautilus`
+  by_id_suffix: HashMap<String, String>,
+}
+
+impl DesktopEntryIndex {
+  /// The process-wide index, built on first use. When first used on the GTK main thread the
+  /// application directories are watched and the index is rebuilt when they change.
+  pub fn shared() -> Arc<DesktopEntryIndex> {
+    if let Some(index) = INDEX.read().unwrap().as_ref() {
+      return index.clone();
+    }
+
+    let index = Arc::new(Self::build());
+    *INDEX.write().unwrap() = Some(index.clone());
+
+    if glib::MainContext::default().is_owner() {
+      Self::watch_directories();
+    }
+
+    index
+  }
+
+  /// Find the entry for an app ID or window class, trying in order the desktop ID,
+  /// `StartupWMClass`, executable name and the last part of a reverse-DNS desktop ID
+  pub fn lookup(&self, app_id: &str) -> Option<&DesktopEntry> {
+    let key = app_id.trim_end_matches(".desktop").to_lowercase();
+    if let Some(entry) = self.entries.get(&key) {
+      return Some(entry);
+    }
+
+    [&self.by_wm_class, &self.by_exec, &self.by_id_suffix]
+      .into_iter()
+      .find_map(|index| index.get(&key))
+      .and_then(|id| self.entries.get(id))
+  }
+
+  fn build() -> Self {
+    let locales = locale_variants();
+    let mut index = Self::default();
+    // Entries that set Hidden=true delete the ID for directories further down the list
+    let mut hidden: Vec<String> = Vec::new();
+
+    for dir in application_dirs() {
+      let mut files = Vec::new();
+      collect_desktop_files(&dir, &mut files);
+
+      for path in files {
+        let Some(id) = desktop_id(&dir, &path) else { continue };
+        let key = id.to_lowercase();
+        if index.entries.contains_key(&key) || hidden.contains(&key) {
+          continue;
+        }
+
+        let Ok(contents) = fs::read_to_string(&path) else { continue };
+        match parse_desktop_entry(&id, &contents, &locales) {
+          Some(entry) => index.insert(key, entry),
+          None => hidden.push(key),
+        }
+      }
+    }
+
+    index
+  }
+
+  fn insert(&mut self, key: String, entry: DesktopEntry) {
+    if let Some(wm_class) = &entry.startup_wm_class {
+      self.by_wm_class.entry(wm_class.to_lowercase()).or_insert_with(|| key.clone());
+    }
+    if let Some(exec) = entry.exec.as_deref().and_then(exec_name) {
+      self.by_exec.entry(exec.to_lowercase()).or_insert_with(|| key.clone());
+    }
+    if let Some((_, suffix)) = key.rsplit_once('.') {
+      self.by_id_suffix.entry(suffix.to_string()).or_insert_with(|| key.clone());
+    }
+    self.entries.insert(key, entry);
+  }
+
+  fn watch_directories() {
+    WATCHERS.with(|watchers| {
+      let mut watchers = watchers.borrow_mut();
+      watchers.clear();
+
+      for dir in application_dirs() {
+        // Missing directories can be watched too, and report being created
+        let Ok(monitor) = gio::File::for_path(&dir)
+          .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+        else {
+          continue;
+        };
+
+        monitor.connect_changed(|_, _, _, _| Self::schedule_refresh());
+        watchers.push(monitor);
+      }
+    });
+  }
+
+  fn schedule_refresh() {
+    if REFRESH_PENDING.replace(true) {
+      return;
+    }
+
+    glib::timeout_add_local_once(REFRESH_DELAY, || {
+      REFRESH_PENDING.set(false);
+      *INDEX.write().unwrap() = Some(Arc::new(Self::build()));
+    });
+  }
+}
+
+/// `applications` directories in XDG precedence order: `$XDG_DATA_HOME`, then
+/// `$XDG_DATA_DIRS`, then flatpak's export directories if they weren't already listed
+fn application_dirs() -> Vec<PathBuf> {
+  let home = env::var_os("HOME").map(PathBuf::from);
+
+  let data_home = env::var_os("XDG_DATA_HOME")
+    .filter(|dir| !dir.is_empty())
+    .map(PathBuf::from)
+    .or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+
+  let data_dirs = env::var("XDG_DATA_DIRS")
+    .ok()
+    .filter(|dirs| !dirs.is_empty())
+    .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+  let flatpak_dirs = [
+    home.as_ref().map(|home| home.join(".local/share/flatpak/exports/share")),
+    Some(PathBuf::from("/var/lib/flatpak/exports/share")),
+  ];
+
+  let mut dirs: Vec<PathBuf> = Vec::new();
+  let candidates = data_home
+    .into_iter()
+    .chain(data_dirs.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from))
+    .chain(flatpak_dirs.into_iter().flatten());
+
+  for dir in candidates {
+    let dir = dir.join("applications");
+    if !dirs.contains(&dir) {
+      dirs.push(dir);
+    }
+  }
+
+  dirs
+}
+
+fn collect_desktop_files(dir: &Path, files: &mut Vec<PathBuf>) {
+  let Ok(entries) = fs::read_dir(dir) else { return };
+
+  for entry in entries.flatten() {
+    let path = entry.path();
+    if path.is_dir() {
+      collect_desktop_files(&path, files);
+    } else if path.extension().is_some_and(|ext| ext == "desktop") {
+      files.push(path);
+    }
+  }
+}
+
+/// The desktop file ID: the path below the applications directory with `/` replaced by `-`
+/// and without the `.desktop` extension
+fn desktop_id(dir: &Path, path: &Path) -> Option<String> {
+  let relative = path.strip_prefix(dir).ok()?.to_str()?;
+  Some(relative.trim_end_matches(".desktop").replace('/', "-"))
+}
+
+/// Parse the `[Desktop Entry]` group. Returns None for entries marked `Hidden`, which count
+/// as deleted.
+fn parse_desktop_entry(id: &str, contents: &str, locales: &[String]) -> Option<DesktopEntry> {
+  let mut values: HashMap<&str, &str> = HashMap::new();
+  let mut in_entry_group = false;
+
+  for line in contents.lines() {
+    let line = line.trim();
+    if line.starts_with('[') {
+      in_entry_group = line == "[Desktop Entry]";
+      continue;
+    }
+    if !in_entry_group || line.starts_with('#') {
+      continue;
+    }
+    if let Some((key, value)) = line.split_once('=') {
+      values.entry(key.trim()).or_insert(value.trim());
+    }
+  }
+
+  if values.get("Hidden") == Some(&"true") {
+    return None;
+  }
+
+  // Localized keys look like Name[de_DE]; take the most specific match for the user's locale
+  let localized = |key: &str| {
+    locales
+      .iter()
+      .find_map(|locale| values.get(format!("{}[{}]", key, locale).as_str()))
+      .or_else(|| values.get(key))
+      .map(|value| value.to_string())
+  };
+
+  Some(DesktopEntry {
+    name: localized("Name").unwrap_or_else(|| id.to_string()),
+    icon: localized("Icon").filter(|icon| !icon.is_empty()),
+    exec: values.get("Exec").map(|exec| exec.to_string()),
+    startup_wm_class: values.get("StartupWMClass").map(|class| class.to_string()),
+  })
+}
+
+/// The program an `Exec` line runs, skipping `env` and its variable assignments
+fn exec_name(exec: &str) -> Option<String> {
+  let program = exec
+    .split_whitespace()
+    .map(|arg| arg.trim_matches('"'))
+    .find(|arg| *arg != "env" && !arg.contains('='))?;
+
+  Path::new(program).file_name()?.to_str().map(|name| name.to_string())
+}
+
+/// Locale suffixes to try for localized keys, most specific first, from `LC_ALL`,
+/// `LC_MESSAGES` or `LANG` (e.g. `de_DE.UTF-8@euro` gives `de_DE@euro`, `de_DE`, `de@euro`, `de`)
+fn locale_variants() -> Vec<String> {
+  let Some(locale) = ["LC_ALL", "LC_MESSAGES", "LANG"]
+    .iter()
+    .filter_map(|var| env::var(var).ok())
+    .find(|value| !value.is_empty())
+  else {
+    return Vec::new();
+  };
+
+  let (locale, modifier) = match locale.split_once('@') {
+    Some((locale, modifier)) => (locale.to_string(), Some(modifier.to_string())),
+    None => (locale, None),
+  };
+  let locale = locale.split('.').next().unwrap_or_default();
+  let (lang, country) = match locale.split_once('_') {
+    Some((lang, country)) => (lang, Some(country)),
+    None => (locale, None),
+  };
+
+  let mut variants = Vec::new();
+  if let (Some(country), Some(modifier)) = (country, &modifier) {
+    variants.push(format!("{}_{}@{}", lang, country, modifier));
+  }
+  if let Some(country) = country {
+    variants.push(format!("{}_{}", lang, country));
+  }
+  if let Some(modifier) = &modifier {
+    variants.push(format!("{}@{}", lang, modifier));
+  }
+  if !lang.is_empty() && lang != "C" && lang != "POSIX" {
+    variants.push(lang.to_string());
+  }
+
+  variants
+}
@@ -1 +1,2 @@
+pub mod desktop_entries;
 pub mod process;
@@ -53,6 +53,12 @@ impl PanelButton {
     self.set_property("menu", menu.as_list_store());
   }
 
+  /// Menu shown on right-click. Its items are reported through `connect_menu_item_clicked`
+  /// like those of the regular menu.
+  pub fn set_context_menu(&self, menu: TypedListStore<MenuItemModel>) {
+    self.set_property("context-menu", menu.as_list_store());
+  }
+
   pub fn icon_name(&self) -> Option<String> {
     self.property::<Option<String>>("icon-name")
   }
@@ -61,6 +67,10 @@ impl PanelButton {
     self.set_property("icon-name", &icon_name.to_value());
   }
 
+  pub fn clear_icon_name(&self) {
+    self.set_property("icon-name", None::<String>);
+  }
+
   pub fn set_text(&self, text: &str) {
     self.set_property("text", &text.to_value());
   }
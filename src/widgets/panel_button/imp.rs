@@ -4,9 +4,10 @@ use gtk::glib::object::ObjectExt;
 use gtk::glib::subclass::Signal;
 use gtk::glib::types::StaticType;
 use gtk::glib::value::ToValue;
-use gtk::prelude::WidgetExt;
+use gtk::prelude::{GestureSingleExt, WidgetExt};
 use gtk::subclass::widget::{WidgetClassExt, WidgetImpl};
-use gtk::{BinLayout, Orientation, Widget};
+use gtk::gdk::BUTTON_SECONDARY;
+use gtk::{BinLayout, GestureClick, Orientation, Widget};
 use gtk::glib::{self, ParamSpec, ParamSpecObject, ParamSpecString, Value, object_subclass};
 use std::cell::{OnceCell, RefCell};
 use std::sync::OnceLock;
@@ -28,6 +29,7 @@ pub struct PanelButtonImp {
   dropdown_widget: RefCell<Option<Widget>>,
   button: OnceCell<Button>,
   menu: OnceCell<DropdownMenu>,
+  context_menu: OnceCell<DropdownMenu>,
   dropdown_component: OnceCell<DropdownComponent>,
 }
 
@@ -41,6 +43,7 @@ impl Default for PanelButtonImp {
       dropdown_widget: RefCell::new(None),
       button: OnceCell::new(),
       menu: OnceCell::new(),
+      context_menu: OnceCell::new(),
       dropdown_component: OnceCell::new(),
     }
   }
@@ -77,6 +80,7 @@ impl ObjectImpl for PanelButtonImp {
         ParamSpecObject::builder::<Widget>("custom-widget").build(),
         ParamSpecObject::builder::<Widget>("dropdown-widget").build(),
         ParamSpecObject::builder::<ListStore>("menu").build(),
+        ParamSpecObject::builder::<ListStore>("context-menu").build(),
       ]
     })
   }
@@ -87,8 +91,8 @@ impl ObjectImpl for PanelButtonImp {
       "icon-name" => self.icon_name.borrow().to_value(),
       "custom-widget" => self.custom_widget.borrow().to_value(),
       "dropdown-widget" => self.dropdown_widget.borrow().to_value(),
-      "menu" => {
-        // Return an empty ListStore since menu is write-only
+      "menu" | "context-menu" => {
+        // Return an empty ListStore since menus are write-only
         ListStore::new::<MenuItemModel>().to_value()
       }
       _ => unimplemented!(),
@@ -140,6 +144,19 @@ impl ObjectImpl for PanelButtonImp {
             menu.set_menu(typed_store);
           }
       }
+      "context-menu" => {
+          let list_store: ListStore = value.get().expect("type checked upstream");
+          let typed_store: TypedListStore<MenuItemModel> = TypedListStore::from_list_store(list_store);
+
+          // Create context menu if it doesn't exist yet
+          if self.context_menu.get().is_none() {
+            self.create_context_menu();
+          }
+
+          if let Some(menu) = self.context_menu.get() {
+            menu.set_menu(typed_store);
+          }
+      }
       _ => unimplemented!(),
     }
   }
@@ -211,6 +228,40 @@ impl PanelButtonImp {
     self.menu.set(menu).expect("Failed to set menu");
   }
 
+  fn create_context_menu(&self) {
+    let obj = self.obj();
+    let menu = DropdownMenu::new(&obj);
+
+    if let Some(button) = self.button.get() {
+      menu.set_parent(button);
+    }
+
+    let obj_clone = obj.clone();
+    menu.connect_menu_clicked(move |model| {
+      obj_clone.emit_by_name::<()>("menu-item-clicked", &[&model]);
+    });
+
+    // Right-click opens the context menu, leaving left-click to the button
+    let right_click = GestureClick::new();
+    right_click.set_button(BUTTON_SECONDARY);
+    let obj_weak = obj.downgrade();
+    right_click.connect_released(move |_, _, _, _| {
+      let Some(obj) = obj_weak.upgrade() else { return };
+
+      let imp = obj.imp();
+      if let Some(context_menu) = imp.context_menu.get() {
+        if let Some(menu) = imp.menu.get() {
+          menu.hide_menu();
+        }
+        PanelButton::close_other_instances(&obj);
+        context_menu.toggle_visibility();
+      }
+    });
+    obj.add_controller(right_click);
+
+    self.context_menu.set(menu).expect("Failed to set context menu");
+  }
+
   fn create_dropdown_component(&self) {
     let obj = self.obj();
     let dropdown = DropdownComponent::new(&obj);
@@ -242,6 +293,9 @@ impl PanelButtonImp {
     if let Some(menu) = self.menu.get() {
       menu.hide_menu();
     }
+    if let Some(context_menu) = self.context_menu.get() {
+      context_menu.hide_menu();
+    }
     if let Some(dropdown) = self.dropdown_component.get() {
       dropdown.hide();
     }
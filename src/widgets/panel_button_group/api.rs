@@ -23,6 +23,10 @@ impl PanelButtonGroup {
     self.imp().remove_button(button);
   }
 
+  pub fn set_buttons(&self, buttons: &[PanelButton]) {
+    self.imp().set_buttons(buttons);
+  }
+
   pub fn clear(&self) {
     self.imp().clear();
   }
@@ -81,6 +81,27 @@ impl PanelButtonGroupImp {
     self.buttons.borrow_mut().retain(|b| b != button);
   }
 
+  /// Make `buttons` the group's contents in that order. Buttons already in the group are
+  /// moved rather than re-added, so they keep their state and don't flicker.
+  pub fn set_buttons(&self, buttons: &[PanelButton]) {
+    let current = self.buttons.borrow().clone();
+    for button in current.iter().filter(|b| !buttons.contains(b)) {
+      self.container.remove(button);
+    }
+
+    let mut previous: Option<&PanelButton> = None;
+    for button in buttons {
+      if current.contains(button) {
+        self.container.reorder_child_after(button, previous);
+      } else {
+        self.container.insert_child_after(button, previous);
+      }
+      previous = Some(button);
+    }
+
+    *self.buttons.borrow_mut() = buttons.to_vec();
+  }
+
   pub fn clear(&self) {
     let buttons = self.buttons.borrow().clone();
     for button in buttons {